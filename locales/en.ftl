@@ -0,0 +1,293 @@
+# English messages. Every id used in the game must be here; other languages
+# fall back to these for anything they leave out.
+
+title = Tetris
+play = Play
+online = Online
+leaderboard = Leaderboard
+achievements = Achievements
+history = History
+settings = Settings
+quit = Quit
+back = Back
+main-menu = Main Menu
+restart = Restart
+pause = Pause
+resume = Resume
+undo = Undo
+save = Save
+reload = Reload
+saved = Saved { $path }
+loaded = Loaded { $path }
+select-mode = Select mode
+practice = Practice
+sequence = Sequence
+
+mode-endless = Endless
+mode-sprint = Sprint
+mode-ultra = Ultra
+mode-btype = B-Type
+mode-sequence = Sequence
+
+# Playing
+hold = Hold
+next = Next
+score = Score
+level = Level
+level-number = Level { $level }
+combo = COMBO
+back-to-back = BACK-TO-BACK
+popup-points = { $name } +{ $points }
+popup-combo = COMBO x{ $combo }
+paused = PAUSED
+go = GO!
+press-to-resume = Press P to resume
+goal-lines = Lines: { $cleared }/{ $goal }
+goal-time = Left: { $left }
+rewind = Rewind { $steps }
+undo-steps = { $steps } undo steps
+garbage = Garbage
+
+clear-single = SINGLE
+clear-double = DOUBLE
+clear-triple = TRIPLE
+clear-tetris = TETRIS
+clear-tspin = T-SPIN { $lines }
+clear-tspin-mini = T-SPIN MINI { $lines }
+
+top-out-block = Block out
+top-out-lock = Lock out
+top-out-garbage = Garbage out
+
+finesse = Finesse
+finesse-strict = Strict
+finesse-faults = Faults: { $faults } in { $pieces } pieces
+finesse-fault = { $piece }: { $used } inputs, { $optimal } needed
+assist = Hint
+assist-hold-key = { $key } (hold)
+
+# Stats and history
+date = Date
+mode = Mode
+lines = Lines
+time = Time
+pieces = Pieces
+pps = PPS
+apm = APM
+faults = Faults
+stats-singles = Singles
+stats-doubles = Doubles
+stats-triples = Triples
+stats-tetrises = Tetrises
+stats-tspins = T-spins
+stats-max-combo = Max combo
+history-export-csv = Export CSV
+history-export-json = Export JSON
+history-export-failed = Export failed: { $error }
+history-save-failed = Could not save game: { $error }
+
+# Results
+results-finished = Finished!
+results-game-over = Game Over!
+results-randomizer = Randomizer: { $randomizer }
+results-curve = Speed curve: { $curve }
+results-best = Best: { $score }
+results-high-score = New high score!
+score-submitting = Submitting score...
+score-submitted = Score submitted
+score-submit-failed = Could not submit score: { $error }
+
+# Leaderboard
+leaderboard-global = Global top 100
+leaderboard-local = Local top 100
+leaderboard-lines = { $lines } lines
+leaderboard-loading = Loading...
+leaderboard-no-url = No leaderboard URL set, showing local results
+leaderboard-offline = Offline ({ $error }), showing local results
+
+# Achievements
+achievement-unlocked = Achievement unlocked
+achievement-locked = Locked
+achievements-progress = { $unlocked } of { $total } unlocked
+achievement-first-tetris = Tetris!
+achievement-first-tetris-description = Clear four lines at once
+achievement-tspin-double = Spin Doctor
+achievement-tspin-double-description = Clear two lines with a T-spin
+achievement-tspin-triple = Triple Threat
+achievement-tspin-triple-description = Clear three lines with a T-spin
+achievement-perfect-clear = All Clear
+achievement-perfect-clear-description = Leave the board completely empty
+achievement-combo-10 = Chain Reaction
+achievement-combo-10-description = Reach a 10 combo
+achievement-back-to-back-5 = Relentless
+achievement-back-to-back-5-description = Chain five back-to-back clears
+achievement-score-100k = Six Figures
+achievement-score-100k-description = Score 100,000 points in one game
+achievement-lines-150 = Marathoner
+achievement-lines-150-description = Clear 150 lines in one game
+achievement-sprint-under-60 = Speed Demon
+achievement-sprint-under-60-description = Finish a 40 line sprint in under a minute
+achievement-ultra-score-50k = Ultra Violence
+achievement-ultra-score-50k-description = Score 50,000 points in Ultra
+
+# Quests
+quests-daily = Daily quests
+quests-weekly = Weekly quests
+quests-stars = Quest stars: { $stars }
+quests-next-palette = { $palette } palette unlocks at { $stars } stars
+quest-complete = Quest complete: { $quest }
+quest-lines = Clear { $count } lines
+quest-tetrises = Clear { $count } Tetrises
+quest-tspin-doubles = Perform { $count } T-spin doubles
+quest-pieces = Place { $count } pieces
+quest-games = Play { $count } games
+quest-combo = Reach a { $combo } combo
+quest-sprint = Finish a sprint under { $time }
+quest-ultra = Score { $score } in Ultra
+
+# Piece sequences and fumen
+sequence-title = Piece sequence
+sequence-pieces = Pieces (IOTLJSZ)
+sequence-repeat = Repeat
+sequence-board = Starting board (. empty, X garbage, or piece letters)
+sequence-start = Start
+sequence-clear = Clear
+sequence-restart-hint = R restarts from the beginning of the sequence.
+sequence-unknown-piece = Unknown piece '{ $piece }'
+sequence-empty = Sequence is empty
+board-too-tall = Board has more than { $rows } rows
+board-too-wide = Row { $row } is wider than { $cells } cells
+board-unknown-cell = Unknown cell '{ $cell }' in row { $row }
+fumen = Fumen
+fumen-import = Import
+fumen-export = Export
+fumen-not-v115 = Not a v115 fumen
+fumen-truncated = Fumen data is truncated
+fumen-invalid-character = Invalid fumen character '{ $character }'
+fumen-overflow = Field data overflows the board
+fumen-no-pages = Fumen has no pages
+fumen-invalid-piece = Invalid piece in fumen
+
+# Settings
+settings-language = Language
+settings-language-system = System default
+language-english = English
+language-japanese = Japanese
+settings-gravity = Gravity
+settings-lock-delay = Lock delay
+settings-das = DAS
+settings-arr = ARR
+settings-clear-delay = Line clear delay
+settings-are = Entry delay (ARE)
+settings-initial-actions = Initial rotation/hold
+settings-soft-drop = Soft drop speed
+settings-soft-drop-instant = Instant
+settings-kicks = Wall kicks
+settings-effects = Particle effects
+settings-smooth-motion = Smooth piece motion
+settings-danger = Danger warning
+settings-danger-strength = strength
+settings-touch-gestures = Touch gestures
+settings-control-buttons = On-screen buttons
+settings-mirror = Mirror
+settings-mirror-pieces = Pieces
+settings-mirror-board = Board
+settings-accessibility = Accessibility
+settings-high-contrast = High contrast
+settings-large-board = Large board
+settings-reduce-flashing = Reduce flashing
+settings-btype = B-Type garbage
+settings-btype-holes = holes
+settings-skin = Block skin
+settings-skin-flat = Flat colors
+settings-sound = Sound effects
+settings-volume = Volume
+settings-sound-pack = Sound pack
+settings-sound-pack-built-in = Built-in
+settings-palette = Palette
+settings-patterns = Piece patterns
+settings-player-name = Player name
+settings-leaderboard-url = Leaderboard URL
+settings-leaderboard-url-hint = https://... (blank for offline)
+settings-discord = Discord status
+settings-discord-app-id = Application ID
+settings-colors = Colors
+settings-controls = Controls
+unit-rows = rows
+palette-standard = Standard
+palette-colorblind = Colorblind safe
+palette-pastel = Pastel
+palette-neon = Neon
+palette-retro = Retro
+palette-locked = { $palette } ({ $stars } stars)
+palette-still-locked = { $palette } needs { $stars } quest stars, so the default palette is used
+colors-reset = Reset colors
+randomizer-bag7 = 7-bag
+randomizer-bag14 = 14-bag
+randomizer-classic = Classic
+randomizer-tgm = TGM history
+randomizer-memoryless = Memoryless
+curve-fixed = Fixed speed
+keybinds-press-key = Press a key...
+preset = Controls
+preset-custom = Custom
+preset-guideline = Guideline
+preset-wasd = WASD
+preset-left-handed = Left-handed
+preset-one-handed = One-handed
+preset-vi = Vi keys
+action-move-left = Move left
+action-move-right = Move right
+action-soft-drop = Soft drop
+action-hard-drop = Hard drop
+action-rotate-cw = Rotate right
+action-rotate-ccw = Rotate left
+action-rotate-180 = Rotate 180
+
+# Online lobby
+lobby-server = Server
+lobby-connect = Connect
+lobby-connecting = Connecting...
+lobby-disconnect = Disconnect
+lobby-disconnected = Disconnected from the server
+lobby-lost = Lost the connection: { $error }
+lobby-refresh = Refresh
+lobby-quick-match = Quick match
+lobby-no-rooms = No rooms open yet
+lobby-join = Join
+lobby-room-name = Room name
+lobby-default-room = { $player }'s room
+lobby-create = Create room
+lobby-mode = Mode
+lobby-best-of = Best of { $games }
+lobby-leave = Leave
+lobby-host = (host)
+lobby-ready = Ready
+lobby-not-ready = Not ready
+lobby-wins = { $wins } wins
+lobby-in-game = Game in progress
+lobby-handicap = Handicap
+lobby-handicap-garbage = Starting garbage
+lobby-handicap-gravity = Gravity
+lobby-handicap-attack = Attack
+lobby-handicap-preview = Preview
+lobby-send = Send
+lobby-game-won = { $player } won the game
+lobby-game-drawn = The game ended without a winner
+lobby-match-won = { $player } won the match!
+lobby-opponent-left = Your opponent left
+lobby-rejected = The server rejected the result: { $reason }
+lobby-unknown-mode = { $mode } isn't installed here, so this game is Endless
+lobby-bad-input = Lost track of the opponent's inputs: { $error }
+lobby-waiting = Waiting for { $player }...
+lobby-opponent-incoming = { $lines } incoming
+lobby-desync = The boards went out of sync at frame { $frame }
+lobby-watch = Watch
+lobby-watching = Watching this room
+lobby-spectators = Watching: { $names }
+lobby-player-left = A player left the game
+
+# Discord status
+presence-state = Score { $score } · Level { $level }
+presence-paused = { $state } (paused)
+presence-menus = In the menus
@@ -0,0 +1,292 @@
+# 日本語のメッセージ。ここにない id は英語で表示されます。
+
+title = テトリス
+play = プレイ
+online = オンライン
+leaderboard = ランキング
+achievements = 実績
+history = 履歴
+settings = 設定
+quit = 終了
+back = 戻る
+main-menu = メインメニュー
+restart = リスタート
+pause = 一時停止
+resume = 再開
+undo = 元に戻す
+save = 保存
+reload = 再読み込み
+saved = { $path } に保存しました
+loaded = { $path } を読み込みました
+select-mode = モード選択
+practice = 練習
+sequence = ミノ順
+
+mode-endless = エンドレス
+mode-sprint = スプリント
+mode-ultra = ウルトラ
+mode-btype = Bタイプ
+mode-sequence = ミノ順指定
+
+# プレイ中
+hold = ホールド
+next = ネクスト
+score = スコア
+level = レベル
+level-number = レベル { $level }
+combo = COMBO
+back-to-back = BACK-TO-BACK
+popup-points = { $name } +{ $points }
+popup-combo = COMBO x{ $combo }
+paused = 一時停止中
+go = GO!
+press-to-resume = P キーで再開
+goal-lines = ライン: { $cleared }/{ $goal }
+goal-time = 残り: { $left }
+rewind = { $steps } 手戻す
+undo-steps = 戻せる手数: { $steps }
+garbage = おじゃま
+
+clear-single = シングル
+clear-double = ダブル
+clear-triple = トリプル
+clear-tetris = テトリス
+clear-tspin = Tスピン { $lines }
+clear-tspin-mini = Tスピンミニ { $lines }
+
+top-out-block = ブロックアウト
+top-out-lock = ロックアウト
+top-out-garbage = おじゃまによる押し出し
+
+finesse = 最適化
+finesse-strict = 厳格
+finesse-faults = ミス: { $faults } / { $pieces } 個
+finesse-fault = { $piece }: { $used } 操作 (最短 { $optimal })
+assist = ヒント
+assist-hold-key = { $key } (長押し)
+
+# 統計と履歴
+date = 日時
+mode = モード
+lines = ライン
+time = タイム
+pieces = ピース
+pps = PPS
+apm = APM
+faults = ミス
+stats-singles = シングル
+stats-doubles = ダブル
+stats-triples = トリプル
+stats-tetrises = テトリス
+stats-tspins = Tスピン
+stats-max-combo = 最大コンボ
+history-export-csv = CSV で書き出し
+history-export-json = JSON で書き出し
+history-export-failed = 書き出しに失敗しました: { $error }
+history-save-failed = ゲームを保存できませんでした: { $error }
+
+# 結果
+results-finished = クリア!
+results-game-over = ゲームオーバー!
+results-randomizer = ツモ: { $randomizer }
+results-curve = 速度カーブ: { $curve }
+results-best = ベスト: { $score }
+results-high-score = ハイスコア更新!
+score-submitting = スコアを送信中...
+score-submitted = スコアを送信しました
+score-submit-failed = スコアを送信できませんでした: { $error }
+
+# ランキング
+leaderboard-global = 世界トップ 100
+leaderboard-local = ローカルトップ 100
+leaderboard-lines = { $lines } ライン
+leaderboard-loading = 読み込み中...
+leaderboard-no-url = ランキングの URL が未設定のため、ローカルの記録を表示しています
+leaderboard-offline = オフライン ({ $error }) のため、ローカルの記録を表示しています
+
+# 実績
+achievement-unlocked = 実績解除
+achievement-locked = 未解除
+achievements-progress = { $total } 個中 { $unlocked } 個解除
+achievement-first-tetris = テトリス!
+achievement-first-tetris-description = 4 ラインを同時に消す
+achievement-tspin-double = スピンドクター
+achievement-tspin-double-description = Tスピンで 2 ライン消す
+achievement-tspin-triple = トリプルスレット
+achievement-tspin-triple-description = Tスピンで 3 ライン消す
+achievement-perfect-clear = 全消し
+achievement-perfect-clear-description = 盤面を完全に空にする
+achievement-combo-10 = 連鎖反応
+achievement-combo-10-description = 10 コンボを達成する
+achievement-back-to-back-5 = 怒涛
+achievement-back-to-back-5-description = Back-to-Back を 5 回つなげる
+achievement-score-100k = 6 桁
+achievement-score-100k-description = 1 ゲームで 100,000 点を取る
+achievement-lines-150 = マラソンランナー
+achievement-lines-150-description = 1 ゲームで 150 ライン消す
+achievement-sprint-under-60 = スピードの鬼
+achievement-sprint-under-60-description = 40 ラインスプリントを 1 分以内にクリアする
+achievement-ultra-score-50k = ウルトラバイオレンス
+achievement-ultra-score-50k-description = ウルトラで 50,000 点を取る
+
+# クエスト
+quests-daily = デイリークエスト
+quests-weekly = ウィークリークエスト
+quests-stars = クエストスター: { $stars }
+quests-next-palette = スター { $stars } 個で { $palette } パレットを解除
+quest-complete = クエスト達成: { $quest }
+quest-lines = { $count } ライン消去
+quest-tetrises = テトリスを { $count } 回
+quest-tspin-doubles = Tスピンダブルを { $count } 回
+quest-pieces = ミノを { $count } 個設置
+quest-games = { $count } ゲームプレイ
+quest-combo = { $combo } コンボ達成
+quest-sprint = スプリントを { $time } 以内にクリア
+quest-ultra = ウルトラで { $score } 点獲得
+
+# ミノ順と fumen
+sequence-title = ミノ順
+sequence-pieces = ピース (IOTLJSZ)
+sequence-repeat = 繰り返す
+sequence-board = 初期盤面 (. は空き、X はおじゃま、またはピースの文字)
+sequence-start = 開始
+sequence-clear = 解除
+sequence-restart-hint = R キーでミノ順の最初からやり直します。
+sequence-unknown-piece = 不明なピース '{ $piece }'
+sequence-empty = ミノ順が空です
+board-too-tall = 盤面が { $rows } 段を超えています
+board-too-wide = { $row } 段目が { $cells } マスより広いです
+board-unknown-cell = { $row } 段目に不明なマス '{ $cell }' があります
+fumen = fumen
+fumen-import = 読み込み
+fumen-export = 書き出し
+fumen-not-v115 = v115 形式の fumen ではありません
+fumen-truncated = fumen のデータが途中で切れています
+fumen-invalid-character = fumen に使えない文字 '{ $character }' があります
+fumen-overflow = フィールドのデータが盤面からはみ出しています
+fumen-no-pages = fumen にページがありません
+fumen-invalid-piece = fumen のピースが不正です
+
+# 設定
+settings-language = 言語
+settings-language-system = システムの設定
+language-english = 英語
+language-japanese = 日本語
+settings-gravity = 落下速度
+settings-lock-delay = 固定猶予
+settings-das = DAS
+settings-arr = ARR
+settings-clear-delay = ライン消去の待ち時間
+settings-are = 出現待ち (ARE)
+settings-initial-actions = 先行回転・ホールド
+settings-soft-drop = ソフトドロップ速度
+settings-soft-drop-instant = 即時
+settings-kicks = 回転補正
+settings-effects = パーティクル効果
+settings-smooth-motion = ピースの動きを滑らかに
+settings-danger = 危険表示
+settings-danger-strength = 強さ
+settings-touch-gestures = タッチ操作
+settings-control-buttons = 画面上のボタン
+settings-mirror = 反転
+settings-mirror-pieces = ピース
+settings-mirror-board = 盤面
+settings-accessibility = アクセシビリティ
+settings-high-contrast = ハイコントラスト
+settings-large-board = 大きな盤面
+settings-reduce-flashing = 点滅を抑える
+settings-btype = Bタイプのおじゃま
+settings-btype-holes = 穴の割合
+settings-skin = ブロックのスキン
+settings-skin-flat = 単色
+settings-sound = 効果音
+settings-volume = 音量
+settings-sound-pack = サウンドパック
+settings-sound-pack-built-in = 内蔵
+settings-palette = 配色
+settings-patterns = ピースの模様
+settings-player-name = プレイヤー名
+settings-leaderboard-url = ランキングの URL
+settings-leaderboard-url-hint = https://... (空欄でオフライン)
+settings-discord = Discord ステータス
+settings-discord-app-id = アプリケーション ID
+settings-colors = 色
+settings-controls = 操作
+unit-rows = 段
+palette-standard = 標準
+palette-colorblind = 色覚多様性対応
+palette-pastel = パステル
+palette-neon = ネオン
+palette-retro = レトロ
+palette-locked = { $palette }（スター { $stars } 個）
+palette-still-locked = { $palette } にはクエストのスターが { $stars } 個必要なため、標準のパレットを使います
+colors-reset = 色を元に戻す
+randomizer-bag7 = 7種1巡
+randomizer-bag14 = 14種1巡
+randomizer-classic = クラシック
+randomizer-tgm = TGM 履歴方式
+randomizer-memoryless = 完全ランダム
+curve-fixed = 固定速度
+keybinds-press-key = キーを押してください...
+preset = 操作設定
+preset-custom = カスタム
+preset-guideline = ガイドライン
+preset-wasd = WASD
+preset-left-handed = 左利き
+preset-one-handed = 片手
+preset-vi = Vi キー
+action-move-left = 左移動
+action-move-right = 右移動
+action-soft-drop = ソフトドロップ
+action-hard-drop = ハードドロップ
+action-rotate-cw = 右回転
+action-rotate-ccw = 左回転
+action-rotate-180 = 180 度回転
+
+# オンラインロビー
+lobby-server = サーバー
+lobby-connect = 接続
+lobby-connecting = 接続中...
+lobby-disconnect = 切断
+lobby-disconnected = サーバーから切断されました
+lobby-lost = 接続が切れました: { $error }
+lobby-refresh = 更新
+lobby-quick-match = クイックマッチ
+lobby-no-rooms = まだ部屋がありません
+lobby-join = 参加
+lobby-room-name = 部屋の名前
+lobby-default-room = { $player } の部屋
+lobby-create = 部屋を作る
+lobby-mode = モード
+lobby-best-of = { $games } 本勝負
+lobby-leave = 退出
+lobby-host = (ホスト)
+lobby-ready = 準備完了
+lobby-not-ready = 準備中
+lobby-wins = { $wins } 勝
+lobby-in-game = 対戦中
+lobby-handicap = ハンデ
+lobby-handicap-garbage = 初期せり上がり
+lobby-handicap-gravity = 落下速度
+lobby-handicap-attack = 攻撃力
+lobby-handicap-preview = ネクスト数
+lobby-send = 送信
+lobby-game-won = { $player } がこのゲームに勝ちました
+lobby-game-drawn = 勝者なしでゲームが終わりました
+lobby-match-won = { $player } がマッチに勝ちました！
+lobby-opponent-left = 対戦相手が退出しました
+lobby-rejected = サーバーが結果を受け付けませんでした: { $reason }
+lobby-unknown-mode = { $mode } がインストールされていないため、このゲームはエンドレスになります
+lobby-bad-input = 対戦相手の入力を追えなくなりました: { $error }
+lobby-waiting = { $player } を待っています...
+lobby-opponent-incoming = 受信予定 { $lines } ライン
+lobby-desync = フレーム { $frame } で盤面の同期がずれました
+lobby-watch = 観戦
+lobby-watching = このルームを観戦中
+lobby-spectators = 観戦者: { $names }
+lobby-player-left = プレイヤーが退出しました
+
+# Discord ステータス
+presence-state = スコア { $score } · レベル { $level }
+presence-paused = { $state } (一時停止中)
+presence-menus = メニュー画面
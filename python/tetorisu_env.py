@@ -0,0 +1,58 @@
+"""Gym-style wrapper around `tetorisu --env`.
+
+    env = TetrisEnv(rewards={"lines": 1.0, "top_out": -1.0})
+    observation = env.reset(seed=42)
+    observation, reward, done = env.step(env.actions.index("hard_drop"))
+
+Observations are dicts of flat lists: `board` (row-major, 1 for filled),
+`piece` (kind, column, row, rotation), `queue`, `hold` and `hold_used`.
+Only the standard library is needed.
+"""
+
+import json
+import subprocess
+
+
+class TetrisEnv:
+    def __init__(self, binary="tetorisu", mode=None, rewards=None):
+        command = [binary, "--env"]
+        if mode is not None:
+            command += ["--mode", mode]
+        self.rewards = rewards or {}
+        self.process = subprocess.Popen(
+            command, stdin=subprocess.PIPE, stdout=subprocess.PIPE, text=True, bufsize=1
+        )
+        spec = self._request({"type": "spec"})
+        self.actions = spec["actions"]
+        self.board_shape = tuple(spec["board"])
+        self.queue_length = spec["queue"]
+        self.piece_kinds = spec["pieces"]
+
+    def _request(self, request):
+        self.process.stdin.write(json.dumps(request) + "\n")
+        self.process.stdin.flush()
+        line = self.process.stdout.readline()
+        if not line:
+            raise RuntimeError("tetorisu --env exited")
+        response = json.loads(line)
+        if "error" in response:
+            raise ValueError(response["error"])
+        return response
+
+    def reset(self, seed=0):
+        return self._request({"type": "reset", "seed": seed, "rewards": self.rewards})
+
+    def step(self, action):
+        response = self._request({"type": "step", "action": int(action)})
+        return response["observation"], response["reward"], response["done"]
+
+    def close(self):
+        if self.process.poll() is None:
+            self.process.stdin.close()
+            self.process.wait()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *_):
+        self.close()
@@ -0,0 +1,154 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::history;
+use crate::i18n::{tr, tr_args};
+
+const TOAST_SECONDS: f64 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Achievement {
+    FirstTetris,
+    TSpinDouble,
+    TSpinTriple,
+    PerfectClear,
+    Combo10,
+    BackToBack5,
+    Score100k,
+    Lines150,
+    SprintUnder60,
+    UltraScore50k,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 10] = [
+        Achievement::FirstTetris,
+        Achievement::TSpinDouble,
+        Achievement::TSpinTriple,
+        Achievement::PerfectClear,
+        Achievement::Combo10,
+        Achievement::BackToBack5,
+        Achievement::Score100k,
+        Achievement::Lines150,
+        Achievement::SprintUnder60,
+        Achievement::UltraScore50k,
+    ];
+
+    // Message ids are "achievement-<id>" for the name and
+    // "achievement-<id>-description" for what it takes.
+    fn id(self) -> &'static str {
+        match self {
+            Achievement::FirstTetris => "first-tetris",
+            Achievement::TSpinDouble => "tspin-double",
+            Achievement::TSpinTriple => "tspin-triple",
+            Achievement::PerfectClear => "perfect-clear",
+            Achievement::Combo10 => "combo-10",
+            Achievement::BackToBack5 => "back-to-back-5",
+            Achievement::Score100k => "score-100k",
+            Achievement::Lines150 => "lines-150",
+            Achievement::SprintUnder60 => "sprint-under-60",
+            Achievement::UltraScore50k => "ultra-score-50k",
+        }
+    }
+
+    pub fn name(self) -> String {
+        tr(&format!("achievement-{}", self.id()))
+    }
+
+    pub fn description(self) -> String {
+        tr(&format!("achievement-{}-description", self.id()))
+    }
+}
+
+// Unlocks are kept with the date they happened and saved as soon as they
+// change. Fresh ones queue a toast.
+#[derive(Default)]
+pub struct Achievements {
+    unlocked: BTreeMap<Achievement, String>,
+    toasts: Vec<(Achievement, f64)>,
+}
+
+fn path() -> PathBuf {
+    history::data_dir().join("achievements.json")
+}
+
+impl Achievements {
+    // A missing or unreadable file just means nothing is unlocked yet.
+    pub fn load() -> Self {
+        let unlocked = fs::read_to_string(path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            unlocked,
+            toasts: Vec::new(),
+        }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&self.unlocked)?)
+    }
+
+    pub fn unlock(&mut self, achievement: Achievement, now: f64) {
+        if self.unlocked.contains_key(&achievement) {
+            return;
+        }
+        self.unlocked.insert(achievement, history::timestamp());
+        self.toasts.push((achievement, now));
+        if let Err(error) = self.save() {
+            eprintln!("Could not save achievements: {}", error);
+        }
+    }
+
+    // Time until the oldest toast goes away.
+    pub fn toast_remaining(&self, now: f64) -> Option<f64> {
+        self.toasts.first().map(|&(_, at)| (TOAST_SECONDS - (now - at)).max(0.0))
+    }
+
+    pub fn paint_toasts(&mut self, ctx: &egui::Context, now: f64) {
+        self.toasts.retain(|&(_, at)| now - at < TOAST_SECONDS);
+        if self.toasts.is_empty() {
+            return;
+        }
+        egui::Area::new("achievement toasts")
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                for &(achievement, _) in &self.toasts {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(egui::RichText::new(tr("achievement-unlocked")).small());
+                        ui.strong(achievement.name());
+                        ui.label(achievement.description());
+                    });
+                }
+            });
+    }
+
+    pub fn gallery(&self, ui: &mut egui::Ui) {
+        ui.label(tr_args(
+            "achievements-progress",
+            &[("unlocked", &self.unlocked.len()), ("total", &Achievement::ALL.len())],
+        ));
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("achievements").num_columns(3).striped(true).show(ui, |ui| {
+                for achievement in Achievement::ALL {
+                    let date = self.unlocked.get(&achievement);
+                    let color = if date.is_some() { ui.visuals().strong_text_color() } else { egui::Color32::GRAY };
+                    ui.colored_label(color, achievement.name());
+                    ui.colored_label(color, achievement.description());
+                    ui.label(date.cloned().unwrap_or_else(|| tr("achievement-locked")));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,169 @@
+use crate::board::FULL_ROW;
+use crate::finesse::{self, Step};
+use crate::i18n::tr_args;
+use crate::keybinds::{Action, Keybinds};
+use crate::rotation::Turn;
+use crate::{PieceKind, TetrisGame, Tetromino, BOARD_WIDTH};
+
+// Weights of a well-known linear evaluation of the stack a placement leaves:
+// total column height, lines cleared, covered holes and how uneven the
+// surface is.
+const HEIGHT: f32 = -0.510066;
+const LINES: f32 = 0.760666;
+const HOLES: f32 = -0.35663;
+const BUMPINESS: f32 = -0.184483;
+
+#[derive(Clone)]
+pub struct Hint {
+    // Where the piece lands.
+    pub piece: Tetromino,
+    // The fewest inputs from spawn that get it there, before the hard drop.
+    pub steps: Vec<Step>,
+}
+
+// What a hint was worked out for: the piece in play, counted by pieces
+// placed so hold swaps and new pieces both show up, and the stack it lands
+// on, which garbage can raise mid-piece.
+#[derive(PartialEq)]
+struct Key {
+    pieces: u32,
+    kind: PieceKind,
+    hold: Option<PieceKind>,
+    hold_used: bool,
+    board: Vec<u16>,
+}
+
+// The search behind a hint is too slow to run every frame, so the last one
+// is kept until the piece, hold or stack changes.
+#[derive(Default)]
+pub struct Assist {
+    cached: Option<(Key, Option<Hint>)>,
+}
+
+impl Assist {
+    pub fn hint(&mut self, game: &TetrisGame) -> Option<Hint> {
+        let key = Key {
+            pieces: game.stats.pieces,
+            kind: game.current_piece.kind,
+            hold: game.hold,
+            hold_used: game.hold_used,
+            board: game.board.bits().to_vec(),
+        };
+        match &self.cached {
+            Some((cached, hint)) if *cached == key => hint.clone(),
+            _ => {
+                let hint = suggest(game);
+                self.cached = Some((key, hint.clone()));
+                hint
+            }
+        }
+    }
+
+    // A new game can have other kicks, and so other reachable spots.
+    pub fn clear(&mut self) {
+        self.cached = None;
+    }
+}
+
+// The best landing spot for the current piece, looking only at the board as
+// it is now. Only spots the finesse search reaches are considered, so the
+// suggested inputs are also the optimal ones.
+pub fn suggest(game: &TetrisGame) -> Option<Hint> {
+    finesse::placements(game, game.current_piece.kind)
+        .into_iter()
+        .map(|(piece, steps)| (evaluate(game, &piece), Hint { piece, steps }))
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, hint)| hint)
+}
+
+fn evaluate(game: &TetrisGame, piece: &Tetromino) -> f32 {
+    let mut rows = game.board.bits().to_vec();
+    for (x, y) in piece.cells() {
+        rows[y as usize] |= 1 << x;
+    }
+    let before = rows.len();
+    rows.retain(|&row| row != FULL_ROW);
+    let lines = before - rows.len();
+
+    let mut heights = [0; BOARD_WIDTH];
+    let mut holes = 0;
+    for (x, column) in heights.iter_mut().enumerate() {
+        let Some(top) = rows.iter().position(|&row| row & 1 << x != 0) else {
+            continue;
+        };
+        *column = rows.len() - top;
+        holes += rows[top..].iter().filter(|&&row| row & 1 << x == 0).count();
+    }
+    let total: usize = heights.iter().sum();
+    let bumpiness: usize = heights.windows(2).map(|pair| pair[0].abs_diff(pair[1])).sum();
+    HEIGHT * total as f32 + LINES * lines as f32 + HOLES * holes as f32 + BUMPINESS * bumpiness as f32
+}
+
+// The steps as the player's own keys, ending with the hard drop.
+pub fn keys(steps: &[Step], keybinds: &Keybinds) -> String {
+    let key = |action| format!("{:?}", keybinds.key(action));
+    let direction = |dx: i32| if dx < 0 { Action::MoveLeft } else { Action::MoveRight };
+    steps
+        .iter()
+        .map(|&step| match step {
+            Step::Tap(dx) => key(direction(dx)),
+            Step::Das(dx) => tr_args("assist-hold-key", &[("key", &key(direction(dx)))]),
+            Step::Rotate(Turn::Cw) => key(Action::RotateCw),
+            Step::Rotate(Turn::Ccw) => key(Action::RotateCcw),
+            Step::Rotate(Turn::Half) => key(Action::Rotate180),
+        })
+        .chain(std::iter::once(key(Action::HardDrop)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::randomizer::Randomizer;
+    use crate::{BlockType, BOARD_HEIGHT, TOTAL_HEIGHT};
+
+    // A bottom row with a well in the last column, and an I piece to play.
+    fn well() -> TetrisGame {
+        let mut visible = vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        visible[BOARD_HEIGHT - 1] = (0..BOARD_WIDTH)
+            .map(|x| if x == BOARD_WIDTH - 1 { BlockType::Empty } else { BlockType::Garbage })
+            .collect();
+        let mut game = TetrisGame::new(None, visible, Some(1), Randomizer::default());
+        game.current_piece = Tetromino::new(PieceKind::I);
+        game
+    }
+
+    #[test]
+    fn fills_the_well() {
+        let hint = suggest(&well()).unwrap();
+        assert!(hint.piece.cells().iter().all(|&(x, _)| x == BOARD_WIDTH as i32 - 1));
+        assert!(hint.steps.contains(&Step::Das(1)));
+    }
+
+    #[test]
+    fn clearing_beats_covering() {
+        let game = well();
+        let mut standing = Tetromino::new(PieceKind::I);
+        standing.rotate(Turn::Cw);
+        standing.x = BOARD_WIDTH as i32 - 1 - 2;
+        let flat = Tetromino::new(PieceKind::I);
+        let (standing, flat) = (game.drop_position(&standing), game.drop_position(&flat));
+        assert!(standing.cells().iter().all(|&(x, _)| x == BOARD_WIDTH as i32 - 1));
+        assert!(evaluate(&game, &standing) > evaluate(&game, &flat));
+    }
+
+    #[test]
+    fn hint_is_kept_until_the_stack_changes() {
+        let mut game = well();
+        let mut assist = Assist::default();
+        let first = assist.hint(&game).unwrap();
+        let again = assist.hint(&game).unwrap();
+        assert_eq!(first.piece.cells(), again.piece.cells());
+        // The stack changing under the same piece, as rising garbage does.
+        game.board.set(0, TOTAL_HEIGHT - 2, BlockType::Garbage);
+        assist.hint(&game);
+        let (key, _) = assist.cached.as_ref().unwrap();
+        assert_eq!(key.board, game.board.bits());
+    }
+}
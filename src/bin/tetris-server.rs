@@ -0,0 +1,671 @@
+use clap::Parser;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tetorisu::net::{
+    self, ClientMessage, Handicap, MatchResult, Member, RoomInfo, RoomSettings, RoomState, Seat, ServerMessage,
+    DEFAULT_PORT, MAX_CHAT_CHARS, MAX_GARBAGE_LINES, MAX_NAME_CHARS,
+};
+
+const ROOM_SIZE: usize = 2;
+// Results claiming more pieces per second than this are rejected.
+const MAX_PPS: f64 = 20.0;
+
+#[derive(Parser)]
+#[command(about = "Hosts rooms for online Tetris matches")]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "0.0.0.0")]
+    bind: String,
+    /// Port to listen on
+    #[arg(long, default_value_t = DEFAULT_PORT)]
+    port: u16,
+}
+
+struct Client {
+    name: String,
+    sender: Sender<ServerMessage>,
+    room: Option<u32>,
+    // A room watched without a seat.
+    watching: Option<u32>,
+    handicap: Handicap,
+    ready: bool,
+}
+
+#[derive(Default)]
+struct Room {
+    name: String,
+    settings: RoomSettings,
+    // The first player is the host.
+    players: Vec<u32>,
+    started: bool,
+    // Games each player has won this match.
+    wins: HashMap<u32, u32>,
+    // Garbage relayed to each player this match, for checking results.
+    garbage: HashMap<u32, u32>,
+    results: HashMap<u32, MatchResult>,
+    spectators: Vec<u32>,
+    // The game's Start and every input since, for spectators who come in
+    // partway through.
+    log: Vec<ServerMessage>,
+}
+
+impl Room {
+    fn reset(&mut self) {
+        self.started = false;
+        self.garbage.clear();
+        self.results.clear();
+        self.log.clear();
+    }
+
+    fn everyone(&self) -> impl Iterator<Item = u32> + '_ {
+        self.players.iter().chain(&self.spectators).copied()
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    next_id: u32,
+    clients: HashMap<u32, Client>,
+    rooms: BTreeMap<u32, Room>,
+}
+
+impl Server {
+    fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn send(&self, id: u32, message: ServerMessage) {
+        if let Some(client) = self.clients.get(&id) {
+            let _ = client.sender.send(message);
+        }
+    }
+
+    fn name(&self, id: u32) -> String {
+        self.clients.get(&id).map_or_else(String::new, |client| client.name.clone())
+    }
+
+    fn room_of(&self, id: u32) -> Option<u32> {
+        self.clients.get(&id).and_then(|client| client.room)
+    }
+
+    fn opponent(&self, id: u32) -> Option<u32> {
+        let room = self.rooms.get(&self.room_of(id)?)?;
+        room.players.iter().copied().find(|&player| player != id)
+    }
+
+    fn room_info(&self, id: u32, room: &Room) -> RoomInfo {
+        RoomInfo {
+            id,
+            name: room.name.clone(),
+            players: room.players.iter().map(|&player| self.name(player)).collect(),
+            started: room.started,
+            settings: room.settings.clone(),
+        }
+    }
+
+    // Sends everyone in the room where it stands now.
+    fn broadcast_room(&self, room_id: u32) {
+        let Some(room) = self.rooms.get(&room_id) else {
+            return;
+        };
+        let members = room
+            .players
+            .iter()
+            .map(|&player| Member {
+                id: player,
+                name: self.name(player),
+                ready: self.clients.get(&player).is_some_and(|client| client.ready),
+                wins: room.wins.get(&player).copied().unwrap_or(0),
+                handicap: self.handicap(player),
+            })
+            .collect();
+        let state = RoomState {
+            id: room_id,
+            name: room.name.clone(),
+            settings: room.settings.clone(),
+            members,
+            started: room.started,
+            spectators: room.spectators.iter().map(|&spectator| self.name(spectator)).collect(),
+        };
+        for id in room.everyone() {
+            self.send(id, ServerMessage::Room(state.clone()));
+        }
+    }
+
+    fn unready(&mut self, room_id: u32) {
+        let players = self.rooms.get(&room_id).map_or_else(Vec::new, |room| room.players.clone());
+        for player in players {
+            if let Some(client) = self.clients.get_mut(&player) {
+                client.ready = false;
+            }
+        }
+    }
+
+    fn handle(&mut self, id: u32, message: ClientMessage) {
+        match message {
+            ClientMessage::Hello { name } => {
+                let name: String = name.trim().chars().take(MAX_NAME_CHARS).collect();
+                if let Some(client) = self.clients.get_mut(&id).filter(|_| !name.is_empty()) {
+                    client.name = name;
+                }
+            }
+            ClientMessage::ListRooms => {
+                let rooms = self.rooms.iter().map(|(&room, info)| self.room_info(room, info)).collect();
+                self.send(id, ServerMessage::Rooms { rooms });
+            }
+            ClientMessage::CreateRoom { name, settings } => {
+                if let Err(message) = settings.validate() {
+                    self.send(id, ServerMessage::Error { message });
+                    return;
+                }
+                let room = self.create_room(name, settings);
+                self.join(id, room);
+            }
+            ClientMessage::JoinRoom { room } => self.join(id, room),
+            ClientMessage::Spectate { room } => self.spectate(id, room),
+            ClientMessage::QuickMatch => {
+                let waiting = self
+                    .rooms
+                    .iter()
+                    .find(|(_, room)| !room.started && room.players.len() < ROOM_SIZE)
+                    .map(|(&room, _)| room);
+                let room = match waiting {
+                    Some(room) => room,
+                    None => {
+                        let name = format!("{}'s room", self.name(id));
+                        self.create_room(name, RoomSettings::default())
+                    }
+                };
+                self.join(id, room);
+            }
+            ClientMessage::Leave => self.leave(id),
+            ClientMessage::ChangeSettings(settings) => self.change_settings(id, settings),
+            ClientMessage::SetHandicap(handicap) => self.set_handicap(id, handicap),
+            ClientMessage::Ready { ready } => self.set_ready(id, ready),
+            ClientMessage::Chat { text } => self.chat(id, text),
+            ClientMessage::Input { frame, actions } => {
+                if let (Some(opponent), true) = (self.opponent(id), self.in_match(id)) {
+                    let player = self.name(id);
+                    self.send(opponent, ServerMessage::Input { player, frame, actions: actions.clone() });
+                    self.show_spectators(id, frame, actions);
+                }
+            }
+            ClientMessage::StateHash { frame, hash } => {
+                if let (Some(opponent), true) = (self.opponent(id), self.in_match(id)) {
+                    let player = self.name(id);
+                    self.send(opponent, ServerMessage::StateHash { player, frame, hash });
+                }
+            }
+            ClientMessage::Garbage { lines } => {
+                if let (Some(opponent), true) = (self.opponent(id), self.in_match(id)) {
+                    let lines = lines.min(MAX_GARBAGE_LINES);
+                    if let Some(room) = self.room_of(id).and_then(|room| self.rooms.get_mut(&room)) {
+                        let received = room.garbage.entry(opponent).or_default();
+                        *received = received.saturating_add(lines);
+                    }
+                    self.send(opponent, ServerMessage::Garbage { lines });
+                }
+            }
+            ClientMessage::Result(result) => self.finish(id, result),
+        }
+    }
+
+    // Passes a player's inputs on to anyone watching, and keeps them for
+    // whoever starts watching later.
+    fn show_spectators(&mut self, id: u32, frame: u32, actions: Vec<String>) {
+        let Some(room) = self.room_of(id).and_then(|room| self.rooms.get_mut(&room)) else {
+            return;
+        };
+        let Some(seat) = room.players.iter().position(|&player| player == id) else {
+            return;
+        };
+        let message = ServerMessage::SeatInput { seat, frame, actions };
+        room.log.push(message.clone());
+        let spectators = room.spectators.clone();
+        for spectator in spectators {
+            self.send(spectator, message.clone());
+        }
+    }
+
+    fn in_match(&self, id: u32) -> bool {
+        self.room_of(id)
+            .and_then(|room| self.rooms.get(&room))
+            .is_some_and(|room| room.started)
+    }
+
+    fn set_handicap(&mut self, id: u32, handicap: Handicap) {
+        if self.in_match(id) {
+            let message = "Handicaps can't change during a match".to_string();
+            self.send(id, ServerMessage::Error { message });
+            return;
+        }
+        if let Err(message) = handicap.validate() {
+            self.send(id, ServerMessage::Error { message });
+            return;
+        }
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.handicap = handicap;
+        }
+        if let Some(room) = self.room_of(id) {
+            self.unready(room);
+            self.broadcast_room(room);
+        }
+    }
+
+    fn change_settings(&mut self, id: u32, settings: RoomSettings) {
+        let Some(room_id) = self.room_of(id) else {
+            return;
+        };
+        let room = &self.rooms[&room_id];
+        let allowed = if room.players.first() != Some(&id) {
+            Err("Only the host can change the room's settings".to_string())
+        } else if room.started {
+            Err("Settings can't change during a game".to_string())
+        } else {
+            settings.validate()
+        };
+        if let Err(message) = allowed {
+            self.send(id, ServerMessage::Error { message });
+            return;
+        }
+        let room = self.rooms.get_mut(&room_id).unwrap();
+        room.settings = settings;
+        // A different match, so the tally starts over.
+        room.wins.clear();
+        self.unready(room_id);
+        self.broadcast_room(room_id);
+    }
+
+    fn set_ready(&mut self, id: u32, ready: bool) {
+        let Some(room_id) = self.room_of(id).filter(|_| !self.in_match(id)) else {
+            return;
+        };
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.ready = ready;
+        }
+        let room = &self.rooms[&room_id];
+        let all_ready = room.players.len() == ROOM_SIZE
+            && room.players.iter().all(|player| self.clients.get(player).is_some_and(|client| client.ready));
+        if all_ready {
+            self.start(room_id);
+        }
+        self.broadcast_room(room_id);
+    }
+
+    fn chat(&mut self, id: u32, text: String) {
+        let watching = self.clients.get(&id).and_then(|client| client.watching);
+        let Some(room) = self.room_of(id).or(watching).and_then(|room| self.rooms.get(&room)) else {
+            let message = "Join a room to chat".to_string();
+            self.send(id, ServerMessage::Error { message });
+            return;
+        };
+        let text: String = text.trim().chars().take(MAX_CHAT_CHARS).collect();
+        if text.is_empty() {
+            return;
+        }
+        let player = self.name(id);
+        for other in room.everyone() {
+            self.send(other, ServerMessage::Chat { player: player.clone(), text: text.clone() });
+        }
+    }
+
+    fn handicap(&self, id: u32) -> Handicap {
+        self.clients.get(&id).map_or_else(Handicap::default, |client| client.handicap)
+    }
+
+    fn create_room(&mut self, name: String, settings: RoomSettings) -> u32 {
+        let room = self.next_id();
+        self.rooms.insert(room, Room { name, settings, ..Room::default() });
+        room
+    }
+
+    // Joining only takes a seat; the game waits for everyone to ready up.
+    fn join(&mut self, id: u32, room_id: u32) {
+        self.leave(id);
+        let Some(room) = self.rooms.get_mut(&room_id) else {
+            self.send(id, ServerMessage::Error { message: format!("No room {}", room_id) });
+            return;
+        };
+        if room.started || room.players.len() >= ROOM_SIZE {
+            self.send(id, ServerMessage::Error { message: format!("Room {} is full", room_id) });
+            return;
+        }
+        room.players.push(id);
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = Some(room_id);
+            client.ready = false;
+        }
+        self.broadcast_room(room_id);
+    }
+
+    // Watching takes no seat, so it works on full rooms and games already
+    // going, which are caught up on at once.
+    fn spectate(&mut self, id: u32, room_id: u32) {
+        self.leave(id);
+        let Some(room) = self.rooms.get_mut(&room_id) else {
+            self.send(id, ServerMessage::Error { message: format!("No room {}", room_id) });
+            return;
+        };
+        room.spectators.push(id);
+        let log = room.log.clone();
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.watching = Some(room_id);
+        }
+        self.broadcast_room(room_id);
+        for message in log {
+            self.send(id, message);
+        }
+    }
+
+    fn start(&mut self, room_id: u32) {
+        self.unready(room_id);
+        let room = self.rooms.get_mut(&room_id).unwrap();
+        room.started = true;
+        let players = room.players.clone();
+        let seats: Vec<Seat> = players
+            .iter()
+            .map(|&player| Seat {
+                name: self.name(player),
+                handicap: self.handicap(player),
+            })
+            .collect();
+        let seed = rand::thread_rng().gen();
+        let message = ServerMessage::Start { seed, seats };
+        let room = self.rooms.get_mut(&room_id).unwrap();
+        room.log = vec![message.clone()];
+        let everyone: Vec<u32> = room.everyone().collect();
+        for id in everyone {
+            self.send(id, message.clone());
+        }
+    }
+
+    fn leave(&mut self, id: u32) {
+        if let Some(room_id) = self.clients.get_mut(&id).and_then(|client| client.watching.take()) {
+            if let Some(room) = self.rooms.get_mut(&room_id) {
+                room.spectators.retain(|&spectator| spectator != id);
+                if room.players.is_empty() && room.spectators.is_empty() {
+                    self.rooms.remove(&room_id);
+                } else {
+                    self.broadcast_room(room_id);
+                }
+            }
+        }
+        let Some(room_id) = self.clients.get_mut(&id).and_then(|client| client.room.take()) else {
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&room_id) else {
+            return;
+        };
+        room.players.retain(|&player| player != id);
+        // A room stays open while anyone is still watching it.
+        if room.players.is_empty() && room.spectators.is_empty() {
+            self.rooms.remove(&room_id);
+            return;
+        }
+        let started = room.started;
+        room.reset();
+        room.wins.clear();
+        let everyone: Vec<u32> = room.everyone().collect();
+        if started {
+            for other in everyone {
+                self.send(other, ServerMessage::OpponentLeft);
+            }
+        }
+        self.unready(room_id);
+        self.broadcast_room(room_id);
+    }
+
+    // A top out ends the match at once; otherwise it's decided on score once
+    // both players have reported.
+    fn finish(&mut self, id: u32, result: MatchResult) {
+        let Some(room_id) = self.room_of(id).filter(|_| self.in_match(id)) else {
+            return;
+        };
+        let opponent = self.opponent(id);
+        let garbage_rows = self.handicap(id).garbage_rows;
+        let room = self.rooms.get_mut(&room_id).unwrap();
+        if let Err(reason) = validate(&result, room.garbage.get(&id).copied().unwrap_or(0) + garbage_rows) {
+            self.send(id, ServerMessage::Rejected { reason });
+            return;
+        }
+        let topped_out = result.topped_out;
+        room.results.insert(id, result);
+
+        let winner = if topped_out {
+            opponent
+        } else if room.results.len() == room.players.len() {
+            room.results
+                .iter()
+                .filter(|(_, result)| !result.topped_out)
+                .max_by_key(|(_, result)| result.score)
+                .map(|(&player, _)| player)
+        } else {
+            return;
+        };
+        let everyone: Vec<u32> = room.everyone().collect();
+        room.reset();
+        let champion = winner.filter(|&player| {
+            let wins = room.wins.entry(player).or_default();
+            *wins += 1;
+            *wins >= room.settings.wins_needed()
+        });
+        if champion.is_some() {
+            room.wins.clear();
+        }
+        let winner = winner.map(|player| self.name(player));
+        for &player in &everyone {
+            self.send(player, ServerMessage::Finished { winner: winner.clone() });
+        }
+        if let Some(champion) = champion {
+            let winner = self.name(champion);
+            for &player in &everyone {
+                self.send(player, ServerMessage::MatchOver { winner: winner.clone() });
+            }
+        }
+        self.broadcast_room(room_id);
+    }
+}
+
+// Every piece adds four cells and every garbage line nine, counting the rows
+// a handicap starts the board with, and each cleared line needs ten, so that
+// bounds the lines a result can claim.
+fn validate(result: &MatchResult, garbage: u32) -> Result<(), String> {
+    let cells = result.pieces as u64 * 4 + garbage as u64 * 9;
+    if result.lines as u64 * 10 > cells {
+        return Err(format!("{} lines can't come from {} pieces", result.lines, result.pieces));
+    }
+    let seconds = result.time_ms as f64 / 1000.0;
+    if result.pieces as f64 > seconds * MAX_PPS + 1.0 {
+        return Err(format!("{} pieces in {:.1}s is too fast", result.pieces, seconds));
+    }
+    Ok(())
+}
+
+fn serve(server: Arc<Mutex<Server>>, stream: TcpStream) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let (sender, receiver) = mpsc::channel();
+    let id = {
+        let mut server = server.lock().unwrap();
+        let id = server.next_id();
+        let client = Client {
+            name: format!("Player {}", id),
+            sender: sender.clone(),
+            room: None,
+            watching: None,
+            handicap: Handicap::default(),
+            ready: false,
+        };
+        server.clients.insert(id, client);
+        id
+    };
+    // Writes go through a channel so a slow client never holds the lock.
+    thread::spawn(move || {
+        for message in receiver {
+            if net::send(&mut writer, &message).is_err() {
+                break;
+            }
+        }
+    });
+    let _ = sender.send(ServerMessage::Welcome { id });
+
+    let mut reader = BufReader::new(stream);
+    loop {
+        match net::receive::<ClientMessage>(&mut reader) {
+            Ok(Some(message)) => server.lock().unwrap().handle(id, message),
+            Ok(None) => break,
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                let _ = sender.send(ServerMessage::Error { message: error.to_string() });
+            }
+            Err(_) => break,
+        }
+    }
+    let mut server = server.lock().unwrap();
+    server.leave(id);
+    server.clients.remove(&id);
+}
+
+fn main() {
+    let args = Args::parse();
+    let listener = match TcpListener::bind((args.bind.as_str(), args.port)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("Could not listen on {}:{}: {}", args.bind, args.port, error);
+            std::process::exit(1);
+        }
+    };
+    println!("Listening on {}:{}", args.bind, args.port);
+
+    let server = Arc::new(Mutex::new(Server::default()));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = server.clone();
+                thread::spawn(move || serve(server, stream));
+            }
+            Err(error) => eprintln!("Connection failed: {}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+
+    fn connect(server: &mut Server) -> (u32, Receiver<ServerMessage>) {
+        let (sender, receiver) = mpsc::channel();
+        let id = server.next_id();
+        let client = Client {
+            name: format!("Player {}", id),
+            sender,
+            room: None,
+            watching: None,
+            handicap: Handicap::default(),
+            ready: false,
+        };
+        server.clients.insert(id, client);
+        (id, receiver)
+    }
+
+    fn inputs(receiver: &Receiver<ServerMessage>) -> Vec<(usize, u32)> {
+        receiver
+            .try_iter()
+            .filter_map(|message| match message {
+                ServerMessage::SeatInput { seat, frame, .. } => Some((seat, frame)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spectators_catch_up_and_cannot_play() {
+        let mut server = Server::default();
+        let (host, host_messages) = connect(&mut server);
+        let (guest, guest_messages) = connect(&mut server);
+        server.handle(host, ClientMessage::CreateRoom { name: "Room".to_string(), settings: RoomSettings::default() });
+        let room = server.room_of(host).unwrap();
+        server.handle(guest, ClientMessage::JoinRoom { room });
+        server.handle(host, ClientMessage::Ready { ready: true });
+        server.handle(guest, ClientMessage::Ready { ready: true });
+        assert!(server.in_match(host));
+        server.handle(host, ClientMessage::Input { frame: 2, actions: Vec::new() });
+
+        // Joining late replays the start and the inputs so far.
+        let (spectator, spectator_messages) = connect(&mut server);
+        server.handle(spectator, ClientMessage::Spectate { room });
+        let caught_up: Vec<ServerMessage> = spectator_messages.try_iter().collect();
+        assert!(caught_up.iter().any(|message| matches!(message, ServerMessage::Start { .. })));
+        assert!(caught_up.iter().any(|message| matches!(message, ServerMessage::SeatInput { seat: 0, frame: 2, .. })));
+
+        server.handle(guest, ClientMessage::Input { frame: 2, actions: Vec::new() });
+        assert_eq!(inputs(&spectator_messages), [(1, 2)]);
+
+        // A spectator's inputs go nowhere, and the room stays full.
+        host_messages.try_iter().count();
+        server.handle(spectator, ClientMessage::Input { frame: 3, actions: Vec::new() });
+        assert!(!host_messages.try_iter().any(|message| matches!(message, ServerMessage::Input { .. })));
+        assert!(guest_messages.try_iter().all(|message| !matches!(message, ServerMessage::Input { frame: 3, .. })));
+        assert_eq!(server.rooms[&room].players, [host, guest]);
+
+        server.handle(spectator, ClientMessage::Leave);
+        assert!(server.rooms[&room].spectators.is_empty());
+    }
+
+    fn start_match(server: &mut Server, handicap: Handicap) -> (u32, Receiver<ServerMessage>) {
+        let (host, host_messages) = connect(server);
+        let (guest, _) = connect(server);
+        server.handle(host, ClientMessage::SetHandicap(handicap));
+        server.handle(host, ClientMessage::CreateRoom { name: "Room".to_string(), settings: RoomSettings::default() });
+        let room = server.room_of(host).unwrap();
+        server.handle(guest, ClientMessage::JoinRoom { room });
+        server.handle(host, ClientMessage::Ready { ready: true });
+        server.handle(guest, ClientMessage::Ready { ready: true });
+        assert!(server.in_match(host));
+        (host, host_messages)
+    }
+
+    fn rejected(server: &mut Server, id: u32, messages: &Receiver<ServerMessage>) -> bool {
+        messages.try_iter().count();
+        let result = MatchResult { score: 0, lines: 2, pieces: 2, time_ms: 10_000, topped_out: false };
+        server.handle(id, ClientMessage::Result(result));
+        messages.try_iter().any(|message| matches!(message, ServerMessage::Rejected { .. }))
+    }
+
+    #[test]
+    fn starting_garbage_counts_towards_lines() {
+        let mut server = Server::default();
+        let (player, messages) = start_match(&mut server, Handicap::default());
+        assert!(rejected(&mut server, player, &messages));
+
+        let mut server = Server::default();
+        let handicap = Handicap { garbage_rows: 2, ..Handicap::default() };
+        let (player, messages) = start_match(&mut server, handicap);
+        assert!(!rejected(&mut server, player, &messages));
+    }
+
+    #[test]
+    fn clients_cannot_overflow_names_or_garbage() {
+        let mut server = Server::default();
+        let (host, _) = start_match(&mut server, Handicap::default());
+        let guest = server.opponent(host).unwrap();
+        server.handle(host, ClientMessage::Hello { name: "x".repeat(1000) });
+        assert_eq!(server.name(host).chars().count(), MAX_NAME_CHARS);
+        server.handle(guest, ClientMessage::Hello { name: "  ".to_string() });
+        assert_eq!(server.name(guest), format!("Player {}", guest));
+
+        for _ in 0..2 {
+            server.handle(host, ClientMessage::Garbage { lines: u32::MAX });
+        }
+        let room = server.room_of(host).unwrap();
+        assert_eq!(server.rooms[&room].garbage[&guest], 2 * MAX_GARBAGE_LINES);
+    }
+}
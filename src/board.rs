@@ -0,0 +1,225 @@
+use std::ops::Deref;
+
+use crate::{BlockType, BOARD_WIDTH, HIDDEN_ROWS, TOTAL_HEIGHT};
+
+pub const FULL_ROW: u16 = (1 << BOARD_WIDTH) - 1;
+
+// The playfield as one bit per cell, a u16 per row with bit x for column x,
+// and the block types kept alongside for drawing. Collision and line checks
+// only touch the bits. Reads go through Deref to the rows of blocks; writes
+// go through the methods so both layers stay in step.
+#[derive(Clone)]
+pub(crate) struct Board {
+    bits: Vec<u16>,
+    cells: Vec<Vec<BlockType>>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(vec![vec![BlockType::Empty; BOARD_WIDTH]; TOTAL_HEIGHT - HIDDEN_ROWS])
+    }
+}
+
+impl Deref for Board {
+    type Target = [Vec<BlockType>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.cells
+    }
+}
+
+fn row_bits(row: &[BlockType]) -> u16 {
+    row.iter()
+        .enumerate()
+        .filter(|(_, &block)| block != BlockType::Empty)
+        .fold(0, |bits, (x, _)| bits | 1 << x)
+}
+
+impl Board {
+    // Takes the visible rows only; the hidden rows are added on top.
+    pub fn new(visible: Vec<Vec<BlockType>>) -> Self {
+        let mut cells = vec![vec![BlockType::Empty; BOARD_WIDTH]; HIDDEN_ROWS];
+        cells.extend(visible);
+        let bits = cells.iter().map(|row| row_bits(row)).collect();
+        Self { bits, cells }
+    }
+
+    // Walls and floor count as filled; the space above the board is open.
+    pub fn is_filled(&self, x: i32, y: i32) -> bool {
+        if x < 0 || x >= BOARD_WIDTH as i32 || y >= TOTAL_HEIGHT as i32 {
+            return true;
+        }
+        y >= 0 && self.bits[y as usize] & 1 << x != 0
+    }
+
+    // Out of range cells are ignored.
+    pub fn set(&mut self, x: usize, y: usize, block: BlockType) {
+        if let Some(cell) = self.cells.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *cell = block;
+            if block == BlockType::Empty {
+                self.bits[y] &= !(1 << x);
+            } else {
+                self.bits[y] |= 1 << x;
+            }
+        }
+    }
+
+    // Whether a shape, given as one bitmask per row with its top-left corner
+    // at (x, y), sits clear of the walls, floor and stack.
+    pub fn fits(&self, masks: &[u16], x: i32, y: i32) -> bool {
+        for (i, &mask) in masks.iter().enumerate() {
+            if mask == 0 {
+                continue;
+            }
+            let row = y + i as i32;
+            if row >= TOTAL_HEIGHT as i32 {
+                return false;
+            }
+            let shifted = if x >= 0 {
+                (mask as u32) << x
+            } else if mask & ((1 << -x) - 1) != 0 {
+                return false;
+            } else {
+                (mask >> -x) as u32
+            };
+            if shifted > FULL_ROW as u32 || (row >= 0 && self.bits[row as usize] as u32 & shifted != 0) {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn bits(&self) -> &[u16] {
+        &self.bits
+    }
+
+    pub fn full_rows(&self) -> Vec<usize> {
+        (0..TOTAL_HEIGHT).filter(|&y| self.bits[y] == FULL_ROW).collect()
+    }
+
+    // Whether everything outside the given rows is empty.
+    pub fn is_clear_except(&self, rows: &[usize]) -> bool {
+        (0..TOTAL_HEIGHT).all(|y| rows.contains(&y) || self.bits[y] == 0)
+    }
+
+    // Rows from the floor up to the highest filled cell.
+    pub fn stack_height(&self) -> usize {
+        self.bits.iter().position(|&bits| bits != 0).map_or(0, |top| TOTAL_HEIGHT - top)
+    }
+
+    // Rows must be in ascending order; removing from the top down keeps the
+    // remaining indices valid.
+    pub fn remove_rows(&mut self, rows: &[usize]) {
+        for &y in rows {
+            self.bits.remove(y);
+            self.bits.insert(0, 0);
+            self.cells.remove(y);
+            self.cells.insert(0, vec![BlockType::Empty; BOARD_WIDTH]);
+        }
+    }
+
+    // Pushes the stack up from the bottom. Returns whether anything was
+    // pushed off the top.
+    pub fn push_row(&mut self, row: Vec<BlockType>) -> bool {
+        let overflowed = self.bits.remove(0) != 0;
+        self.cells.remove(0);
+        self.bits.push(row_bits(&row));
+        self.cells.push(row);
+        overflowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PieceKind, BOARD_HEIGHT};
+
+    const BOTTOM: usize = TOTAL_HEIGHT - 1;
+
+    fn filled_row(gap: Option<usize>) -> Vec<BlockType> {
+        (0..BOARD_WIDTH)
+            .map(|x| if Some(x) == gap { BlockType::Empty } else { BlockType::Garbage })
+            .collect()
+    }
+
+    #[test]
+    fn set_keeps_bits_and_cells_in_step() {
+        let mut board = Board::default();
+        board.set(3, BOTTOM, BlockType::Filled(PieceKind::T));
+        assert_eq!(board.bits()[BOTTOM], 1 << 3);
+        assert!(board[BOTTOM][3] == BlockType::Filled(PieceKind::T));
+        assert!(board.is_filled(3, BOTTOM as i32));
+        board.set(3, BOTTOM, BlockType::Empty);
+        assert_eq!(board.bits()[BOTTOM], 0);
+        assert!(board[BOTTOM][3] == BlockType::Empty);
+        board.set(BOARD_WIDTH, BOTTOM, BlockType::Garbage);
+        board.set(0, TOTAL_HEIGHT, BlockType::Garbage);
+        assert!(board.bits().iter().all(|&bits| bits == 0));
+    }
+
+    #[test]
+    fn walls_and_floor_are_filled() {
+        let board = Board::default();
+        assert!(board.is_filled(-1, 0));
+        assert!(board.is_filled(BOARD_WIDTH as i32, 0));
+        assert!(board.is_filled(0, TOTAL_HEIGHT as i32));
+        assert!(!board.is_filled(0, -1));
+        assert!(!board.is_filled(0, 0));
+    }
+
+    #[test]
+    fn fits_against_walls_floor_and_stack() {
+        let mut board = Board::default();
+        // A horizontal I piece.
+        let masks = [0b1111];
+        assert!(board.fits(&masks, 0, BOTTOM as i32));
+        assert!(board.fits(&masks, BOARD_WIDTH as i32 - 4, BOTTOM as i32));
+        assert!(!board.fits(&masks, BOARD_WIDTH as i32 - 3, BOTTOM as i32));
+        assert!(!board.fits(&masks, -1, BOTTOM as i32));
+        assert!(!board.fits(&masks, 0, TOTAL_HEIGHT as i32));
+        assert!(board.fits(&masks, 0, -1));
+        // Empty columns on the left of the mask may hang past the wall.
+        assert!(board.fits(&[0b0110], -1, BOTTOM as i32));
+        board.set(2, BOTTOM, BlockType::Garbage);
+        assert!(!board.fits(&masks, 0, BOTTOM as i32));
+        assert!(board.fits(&masks, 3, BOTTOM as i32));
+        // Empty mask rows are skipped, even below the floor.
+        assert!(board.fits(&[0b1111, 0], 3, BOTTOM as i32));
+    }
+
+    #[test]
+    fn full_rows_and_removal() {
+        let mut visible = vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        visible[BOARD_HEIGHT - 3] = filled_row(None);
+        visible[BOARD_HEIGHT - 2] = filled_row(Some(4));
+        visible[BOARD_HEIGHT - 1] = filled_row(None);
+        let mut board = Board::new(visible);
+        let rows = board.full_rows();
+        assert_eq!(rows, vec![BOTTOM - 2, BOTTOM]);
+        assert_eq!(board.stack_height(), 3);
+        assert!(!board.is_clear_except(&rows));
+
+        board.remove_rows(&rows);
+        assert_eq!(board.stack_height(), 1);
+        assert_eq!(board.bits()[BOTTOM], FULL_ROW & !(1 << 4));
+        assert!(board[BOTTOM][4] == BlockType::Empty);
+        assert!(board[BOTTOM][0] == BlockType::Garbage);
+        assert!(board.is_clear_except(&[BOTTOM]));
+        assert!(board.full_rows().is_empty());
+    }
+
+    #[test]
+    fn push_row_lifts_the_stack() {
+        let mut board = Board::default();
+        board.set(0, BOTTOM, BlockType::Filled(PieceKind::O));
+        assert!(!board.push_row(filled_row(Some(9))));
+        assert_eq!(board.stack_height(), 2);
+        assert!(board[BOTTOM - 1][0] == BlockType::Filled(PieceKind::O));
+        assert_eq!(board.bits()[BOTTOM], FULL_ROW & !(1 << 9));
+
+        board.set(0, 0, BlockType::Garbage);
+        assert!(board.push_row(filled_row(None)));
+        assert_eq!(board.bits().len(), TOTAL_HEIGHT);
+        assert_eq!(board.full_rows(), vec![BOTTOM]);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tetorisu::net::ClientMessage;
+
+use crate::env::{self, Env};
+use crate::export::{ExportOptions, Recorder};
+use crate::modes::Mode;
+use crate::randomizer::Randomizer;
+use crate::rollback::Session;
+use crate::rotation::Turn;
+use crate::tas::{Input, InputScript, FRAME};
+use crate::{BlockType, TetrisGame, BOARD_HEIGHT, BOARD_WIDTH};
+
+#[derive(Parser)]
+#[command(about = "A Tetris clone")]
+pub struct Cli {
+    /// Mode to start in: endless, sprint, ultra, b-type, practice or a script in the data directory's mods/
+    #[arg(long)]
+    pub mode: Option<String>,
+    /// Seed for the piece randomizer, for repeatable games
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Config file to use instead of the default location
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Start in fullscreen
+    #[arg(long)]
+    pub fullscreen: bool,
+    /// Write each game's engine events to a JSONL file under the data directory's events folder
+    #[arg(long)]
+    pub log_events: bool,
+    /// Drop this many random pieces without a window and report throughput
+    #[arg(long, value_name = "PIECES")]
+    pub headless: Option<u32>,
+    /// Play two random bots against each other through rollback over a link with this much latency, and report how it went
+    #[arg(long, value_name = "MS")]
+    pub rollback_test: Option<u32>,
+    /// Serve a reinforcement learning environment on stdin and stdout, one JSON request per line
+    #[arg(long)]
+    pub env: bool,
+    /// Play an input script without a window and print how the game ended
+    #[arg(long, value_name = "PATH")]
+    pub tas: Option<PathBuf>,
+    /// Also render the --tas run to an animated .gif, .png or .apng
+    #[arg(long, value_name = "PATH", requires = "tas")]
+    pub export: Option<PathBuf>,
+    /// Pixels per cell in the exported animation
+    #[arg(long, value_name = "PIXELS", default_value_t = 16, value_parser = clap::value_parser!(u32).range(2..=64))]
+    pub export_cell: u32,
+    /// Playback speed of the exported animation, e.g. 2 for double speed
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+    pub export_speed: f32,
+}
+
+// Plays random placements as fast as possible, starting a new game whenever
+// one tops out, to benchmark the engine.
+pub fn run_headless(pieces: u32, seed: Option<u64>) {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let new_game = |rng: &mut StdRng| {
+        let mut game = TetrisGame::new(None, empty_board(), Some(rng.gen()), Randomizer::default());
+        game.clear_delay = Duration::ZERO;
+        game
+    };
+    let mut game = new_game(&mut rng);
+    let (mut games, mut lines, mut score) = (1, 0, 0);
+    let start = Instant::now();
+
+    for _ in 0..pieces {
+        match rng.gen_range(0..4) {
+            1 => game.rotate_piece(Turn::Cw),
+            2 => game.rotate_piece(Turn::Half),
+            3 => game.rotate_piece(Turn::Ccw),
+            _ => {}
+        }
+        let dx = if rng.gen() { 1 } else { -1 };
+        for _ in 0..rng.gen_range(0..=BOARD_WIDTH / 2) {
+            game.move_piece(dx);
+        }
+        game.hard_drop();
+        game.tick(Duration::from_millis(16));
+        if game.game_over {
+            lines += game.stats.lines;
+            score += game.score as u64;
+            games += 1;
+            game = new_game(&mut rng);
+        }
+    }
+    lines += game.stats.lines;
+    score += game.score as u64;
+
+    let elapsed = start.elapsed();
+    println!(
+        "{} pieces in {:.3}s ({:.0} pieces/s) across {} games, {} lines, {} points, state hash {:016x}",
+        pieces,
+        elapsed.as_secs_f64(),
+        pieces as f64 / elapsed.as_secs_f64(),
+        games,
+        lines,
+        score,
+        game.state_hash()
+    );
+}
+
+// Two sides, each with its own session, trading messages through a pretend
+// server that holds every message for the given one-way latency.
+pub fn run_rollback_test(latency_ms: u32, seed: Option<u64>) -> Result<(), String> {
+    const FRAMES: u32 = 60 * 60;
+    const TURNS: [Turn; 3] = [Turn::Cw, Turn::Ccw, Turn::Half];
+    let seed = seed.unwrap_or(0);
+    let latency = (latency_ms as f64 / FRAME.as_secs_f64() / 1000.0).ceil() as u32;
+    let new_game = || TetrisGame::new(None, empty_board(), Some(seed), Randomizer::default());
+    let mut sides = [Session::new(new_game(), new_game()), Session::new(new_game(), new_game())];
+    let mut bots: Vec<StdRng> = (0..2).map(|side| StdRng::seed_from_u64(seed + side)).collect();
+    // Each bot picks a column for its piece, walks it over and drops it, a
+    // few frames between inputs.
+    let mut targets: [Option<i32>; 2] = [None; 2];
+    let mut pending: [Vec<Input>; 2] = Default::default();
+    let mut waited = [0; 2];
+    let mut in_flight: [VecDeque<(u32, ClientMessage)>; 2] = Default::default();
+
+    for now in 0..FRAMES {
+        for side in 0..2 {
+            let other = 1 - side;
+            while in_flight[other].front().is_some_and(|&(arrives, _)| arrives <= now) {
+                let (_, message) = in_flight[other].pop_front().unwrap();
+                match message {
+                    ClientMessage::Input { frame, actions } => sides[side].receive_input(frame, &actions)?,
+                    ClientMessage::StateHash { frame, hash } => sides[side].receive_hash(frame, hash),
+                    _ => {}
+                }
+            }
+            let bot = &mut bots[side];
+            if pending[side].is_empty() && bot.gen_ratio(1, 6) {
+                let x = sides[side].local().current_piece.x;
+                let input = match targets[side] {
+                    None => {
+                        targets[side] = Some(bot.gen_range(0..=BOARD_WIDTH as i32 - 4));
+                        Input::Rotate(TURNS[bot.gen_range(0..TURNS.len())])
+                    }
+                    Some(target) if x < target => Input::MoveRight,
+                    Some(target) if x > target => Input::MoveLeft,
+                    Some(_) => {
+                        targets[side] = None;
+                        Input::HardDrop
+                    }
+                };
+                pending[side].push(input);
+            }
+            if sides[side].advance(&pending[side]) {
+                pending[side].clear();
+            } else {
+                waited[side] += 1;
+            }
+            sides[side].take_events();
+            let outgoing = sides[side].outgoing();
+            in_flight[side].extend(outgoing.into_iter().map(|message| (now + latency, message)));
+        }
+    }
+
+    println!("{} frames at {} ms ({} frames) each way:", FRAMES, latency_ms, latency);
+    for (index, session) in sides.iter().enumerate() {
+        println!(
+            "  player {}: at frame {}, confirmed to {}, waited {} frames, {} rollbacks replaying {} frames, {} vs {} pieces",
+            index + 1,
+            session.frame(),
+            session.confirmed_frame(),
+            waited[index],
+            session.rollbacks(),
+            session.replayed(),
+            session.local().stats.pieces,
+            session.remote().stats.pieces
+        );
+    }
+    match sides.iter().find_map(Session::desync) {
+        Some(frame) => Err(format!("boards out of step at frame {}", frame)),
+        None => {
+            println!("  boards in step at every check");
+            Ok(())
+        }
+    }
+}
+
+pub fn run_env(mode: Option<&str>) -> Result<(), String> {
+    let mode = mode.map(Mode::parse).transpose()?.unwrap_or(Mode::Endless);
+    env::serve(Env::new(mode)?).map_err(|error| error.to_string())
+}
+
+// The script's own seed and mode are used unless given on the command line.
+pub fn run_tas(path: &Path, mode: Option<&str>, seed: Option<u64>, export: Option<ExportOptions>) -> Result<(), String> {
+    let script = InputScript::load(path)?;
+    let mode = match mode.or(script.mode.as_deref()) {
+        Some(name) => Mode::parse(name)?,
+        None => Mode::Endless,
+    };
+    let seed = seed.or(script.seed).unwrap_or(0);
+    let outcome = match export {
+        Some(options) => {
+            let mut recorder = Recorder::new(&options)?;
+            let outcome = script.play(&mode, seed, |game| recorder.capture(game))?;
+            recorder.save()?;
+            outcome
+        }
+        None => script.run(&mode, seed)?,
+    };
+    print!("{}", outcome.report());
+    Ok(())
+}
+
+fn empty_board() -> Vec<Vec<BlockType>> {
+    vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT]
+}
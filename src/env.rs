@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use crate::modes::Mode;
+use crate::randomizer::Randomizer;
+use crate::settings::Settings;
+use crate::tas::{self, Input, FRAME};
+use crate::{BlockType, PieceKind, TetrisGame, BOARD_HEIGHT, BOARD_WIDTH, PREVIEW_COUNT, TOTAL_HEIGHT};
+
+// Action numbers, in this order. Each step plays one frame.
+pub const ACTIONS: [&str; 9] = [
+    "none",
+    "move_left",
+    "move_right",
+    "rotate_cw",
+    "rotate_ccw",
+    "rotate_180",
+    "hold",
+    "hard_drop",
+    "soft_drop",
+];
+
+// What each step pays out, summed. Everything but lines and topping out is
+// off by default.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rewards {
+    pub step: f32,
+    pub piece: f32,
+    pub lines: f32,
+    // Per point of score, so spins and combos count for more.
+    pub score: f32,
+    // Per row the stack grows, and back when it shrinks.
+    pub height: f32,
+    pub top_out: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            step: 0.0,
+            piece: 0.0,
+            lines: 1.0,
+            score: 0.0,
+            height: 0.0,
+            top_out: -1.0,
+        }
+    }
+}
+
+// Flat arrays, ready to feed to a network. Piece kinds are numbered in
+// I, O, T, L, J, S, Z order, with -1 for none.
+#[derive(Serialize)]
+pub struct Observation {
+    // Row-major from the top of the hidden rows, 1 for a filled cell. The
+    // falling piece isn't included.
+    pub board: Vec<u8>,
+    // Kind, column, row and rotation (0 to 3 clockwise from spawn).
+    pub piece: [i32; 4],
+    pub queue: Vec<i32>,
+    pub hold: i32,
+    pub hold_used: bool,
+}
+
+#[derive(Serialize)]
+pub struct Transition {
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool,
+}
+
+// One request per line on stdin, answered with one line on stdout:
+//
+//   {"type": "reset", "seed": 42, "rewards": {"lines": 1.0}}  -> observation
+//   {"type": "step", "action": 7}                             -> transition
+//   {"type": "spec"}                                          -> sizes and action names
+//
+// Anything that goes wrong is answered with {"error": "..."}.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Reset {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default)]
+        rewards: Rewards,
+    },
+    Step {
+        action: usize,
+    },
+    Spec,
+}
+
+// The engine without a window, one frame per step, for training agents
+// against exactly the game people play. Line clears are instant, as in
+// --headless, so no steps are spent waiting on the animation.
+pub struct Env {
+    mode: Mode,
+    rewards: Rewards,
+    game: TetrisGame,
+}
+
+impl Env {
+    // Script modes are left out; their hooks need more than the engine.
+    pub fn new(mode: Mode) -> Result<Self, String> {
+        if mode.script().is_some() {
+            return Err(format!("{} is a script mode, which the environment can't run", mode.name()));
+        }
+        let mut env = Self {
+            mode,
+            rewards: Rewards::default(),
+            game: TetrisGame::default(),
+        };
+        env.reset(0, Rewards::default());
+        Ok(env)
+    }
+
+    pub fn reset(&mut self, seed: u64, rewards: Rewards) -> Observation {
+        let board = vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+        self.game = TetrisGame::new(None, board, Some(seed), Randomizer::default());
+        self.game.start_mode(&self.mode, &Settings::default());
+        self.game.clear_delay = Duration::ZERO;
+        self.rewards = rewards;
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: usize) -> Result<Transition, String> {
+        let input = match ACTIONS.get(action) {
+            Some(&"none") | Some(&"soft_drop") => None,
+            Some(name) => Some(Input::parse(name)?),
+            None => return Err(format!("action {} out of range 0..{}", action, ACTIONS.len())),
+        };
+        let game = &mut self.game;
+        if game.game_over {
+            return Err("the game is over; reset first".to_string());
+        }
+        let (score, lines, pieces, height) = (game.score, game.stats.lines, game.stats.pieces, game.stack_height());
+        if let Some(input) = input {
+            tas::apply(game, input);
+        }
+        game.soft_drop = ACTIONS[action] == "soft_drop";
+        game.tick(FRAME);
+        game.events.clear();
+
+        let rewards = &self.rewards;
+        let mut reward = rewards.step
+            + rewards.piece * (game.stats.pieces - pieces) as f32
+            + rewards.lines * (game.stats.lines - lines) as f32
+            + rewards.score * (game.score - score) as f32
+            + rewards.height * (game.stack_height() as f32 - height as f32);
+        if game.top_out.is_some() {
+            reward += rewards.top_out;
+        }
+        Ok(Transition {
+            observation: self.observe(),
+            reward,
+            done: self.game.game_over,
+        })
+    }
+
+    fn observe(&self) -> Observation {
+        let game = &self.game;
+        let piece = &game.current_piece;
+        Observation {
+            board: game.board.iter().flatten().map(|&block| (block != BlockType::Empty) as u8).collect(),
+            piece: [piece.kind as i32, piece.x, piece.y, piece.rotation as i32],
+            queue: game.next_queue.iter().map(|&kind| kind as i32).collect(),
+            hold: game.hold.map_or(-1, |kind| kind as i32),
+            hold_used: game.hold_used,
+        }
+    }
+}
+
+pub fn serve(mut env: Env) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str(&line).map_err(|error| error.to_string()) {
+            Ok(Request::Reset { seed, rewards }) => json!(env.reset(seed, rewards)),
+            Ok(Request::Step { action }) => match env.step(action) {
+                Ok(transition) => json!(transition),
+                Err(error) => json!({ "error": error }),
+            },
+            Ok(Request::Spec) => json!({
+                "actions": ACTIONS,
+                "board": [TOTAL_HEIGHT, BOARD_WIDTH],
+                "queue": PREVIEW_COUNT,
+                "pieces": PieceKind::ALL.len(),
+            }),
+            Err(error) => json!({ "error": error }),
+        };
+        writeln!(out, "{}", response)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HARD_DROP: usize = 7;
+
+    fn env() -> Env {
+        Env::new(Mode::Endless).unwrap()
+    }
+
+    fn observed(observation: &Observation) -> serde_json::Value {
+        json!(observation)
+    }
+
+    #[test]
+    fn seeded_resets_repeat() {
+        let (mut first, mut second) = (env(), env());
+        assert_eq!(observed(&first.reset(42, Rewards::default())), observed(&second.reset(42, Rewards::default())));
+        for action in [1, 3, HARD_DROP, 6, 2, HARD_DROP, 0, 8] {
+            let (a, b) = (first.step(action).unwrap(), second.step(action).unwrap());
+            assert_eq!(json!(a), json!(b));
+        }
+        let queue = |env: &mut Env, seed| env.reset(seed, Rewards::default()).queue;
+        assert_ne!(queue(&mut first, 1), queue(&mut second, 2));
+    }
+
+    #[test]
+    fn steps_pay_out_until_the_top_out() {
+        let mut env = env();
+        let rewards = Rewards { piece: 1.0, ..Rewards::default() };
+        env.reset(3, rewards);
+        let transition = env.step(HARD_DROP).unwrap();
+        assert_eq!(transition.reward, 1.0);
+        assert!(!transition.done);
+
+        // Dropping every piece in the middle tops out without clearing lines,
+        // so only the top out pays.
+        env.reset(3, Rewards::default());
+        let mut rewards = Vec::new();
+        while rewards.len() < 10_000 {
+            let transition = env.step(HARD_DROP).unwrap();
+            rewards.push(transition.reward);
+            if transition.done {
+                break;
+            }
+        }
+        assert_eq!(rewards.pop(), Some(-1.0));
+        assert!(rewards.iter().all(|&reward| reward == 0.0));
+        assert!(env.step(0).is_err());
+    }
+
+    #[test]
+    fn malformed_actions_are_rejected() {
+        let mut env = env();
+        assert!(env.step(ACTIONS.len()).is_err());
+        assert!(env.step(0).is_ok());
+        assert!(serde_json::from_str::<Request>(r#"{"type": "step", "action": -1}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"type": "step"}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"type": "jump"}"#).is_err());
+        assert!(matches!(serde_json::from_str(r#"{"type": "step", "action": 7}"#), Ok(Request::Step { action: 7 })));
+    }
+
+    #[test]
+    fn script_modes_are_refused() {
+        assert!(Env::new(Mode::Script("cheese".to_string())).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::rotation::{Rotation, Turn};
+use crate::sequence::piece_char;
+use crate::{history, GameEvent, Spin, TetrisGame, Tetromino, TopOut};
+
+fn dir() -> PathBuf {
+    history::data_dir().join("events")
+}
+
+// One file per game under the data directory, one JSON object per line.
+// Every record has the game time in seconds and an "event" name; the first
+// is "start" and, if the game runs to the end, the last is "end".
+pub struct EventLog {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl EventLog {
+    // Files are named after the time the game starts, with a counter added
+    // when several start within the same second.
+    pub fn create(mode: &str, seed: Option<u64>, kicks: &str, curve: Option<&str>) -> io::Result<Self> {
+        let dir = dir();
+        fs::create_dir_all(&dir)?;
+        let stamp = history::timestamp().replace([' ', ':'], "-");
+        let mut index = 1;
+        let (path, file) = loop {
+            let name = if index == 1 { stamp.clone() } else { format!("{}-{}", stamp, index) };
+            let path = dir.join(format!("{}.jsonl", name));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => index += 1,
+                Err(error) => return Err(error),
+            }
+        };
+        let mut log = Self {
+            path,
+            writer: BufWriter::new(file),
+        };
+        let start = json!({ "event": "start", "mode": mode, "seed": seed, "kicks": kicks, "curve": curve });
+        log.write(Duration::ZERO, start)?;
+        Ok(log)
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    fn write(&mut self, time: Duration, mut record: Value) -> io::Result<()> {
+        record["time"] = json!(time.as_secs_f64());
+        writeln!(self.writer, "{}", record)
+    }
+
+    pub fn record(&mut self, time: Duration, event: &GameEvent) -> io::Result<()> {
+        let record = match event {
+            GameEvent::Spawned(kind) => json!({ "event": "spawn", "piece": piece_char(*kind).to_string() }),
+            GameEvent::Moved { dx, to } => json!({ "event": "move", "dx": dx, "piece": piece(to) }),
+            GameEvent::Rotated { turn, kick, to } => {
+                json!({ "event": "rotate", "turn": turn_name(*turn), "kick": kick, "piece": piece(to) })
+            }
+            GameEvent::Held(kind) => json!({ "event": "hold", "piece": piece_char(*kind).to_string() }),
+            GameEvent::HardDrop(cells) => json!({ "event": "hard_drop", "cells": cells }),
+            GameEvent::Locked(to) => json!({ "event": "lock", "piece": piece(to) }),
+            GameEvent::LinesCleared { rows, perfect, spin } => json!({
+                "event": "clear",
+                "lines": rows.len(),
+                "rows": rows.iter().map(|(y, _)| y).collect::<Vec<_>>(),
+                "spin": match spin {
+                    Spin::None => "none",
+                    Spin::Mini => "mini",
+                    Spin::Full => "full",
+                },
+                "perfect": perfect,
+            }),
+            GameEvent::Scored { points, combo, back_to_back, .. } => json!({
+                "event": "score",
+                "points": points,
+                "combo": combo,
+                "back_to_back": back_to_back,
+            }),
+            GameEvent::GarbageRaised(lines) => json!({ "event": "garbage", "lines": lines }),
+            GameEvent::ToppedOut(reason) => json!({
+                "event": "top_out",
+                "reason": match reason {
+                    TopOut::Block => "block",
+                    TopOut::Lock => "lock",
+                    TopOut::Garbage => "garbage",
+                },
+            }),
+        };
+        self.write(time, record)
+    }
+
+    pub fn finish(mut self, game: &TetrisGame) -> io::Result<()> {
+        let stats = &game.stats;
+        let record = json!({
+            "event": "end",
+            "score": game.score,
+            "lines": stats.lines,
+            "pieces": stats.pieces,
+            "finished": game.finished,
+        });
+        self.write(stats.time, record)?;
+        self.writer.flush()
+    }
+}
+
+// Rows count from the top of the hidden rows, as on the board.
+fn piece(piece: &Tetromino) -> Value {
+    let rotation = match piece.rotation {
+        Rotation::Spawn => "0",
+        Rotation::Right => "R",
+        Rotation::Reverse => "2",
+        Rotation::Left => "L",
+    };
+    json!({
+        "kind": piece_char(piece.kind).to_string(),
+        "x": piece.x,
+        "y": piece.y,
+        "rotation": rotation,
+        "cells": piece.cells(),
+    })
+}
+
+fn turn_name(turn: Turn) -> &'static str {
+    match turn {
+        Turn::Cw => "cw",
+        Turn::Ccw => "ccw",
+        Turn::Half => "180",
+    }
+}
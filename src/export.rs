@@ -0,0 +1,177 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use crate::skins::{Palette, PieceColors};
+use crate::{BlockType, PieceKind, TetrisGame, BOARD_HEIGHT, BOARD_WIDTH, HIDDEN_ROWS};
+
+// Frames are sampled every 5 centiseconds of output, 20 per second, which
+// every GIF viewer plays at the stated speed.
+const SAMPLE_CENTIS: u16 = 5;
+// How much output time one 60 Hz game frame covers at normal speed.
+const FRAME_CENTIS: f64 = 100.0 / 60.0;
+// The final board stays up this long before the animation loops.
+const HOLD_CENTIS: u16 = 200;
+
+// Palette slots: background, grid lines, garbage, then the pieces in
+// PieceKind::ALL order.
+const BACKGROUND: u8 = 0;
+const GRID: u8 = 1;
+const GARBAGE: u8 = 2;
+
+pub struct ExportOptions {
+    pub path: PathBuf,
+    // Pixels per cell.
+    pub cell: u32,
+    // Playback speed, 2.0 for double speed.
+    pub speed: f32,
+}
+
+enum Format {
+    Gif,
+    Apng,
+}
+
+// Turns a game into an animation one frame at a time, as it is played.
+// Frames are drawn with a fixed palette straight into indexed pixels, and
+// runs of identical frames are merged into one longer frame.
+pub struct Recorder {
+    path: PathBuf,
+    format: Format,
+    cell: usize,
+    speed: f64,
+    palette: Vec<u8>,
+    played: u64,
+    next_sample: f64,
+    frames: Vec<(Vec<u8>, u16)>,
+}
+
+impl Recorder {
+    pub fn new(options: &ExportOptions) -> Result<Self, String> {
+        let extension = options.path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        let format = match extension.to_ascii_lowercase().as_str() {
+            "gif" => Format::Gif,
+            "png" | "apng" => Format::Apng,
+            _ => return Err(format!("{}: expected a .gif, .png or .apng file", options.path.display())),
+        };
+        if !options.speed.is_finite() || options.speed <= 0.0 {
+            return Err(format!("speed must be above zero, not {}", options.speed));
+        }
+        let colors = PieceColors::default();
+        let mut palette = vec![20, 20, 20, 40, 40, 40];
+        palette.extend(colors.garbage().to_array()[..3].iter());
+        for kind in PieceKind::ALL {
+            palette.extend(Palette::Standard.color(kind, &colors).to_array()[..3].iter());
+        }
+        Ok(Self {
+            path: options.path.clone(),
+            format,
+            cell: options.cell as usize,
+            speed: options.speed as f64,
+            palette,
+            played: 0,
+            next_sample: 0.0,
+            frames: Vec::new(),
+        })
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (BOARD_WIDTH * self.cell + 1, BOARD_HEIGHT * self.cell + 1)
+    }
+
+    pub fn capture(&mut self, game: &TetrisGame) {
+        let now = self.played as f64 * FRAME_CENTIS / self.speed;
+        self.played += 1;
+        // Slowed down, one game frame can cover several samples.
+        let mut delay = 0;
+        while now >= self.next_sample {
+            delay += SAMPLE_CENTIS;
+            self.next_sample += SAMPLE_CENTIS as f64;
+        }
+        if delay == 0 {
+            return;
+        }
+        let pixels = self.render(game);
+        match self.frames.last_mut() {
+            Some((last, last_delay)) if *last == pixels => *last_delay = last_delay.saturating_add(delay),
+            _ => self.frames.push((pixels, delay)),
+        }
+    }
+
+    fn render(&self, game: &TetrisGame) -> Vec<u8> {
+        let (width, height) = self.size();
+        let mut pixels = vec![GRID; width * height];
+        let mut fill = |x: usize, y: usize, color: u8| {
+            for row in y * self.cell + 1..(y + 1) * self.cell {
+                pixels[row * width + x * self.cell + 1..row * width + (x + 1) * self.cell].fill(color);
+            }
+        };
+        let color = |block| match block {
+            BlockType::Empty => BACKGROUND,
+            BlockType::Garbage => GARBAGE,
+            BlockType::Filled(kind) => 3 + kind as u8,
+        };
+        for (y, row) in game.board[HIDDEN_ROWS..].iter().enumerate() {
+            for (x, &block) in row.iter().enumerate() {
+                fill(x, y, color(block));
+            }
+        }
+        // The locked piece is already on the board while rows clear.
+        if !game.is_waiting() {
+            let piece = &game.current_piece;
+            for (x, y) in piece.cells() {
+                if y >= HIDDEN_ROWS as i32 {
+                    fill(x as usize, y as usize - HIDDEN_ROWS, color(BlockType::Filled(piece.kind)));
+                }
+            }
+        }
+        pixels
+    }
+
+    pub fn save(mut self) -> Result<(), String> {
+        let path = &self.path;
+        if let Some((_, delay)) = self.frames.last_mut() {
+            *delay = delay.saturating_add(HOLD_CENTIS);
+        }
+        let file = File::create(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        let result = match self.format {
+            Format::Gif => self.write_gif(file),
+            Format::Apng => self.write_apng(file),
+        };
+        result.map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    fn write_gif(&self, file: File) -> Result<(), String> {
+        let (width, height) = self.size();
+        let mut encoder = gif::Encoder::new(BufWriter::new(file), width as u16, height as u16, &self.palette)
+            .map_err(|error| error.to_string())?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|error| error.to_string())?;
+        for (pixels, delay) in &self.frames {
+            let frame = gif::Frame {
+                width: width as u16,
+                height: height as u16,
+                delay: *delay,
+                buffer: Cow::Borrowed(pixels),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).map_err(|error| error.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn write_apng(&self, file: File) -> Result<(), String> {
+        let (width, height) = self.size();
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(self.palette.clone());
+        encoder.set_animated(self.frames.len() as u32, 0).map_err(|error| error.to_string())?;
+        let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+        for (pixels, delay) in &self.frames {
+            writer.set_frame_delay(*delay, 100).map_err(|error| error.to_string())?;
+            writer.write_image_data(pixels).map_err(|error| error.to_string())?;
+        }
+        writer.finish().map_err(|error| error.to_string())
+    }
+}
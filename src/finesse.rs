@@ -0,0 +1,238 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::rotation::Turn;
+use crate::{PieceKind, TetrisGame, Tetromino};
+
+#[derive(Clone, Copy)]
+pub struct FinesseFault {
+    pub kind: PieceKind,
+    pub used: u32,
+    pub optimal: u32,
+}
+
+#[derive(Clone, Default)]
+pub struct Finesse {
+    pub enabled: bool,
+    pub strict: bool,
+    inputs: u32,
+    pub faults: [u32; 7],
+    pub placements: [u32; 7],
+    pub last_fault: Option<FinesseFault>,
+}
+
+impl Finesse {
+    pub fn count_input(&mut self) {
+        self.inputs += 1;
+    }
+
+    pub fn reset_inputs(&mut self) {
+        self.inputs = 0;
+    }
+
+    pub fn total_faults(&self) -> u32 {
+        self.faults.iter().sum()
+    }
+
+    pub fn total_placements(&self) -> u32 {
+        self.placements.iter().sum()
+    }
+
+    // Returns false when strict mode rejects the placement.
+    pub fn record(&mut self, kind: PieceKind, optimal: Option<u32>) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        // Placements the search can't reach (tucks, spins) aren't judged.
+        let Some(optimal) = optimal else {
+            return true;
+        };
+        let index = kind as usize;
+        if self.inputs > optimal {
+            self.faults[index] += 1;
+            self.last_fault = Some(FinesseFault {
+                kind,
+                used: self.inputs,
+                optimal,
+            });
+            if self.strict {
+                return false;
+            }
+        }
+        self.placements[index] += 1;
+        true
+    }
+}
+
+// One input in the search below.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Step {
+    Tap(i32),
+    // Held until the piece reaches the wall.
+    Das(i32),
+    Rotate(Turn),
+}
+
+// Breadth-first search over the inputs available at spawn height: a single
+// tap, a held move to the wall, and each turn the kick table allows. Each
+// costs one input.
+pub fn minimal_inputs(game: &TetrisGame, kind: PieceKind, target: &[(i32, i32)]) -> Option<u32> {
+    let start = Tetromino::new(kind);
+    if !game.is_valid_position(&start) {
+        return None;
+    }
+
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    seen.insert((start.x, start.y, start.rotation));
+    queue.push_back((start, 0));
+
+    while let Some((piece, cost)) = queue.pop_front() {
+        if game.drop_position(&piece).cells() == target {
+            return Some(cost);
+        }
+        for (_, next) in neighbours(game, &piece) {
+            if seen.insert((next.x, next.y, next.rotation)) {
+                queue.push_back((next, cost + 1));
+            }
+        }
+    }
+    None
+}
+
+// Every distinct landing spot the same search reaches, each with the
+// shortest inputs that get there.
+pub fn placements(game: &TetrisGame, kind: PieceKind) -> Vec<(Tetromino, Vec<Step>)> {
+    let start = Tetromino::new(kind);
+    if !game.is_valid_position(&start) {
+        return Vec::new();
+    }
+
+    let mut seen = HashSet::new();
+    let mut landed = HashSet::new();
+    let mut queue = VecDeque::new();
+    let mut result = Vec::new();
+    seen.insert((start.x, start.y, start.rotation));
+    queue.push_back((start, Vec::new()));
+
+    while let Some((piece, steps)) = queue.pop_front() {
+        let dropped = game.drop_position(&piece);
+        if landed.insert(dropped.cells()) {
+            result.push((dropped, steps.clone()));
+        }
+        for (step, next) in neighbours(game, &piece) {
+            if seen.insert((next.x, next.y, next.rotation)) {
+                let mut steps = steps.clone();
+                steps.push(step);
+                queue.push_back((next, steps));
+            }
+        }
+    }
+    result
+}
+
+fn neighbours(game: &TetrisGame, piece: &Tetromino) -> Vec<(Step, Tetromino)> {
+    let mut result = Vec::new();
+    for dx in [-1, 1] {
+        let mut moved = *piece;
+        moved.x += dx;
+        if !game.is_valid_position(&moved) {
+            continue;
+        }
+        result.push((Step::Tap(dx), moved));
+        while game.is_valid_position(&moved) {
+            moved.x += dx;
+        }
+        moved.x -= dx;
+        result.push((Step::Das(dx), moved));
+    }
+    let turns: &[Turn] = if game.kicks.has_half_kicks() {
+        &[Turn::Cw, Turn::Ccw, Turn::Half]
+    } else {
+        &[Turn::Cw, Turn::Ccw]
+    };
+    for &turn in turns {
+        if let Some(rotated) = game.try_rotate(piece, turn) {
+            result.push((Step::Rotate(turn), rotated));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(game: &TetrisGame, kind: PieceKind, turn: Option<Turn>, dx: i32) -> [(i32, i32); 4] {
+        let mut piece = Tetromino::new(kind);
+        if let Some(turn) = turn {
+            piece.rotate(turn);
+        }
+        piece.x += dx;
+        game.drop_position(&piece).cells()
+    }
+
+    #[test]
+    fn half_turn_is_one_input() {
+        let game = TetrisGame::default();
+        assert!(game.kicks.has_half_kicks());
+        let reversed = target(&game, PieceKind::T, Some(Turn::Half), 0);
+        assert_eq!(minimal_inputs(&game, PieceKind::T, &reversed), Some(1));
+        let steps = placements(&game, PieceKind::T)
+            .into_iter()
+            .find(|(piece, _)| piece.cells() == reversed)
+            .map(|(_, steps)| steps);
+        assert_eq!(steps, Some(vec![Step::Rotate(Turn::Half)]));
+    }
+
+    #[test]
+    fn moves_to_the_wall_take_one_input() {
+        let game = TetrisGame::default();
+        let spawn = target(&game, PieceKind::T, None, 0);
+        assert_eq!(minimal_inputs(&game, PieceKind::T, &spawn), Some(0));
+        let tapped = target(&game, PieceKind::T, None, -1);
+        assert_eq!(minimal_inputs(&game, PieceKind::T, &tapped), Some(1));
+        let wall = target(&game, PieceKind::T, None, -3);
+        assert_eq!(minimal_inputs(&game, PieceKind::T, &wall), Some(1));
+        let next_to_wall = target(&game, PieceKind::T, None, -2);
+        assert_eq!(minimal_inputs(&game, PieceKind::T, &next_to_wall), Some(2));
+        let floating = [(0, 0), (1, 0), (2, 0), (1, 1)];
+        assert_eq!(minimal_inputs(&game, PieceKind::T, &floating), None);
+    }
+
+    #[test]
+    fn every_landing_spot_once() {
+        let game = TetrisGame::default();
+        assert_eq!(placements(&game, PieceKind::O).len(), 9);
+        // Flat both ways up, and upright on either side.
+        assert_eq!(placements(&game, PieceKind::T).len(), 8 + 8 + 9 + 9);
+        for (piece, steps) in placements(&game, PieceKind::T) {
+            assert_eq!(minimal_inputs(&game, PieceKind::T, &piece.cells()), Some(steps.len() as u32));
+        }
+    }
+
+    #[test]
+    fn extra_inputs_are_faults() {
+        let mut finesse = Finesse::default();
+        finesse.count_input();
+        finesse.count_input();
+        assert!(finesse.record(PieceKind::T, Some(1)));
+        assert_eq!(finesse.total_placements(), 0);
+
+        finesse.enabled = true;
+        assert!(finesse.record(PieceKind::T, Some(1)));
+        assert_eq!(finesse.total_faults(), 1);
+        assert_eq!(finesse.total_placements(), 1);
+        assert!(finesse.last_fault.is_some_and(|fault| fault.used == 2 && fault.optimal == 1));
+        // Unreachable placements aren't judged.
+        assert!(finesse.record(PieceKind::T, None));
+        assert_eq!(finesse.total_placements(), 1);
+
+        finesse.strict = true;
+        assert!(!finesse.record(PieceKind::I, Some(0)));
+        assert_eq!(finesse.total_faults(), 2);
+        assert_eq!(finesse.total_placements(), 1);
+        finesse.reset_inputs();
+        assert!(finesse.record(PieceKind::I, Some(0)));
+        assert_eq!(finesse.placements[PieceKind::I as usize], 1);
+    }
+}
@@ -0,0 +1,608 @@
+use crate::i18n::{tr, tr_args};
+use crate::{BlockType, PieceKind, BOARD_HEIGHT, BOARD_WIDTH};
+
+// Fumen v115 as used by the community editors: a 23-row field plus one
+// garbage row, stored as field diffs and piece actions in a base64 alphabet.
+const ENCODE_TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const PREFIX: &str = "v115@";
+const FIELD_WIDTH: usize = 10;
+const FIELD_TOP: usize = 23;
+const FIELD_BLOCKS: usize = (FIELD_TOP + 1) * FIELD_WIDTH;
+const PLAY_BLOCKS: usize = FIELD_TOP * FIELD_WIDTH;
+const GRAY: u8 = 8;
+
+type Field = [u8; FIELD_BLOCKS];
+
+#[derive(Clone)]
+pub struct Placement {
+    pub kind: PieceKind,
+    pub cells: Vec<(i32, i32)>,
+}
+
+#[derive(Clone)]
+pub struct Page {
+    pub board: Vec<Vec<BlockType>>,
+    pub piece: Option<Placement>,
+    pub comment: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+struct Action {
+    piece: u8,
+    rotation: u8,
+    x: i32,
+    y: i32,
+    rise: bool,
+    mirror: bool,
+    colorize: bool,
+    comment: bool,
+    lock: bool,
+}
+
+pub fn decode(text: &str) -> Result<Vec<Page>, String> {
+    let start = text.find(PREFIX).ok_or_else(|| tr("fumen-not-v115"))? + PREFIX.len();
+    let mut values = Vec::new();
+    for c in text[start..].trim().bytes() {
+        if c == b'?' {
+            continue;
+        }
+        match ENCODE_TABLE.iter().position(|&e| e == c) {
+            Some(value) => values.push(value as u32),
+            None => return Err(tr_args("fumen-invalid-character", &[("character", &(c as char))])),
+        }
+    }
+
+    let mut reader = Reader { values, position: 0 };
+    let mut pages = Vec::new();
+    let mut prev: Field = [0; FIELD_BLOCKS];
+    let mut repeat = 0;
+    let mut comment = None;
+
+    while !reader.is_end() {
+        let mut field = prev;
+        if repeat == 0 {
+            let mut index = 0;
+            while index < FIELD_BLOCKS {
+                let value = reader.poll(2)? as usize;
+                let diff = value / FIELD_BLOCKS;
+                let count = value % FIELD_BLOCKS + 1;
+                if diff == 8 && count == FIELD_BLOCKS {
+                    repeat = reader.poll(1)? + 1;
+                }
+                if index + count > FIELD_BLOCKS {
+                    return Err(tr("fumen-overflow"));
+                }
+                for cell in &mut field[index..index + count] {
+                    *cell = (*cell as i32 + diff as i32 - 8).clamp(0, GRAY as i32) as u8;
+                }
+                index += count;
+            }
+        }
+
+        let action = decode_action(reader.poll(3)?)?;
+        if action.comment {
+            comment = Some(reader.read_comment()?);
+        }
+
+        let placement = (action.piece != 0).then(|| {
+            let cells = piece_cells(action.piece, action.rotation, action.x, action.y);
+            to_placement(action.piece, &cells)
+        });
+        pages.push(Page {
+            board: to_board(&field),
+            piece: placement,
+            comment: comment.clone(),
+        });
+
+        if action.lock {
+            if action.piece != 0 {
+                let cells = piece_cells(action.piece, action.rotation, action.x, action.y);
+                put_piece(&mut field, action.piece, &cells);
+            }
+            clear_lines(&mut field);
+            if action.rise {
+                rise(&mut field);
+            }
+            if action.mirror {
+                mirror(&mut field);
+            }
+        }
+        prev = field;
+        repeat = repeat.saturating_sub(1);
+    }
+
+    if pages.is_empty() {
+        return Err(tr("fumen-no-pages"));
+    }
+    Ok(pages)
+}
+
+pub fn encode(pages: &[Page]) -> String {
+    let mut values: Vec<u32> = Vec::new();
+    let mut prev: Field = [0; FIELD_BLOCKS];
+    let mut repeat_index: Option<usize> = None;
+
+    for (page_index, page) in pages.iter().enumerate() {
+        let mut field = from_board(&page.board);
+
+        let (changed, field_values) = encode_field(&prev, &field);
+        if changed {
+            values.extend(field_values);
+            repeat_index = None;
+        } else {
+            match repeat_index {
+                Some(index) if values[index] < 63 => values[index] += 1,
+                _ => {
+                    values.extend(field_values);
+                    values.push(0);
+                    repeat_index = Some(values.len() - 1);
+                }
+            }
+        }
+
+        let mut action = Action {
+            piece: 0,
+            rotation: 0,
+            x: 0,
+            y: FIELD_TOP as i32 - 1,
+            rise: false,
+            mirror: false,
+            colorize: page_index == 0,
+            comment: false,
+            lock: true,
+        };
+        if let Some(placement) = &page.piece {
+            if let Some((rotation, x, y)) = find_orientation(placement) {
+                action.piece = piece_value(placement.kind);
+                action.rotation = rotation;
+                action.x = x;
+                action.y = y;
+            }
+        }
+        push_value(&mut values, encode_action(&action), 3);
+
+        if action.piece != 0 {
+            let cells = piece_cells(action.piece, action.rotation, action.x, action.y);
+            put_piece(&mut field, action.piece, &cells);
+        }
+        clear_lines(&mut field);
+        prev = field;
+    }
+
+    let data: String = values.iter().map(|&v| ENCODE_TABLE[v as usize] as char).collect();
+    // Break the data with '?' the same way the web editors do.
+    let mut output = String::from(PREFIX);
+    for (i, c) in data.chars().enumerate() {
+        if i >= 42 && (i - 42) % 47 == 0 {
+            output.push('?');
+        }
+        output.push(c);
+    }
+    output
+}
+
+struct Reader {
+    values: Vec<u32>,
+    position: usize,
+}
+
+impl Reader {
+    fn is_end(&self) -> bool {
+        self.position >= self.values.len()
+    }
+
+    fn poll(&mut self, count: usize) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let digit = *self.values.get(self.position).ok_or_else(|| tr("fumen-truncated"))?;
+            value += digit * 64u32.pow(i as u32);
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn read_comment(&mut self) -> Result<String, String> {
+        let length = self.poll(2)? as usize;
+        let mut escaped = String::with_capacity(length);
+        for _ in 0..length.div_ceil(4) {
+            let mut value = self.poll(5)?;
+            for _ in 0..4 {
+                let index = (value % 96) as usize;
+                value /= 96;
+                if let Some(&c) = COMMENT_TABLE.get(index) {
+                    escaped.push(c as char);
+                }
+            }
+        }
+        escaped.truncate(length);
+        Ok(unescape(&escaped))
+    }
+}
+
+fn push_value(values: &mut Vec<u32>, mut value: u32, count: usize) {
+    for _ in 0..count {
+        values.push(value % 64);
+        value /= 64;
+    }
+}
+
+fn encode_field(prev: &Field, current: &Field) -> (bool, Vec<u32>) {
+    let mut values = Vec::new();
+    let mut changed = false;
+    let diff_at = |i: usize| current[i] as u32 + 8 - prev[i] as u32;
+
+    let mut run_diff = diff_at(0);
+    let mut run_length = 1;
+    for i in 1..FIELD_BLOCKS {
+        let diff = diff_at(i);
+        if diff != run_diff {
+            push_value(&mut values, run_diff * FIELD_BLOCKS as u32 + run_length - 1, 2);
+            changed |= run_diff != 8;
+            run_diff = diff;
+            run_length = 0;
+        }
+        run_length += 1;
+    }
+    push_value(&mut values, run_diff * FIELD_BLOCKS as u32 + run_length - 1, 2);
+    changed |= run_diff != 8;
+    (changed, values)
+}
+
+fn decode_action(mut value: u32) -> Result<Action, String> {
+    let piece = (value % 8) as u8;
+    value /= 8;
+    let rotation = (value % 4) as u8;
+    value /= 4;
+    let location = (value % FIELD_BLOCKS as u32) as i32;
+    value /= FIELD_BLOCKS as u32;
+    let mut flags = [false; 5];
+    for flag in &mut flags {
+        *flag = value % 2 == 1;
+        value /= 2;
+    }
+    if piece > GRAY {
+        return Err(tr("fumen-invalid-piece"));
+    }
+
+    let (x, y) = from_fumen_position(
+        piece,
+        rotation,
+        location % FIELD_WIDTH as i32,
+        FIELD_TOP as i32 - location / FIELD_WIDTH as i32 - 1,
+    );
+    Ok(Action {
+        piece,
+        rotation,
+        x,
+        y,
+        rise: flags[0],
+        mirror: flags[1],
+        colorize: flags[2],
+        comment: flags[3],
+        lock: !flags[4],
+    })
+}
+
+fn encode_action(action: &Action) -> u32 {
+    let (x, y) = if action.piece == 0 {
+        (action.x, action.y)
+    } else {
+        to_fumen_position(action.piece, action.rotation, action.x, action.y)
+    };
+    let location = (FIELD_TOP as i32 - y - 1) * FIELD_WIDTH as i32 + x;
+
+    let mut value = !action.lock as u32;
+    for flag in [action.comment, action.colorize, action.mirror, action.rise] {
+        value = value * 2 + flag as u32;
+    }
+    value = value * FIELD_BLOCKS as u32 + location as u32;
+    value = value * 4 + action.rotation as u32;
+    value * 8 + action.piece as u32
+}
+
+// The original editor anchors some pieces differently from the SRS-style
+// rotation centers used by `piece_cells`.
+fn position_offset(piece: u8, rotation: u8) -> (i32, i32) {
+    match (piece, rotation) {
+        (3, 3) => (1, -1),
+        (3, 0) => (1, 0),
+        (3, 2) => (0, -1),
+        (1, 0) => (1, 0),
+        (1, 3) => (0, -1),
+        (7, 2) => (0, -1),
+        (7, 1) => (-1, 0),
+        (4, 2) => (0, -1),
+        (4, 3) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+fn from_fumen_position(piece: u8, rotation: u8, x: i32, y: i32) -> (i32, i32) {
+    let (dx, dy) = position_offset(piece, rotation);
+    (x + dx, y + dy)
+}
+
+fn to_fumen_position(piece: u8, rotation: u8, x: i32, y: i32) -> (i32, i32) {
+    let (dx, dy) = position_offset(piece, rotation);
+    (x - dx, y - dy)
+}
+
+fn piece_value(kind: PieceKind) -> u8 {
+    match kind {
+        PieceKind::I => 1,
+        PieceKind::L => 2,
+        PieceKind::O => 3,
+        PieceKind::Z => 4,
+        PieceKind::T => 5,
+        PieceKind::J => 6,
+        PieceKind::S => 7,
+    }
+}
+
+fn piece_kind(value: u8) -> Option<PieceKind> {
+    match value {
+        1 => Some(PieceKind::I),
+        2 => Some(PieceKind::L),
+        3 => Some(PieceKind::O),
+        4 => Some(PieceKind::Z),
+        5 => Some(PieceKind::T),
+        6 => Some(PieceKind::J),
+        7 => Some(PieceKind::S),
+        _ => None,
+    }
+}
+
+// Cells around the rotation center with y pointing up. Rotation 0 is
+// upside down, 1 right, 2 spawn and 3 left, matching the fumen encoding.
+fn piece_cells(piece: u8, rotation: u8, x: i32, y: i32) -> Vec<(i32, i32)> {
+    let spawn: [(i32, i32); 4] = match piece {
+        1 => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        2 => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        3 => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        4 => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+        5 => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        6 => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        _ => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+    };
+    spawn
+        .iter()
+        .map(|&(dx, dy)| match rotation {
+            0 => (-dx, -dy),
+            1 => (dy, -dx),
+            3 => (-dy, dx),
+            _ => (dx, dy),
+        })
+        .map(|(dx, dy)| (x + dx, y + dy))
+        .collect()
+}
+
+fn find_orientation(placement: &Placement) -> Option<(u8, i32, i32)> {
+    let piece = piece_value(placement.kind);
+    let mut target: Vec<(i32, i32)> = placement
+        .cells
+        .iter()
+        .map(|&(x, row)| (x, BOARD_HEIGHT as i32 - 1 - row))
+        .collect();
+    target.sort_unstable();
+
+    for rotation in [2, 1, 0, 3] {
+        for &(x, y) in &target {
+            let mut cells = piece_cells(piece, rotation, x, y);
+            cells.sort_unstable();
+            if cells == target {
+                return Some((rotation, x, y));
+            }
+        }
+    }
+    None
+}
+
+fn to_placement(piece: u8, cells: &[(i32, i32)]) -> Placement {
+    Placement {
+        kind: piece_kind(piece).unwrap_or(PieceKind::I),
+        cells: cells
+            .iter()
+            .map(|&(x, y)| (x, BOARD_HEIGHT as i32 - 1 - y))
+            .collect(),
+    }
+}
+
+fn field_index(x: i32, y: i32) -> Option<usize> {
+    if x < 0 || x >= FIELD_WIDTH as i32 || y < 0 || y >= FIELD_TOP as i32 {
+        return None;
+    }
+    Some((FIELD_TOP - 1 - y as usize) * FIELD_WIDTH + x as usize)
+}
+
+fn put_piece(field: &mut Field, piece: u8, cells: &[(i32, i32)]) {
+    for &(x, y) in cells {
+        if let Some(index) = field_index(x, y) {
+            field[index] = piece;
+        }
+    }
+}
+
+fn clear_lines(field: &mut Field) {
+    let mut rows: Vec<[u8; FIELD_WIDTH]> = field[..PLAY_BLOCKS]
+        .chunks(FIELD_WIDTH)
+        .map(|row| row.try_into().unwrap())
+        .filter(|row: &[u8; FIELD_WIDTH]| row.contains(&0))
+        .collect();
+    while rows.len() < FIELD_TOP {
+        rows.insert(0, [0; FIELD_WIDTH]);
+    }
+    for (i, row) in rows.iter().enumerate() {
+        field[i * FIELD_WIDTH..(i + 1) * FIELD_WIDTH].copy_from_slice(row);
+    }
+}
+
+fn rise(field: &mut Field) {
+    field.copy_within(FIELD_WIDTH.., 0);
+    field[PLAY_BLOCKS..].fill(0);
+}
+
+fn mirror(field: &mut Field) {
+    for row in field[..PLAY_BLOCKS].chunks_mut(FIELD_WIDTH) {
+        row.reverse();
+    }
+}
+
+fn to_board(field: &Field) -> Vec<Vec<BlockType>> {
+    let top = FIELD_TOP - BOARD_HEIGHT;
+    (top..FIELD_TOP)
+        .map(|row| {
+            field[row * FIELD_WIDTH..(row + 1) * FIELD_WIDTH]
+                .iter()
+                .map(|&cell| match piece_kind(cell) {
+                    Some(kind) => BlockType::Filled(kind),
+                    None if cell == 0 => BlockType::Empty,
+                    None => BlockType::Garbage,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn from_board(board: &[Vec<BlockType>]) -> Field {
+    let mut field = [0; FIELD_BLOCKS];
+    // Boards may include hidden rows, which land above the visible field.
+    let top = FIELD_TOP.saturating_sub(board.len());
+    for (row, cells) in board.iter().enumerate() {
+        for (x, cell) in cells.iter().enumerate().take(BOARD_WIDTH) {
+            field[(top + row) * FIELD_WIDTH + x] = match *cell {
+                BlockType::Empty => 0,
+                BlockType::Filled(kind) => piece_value(kind),
+                BlockType::Garbage => GRAY,
+            };
+        }
+    }
+    field
+}
+
+fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+        let wide = chars.peek() == Some(&'u');
+        if wide {
+            chars.next();
+        }
+        let digits: String = chars.by_ref().take(if wide { 4 } else { 2 }).collect();
+        match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+            Some(decoded) => output.push(decoded),
+            None => {
+                output.push_str(if wide { "%u" } else { "%" });
+                output.push_str(&digits);
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_board() -> Vec<Vec<BlockType>> {
+        vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT]
+    }
+
+    // A garbage row with a hole on the left and a T flat on top of it.
+    fn garbage_page() -> Page {
+        let mut board = empty_board();
+        for cell in &mut board[BOARD_HEIGHT - 1][1..] {
+            *cell = BlockType::Garbage;
+        }
+        let bottom = BOARD_HEIGHT as i32 - 1;
+        Page {
+            board,
+            piece: Some(Placement {
+                kind: PieceKind::T,
+                cells: vec![(3, bottom - 1), (4, bottom - 1), (5, bottom - 1), (4, bottom - 2)],
+            }),
+            comment: None,
+        }
+    }
+
+    fn sorted_cells(placement: &Placement) -> Vec<(i32, i32)> {
+        let mut cells = placement.cells.clone();
+        cells.sort_unstable();
+        cells
+    }
+
+    fn assert_same(a: &Page, b: &Page) {
+        assert!(a.board == b.board, "boards differ");
+        match (&a.piece, &b.piece) {
+            (None, None) => {}
+            (Some(a), Some(b)) => {
+                assert_eq!(a.kind, b.kind);
+                assert_eq!(sorted_cells(a), sorted_cells(b));
+            }
+            _ => panic!("only one page has a piece"),
+        }
+        assert_eq!(a.comment, b.comment);
+    }
+
+    #[test]
+    fn empty_field() {
+        let pages = decode("v115@vhAAgH").unwrap();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].board == empty_board());
+        assert!(pages[0].piece.is_none());
+        assert_eq!(encode(&pages), "v115@vhAAgH");
+    }
+
+    #[test]
+    fn garbage_and_piece() {
+        let page = garbage_page();
+        assert_eq!(encode(std::slice::from_ref(&page)), "v115@chI8JeVLJ");
+        let pages = decode("v115@chI8JeVLJ").unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_same(&pages[0], &page);
+    }
+
+    #[test]
+    fn round_trip() {
+        let first = garbage_page();
+        // The T locks in the second page's field, so that page starts
+        // from the first one's result.
+        let mut second = Page {
+            board: first.board.clone(),
+            piece: None,
+            comment: None,
+        };
+        for &(x, row) in &first.piece.as_ref().unwrap().cells {
+            second.board[row as usize][x as usize] = BlockType::Filled(PieceKind::T);
+        }
+        let third = Page {
+            board: second.board.clone(),
+            piece: Some(Placement {
+                kind: PieceKind::I,
+                cells: (0..4).map(|row| (0, BOARD_HEIGHT as i32 - 4 + row)).collect(),
+            }),
+            comment: None,
+        };
+        let pages = decode(&encode(&[first, second, third])).unwrap();
+        let again = decode(&encode(&pages)).unwrap();
+        assert_eq!(pages.len(), 3);
+        assert_eq!(again.len(), 3);
+        for (a, b) in pages.iter().zip(&again) {
+            assert_same(a, b);
+        }
+        assert_eq!(encode(&pages), encode(&again));
+    }
+
+    #[test]
+    fn rejects_bad_data() {
+        assert!(decode("v115@").is_err());
+        assert!(decode("v115@vh").is_err());
+        assert!(decode("v115@vh!AgH").is_err());
+        assert!(decode("nothing here").is_err());
+    }
+}
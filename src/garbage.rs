@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// How long garbage waits before it can rise, and how long attack charges
+// before it is sent.
+pub const GARBAGE_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub struct Batch {
+    pub lines: u32,
+    pub remaining: Duration,
+}
+
+// Garbage traffic for versus play. Attack first cancels pending incoming
+// lines; whatever is left charges for a moment and is then sent.
+#[derive(Clone, Default)]
+pub struct GarbageQueue {
+    pub incoming: VecDeque<Batch>,
+    pub outgoing: VecDeque<Batch>,
+    sent: u32,
+    total_sent: u32,
+}
+
+impl GarbageQueue {
+    pub fn receive(&mut self, lines: u32) {
+        if lines > 0 {
+            self.incoming.push_back(Batch {
+                lines,
+                remaining: GARBAGE_DELAY,
+            });
+        }
+    }
+
+    pub fn attack(&mut self, mut lines: u32) {
+        while lines > 0 {
+            let Some(batch) = self.incoming.front_mut() else {
+                break;
+            };
+            let cancelled = batch.lines.min(lines);
+            batch.lines -= cancelled;
+            lines -= cancelled;
+            if batch.lines == 0 {
+                self.incoming.pop_front();
+            }
+        }
+        if lines > 0 {
+            self.outgoing.push_back(Batch {
+                lines,
+                remaining: GARBAGE_DELAY,
+            });
+        }
+    }
+
+    pub fn tick(&mut self, dt: Duration) {
+        for batch in self.incoming.iter_mut().chain(&mut self.outgoing) {
+            batch.remaining = batch.remaining.saturating_sub(dt);
+        }
+        while self.outgoing.front().is_some_and(|batch| batch.remaining.is_zero()) {
+            let lines = self.outgoing.pop_front().unwrap().lines;
+            self.sent += lines;
+            self.total_sent += lines;
+        }
+    }
+
+    // Incoming batches whose delay has run out, removed from the queue.
+    pub fn take_due(&mut self) -> Vec<u32> {
+        let mut due = Vec::new();
+        while self.incoming.front().is_some_and(|batch| batch.remaining.is_zero()) {
+            due.push(self.incoming.pop_front().unwrap().lines);
+        }
+        due
+    }
+
+    // Lines sent since the last call, for the opponent to receive.
+    pub fn take_sent(&mut self) -> u32 {
+        std::mem::take(&mut self.sent)
+    }
+
+    // Every line sent this game, whether or not it has been taken.
+    pub fn total_sent(&self) -> u32 {
+        self.total_sent
+    }
+
+    pub fn charging(&self) -> u32 {
+        self.outgoing.iter().map(|batch| batch.lines).sum()
+    }
+}
@@ -0,0 +1,46 @@
+use eframe::egui;
+use tetorisu::net::{Handicap, MAX_GARBAGE_ROWS, MAX_PREVIEW};
+
+use crate::i18n::tr;
+
+// True once a change is finished, so a drag sends one update rather than
+// one a frame.
+pub fn ui(ui: &mut egui::Ui, handicap: &mut Handicap) -> bool {
+    egui::Grid::new("handicap")
+        .num_columns(2)
+        .show(ui, |ui| {
+            let mut row = |label, slider| {
+                ui.label(tr(label));
+                let response = ui.add(slider);
+                ui.end_row();
+                response.drag_released() || (response.changed() && !response.dragged())
+            };
+            let rows = [
+                row("lobby-handicap-garbage", egui::Slider::new(&mut handicap.garbage_rows, 0..=MAX_GARBAGE_ROWS)),
+                row("lobby-handicap-gravity", egui::Slider::new(&mut handicap.gravity, 0.25..=4.0).suffix("x")),
+                row("lobby-handicap-attack", egui::Slider::new(&mut handicap.attack, 0.0..=2.0).suffix("x")),
+                row("lobby-handicap-preview", egui::Slider::new(&mut handicap.preview, 0..=MAX_PREVIEW)),
+            ];
+            rows.contains(&true)
+        })
+        .inner
+}
+
+// Only what differs from no handicap at all.
+pub fn summary(handicap: &Handicap) -> String {
+    let none = Handicap::default();
+    let mut parts = Vec::new();
+    if handicap.garbage_rows != none.garbage_rows {
+        parts.push(format!("{} {}", tr("lobby-handicap-garbage"), handicap.garbage_rows));
+    }
+    if handicap.gravity != none.gravity {
+        parts.push(format!("{} {:.2}x", tr("lobby-handicap-gravity"), handicap.gravity));
+    }
+    if handicap.attack != none.attack {
+        parts.push(format!("{} {:.2}x", tr("lobby-handicap-attack"), handicap.attack));
+    }
+    if handicap.preview != none.preview {
+        parts.push(format!("{} {}", tr("lobby-handicap-preview"), handicap.preview));
+    }
+    parts.join(", ")
+}
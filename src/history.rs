@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub mode: String,
+    pub score: u32,
+    pub lines: u32,
+    pub duration: f32,
+    pub pps: f32,
+    pub finesse_faults: u32,
+    pub date: String,
+    // The speed curve played with, if any; older records have none.
+    #[serde(default)]
+    pub curve: Option<String>,
+    // Fingerprint of the game's placements, as submitted to the leaderboard.
+    #[serde(default)]
+    pub replay_hash: Option<String>,
+}
+
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("tetris")
+}
+
+fn history_path() -> PathBuf {
+    data_dir().join("history.jsonl")
+}
+
+pub fn append(record: &GameRecord) -> io::Result<()> {
+    let path = history_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)
+}
+
+// Lines that fail to parse are skipped so one bad write can't hide the rest.
+pub fn load() -> Vec<GameRecord> {
+    let Ok(file) = fs::File::open(history_path()) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
+pub fn export_json(records: &[GameRecord], path: &Path) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(records)?)
+}
+
+pub fn export_csv(records: &[GameRecord], path: &Path) -> io::Result<()> {
+    let mut csv = String::from("date,mode,score,lines,duration,pps,finesse_faults\n");
+    for record in records {
+        csv.push_str(&format!(
+            "{},{},{},{},{:.2},{:.2},{}\n",
+            csv_field(&record.date),
+            csv_field(&record.mode),
+            record.score,
+            record.lines,
+            record.duration,
+            record.pps,
+            record.finesse_faults,
+        ));
+    }
+    fs::write(path, csv)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// UTC "YYYY-MM-DD HH:MM:SS" without pulling in a date crate.
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
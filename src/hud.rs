@@ -0,0 +1,151 @@
+use eframe::egui;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use crate::{TetrisGame, Tetromino};
+
+const FLASH_SECONDS: f64 = 0.3;
+const FADE_SECONDS: f64 = 0.8;
+// Roughly how long the drawn piece takes to catch up with a move or turn.
+const EASE_SECONDS: f32 = 0.05;
+
+// A streak counter that swells and flashes when it goes up and fades out
+// once the streak breaks.
+#[derive(Default)]
+pub struct StreakCounter {
+    shown: i32,
+    current: i32,
+    bumped_at: f64,
+    broken_at: Option<f64>,
+}
+
+impl StreakCounter {
+    pub fn update(&mut self, value: i32, now: f64) {
+        if value == self.current {
+            return;
+        }
+        if value > 0 {
+            if value > self.current {
+                self.bumped_at = now;
+            }
+            self.shown = value;
+            self.broken_at = None;
+        } else if self.shown > 0 && self.broken_at.is_none() {
+            self.broken_at = Some(now);
+        }
+        self.current = value;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_animating(&self, now: f64) -> bool {
+        now - self.bumped_at < FLASH_SECONDS || self.broken_at.is_some_and(|at| now - at < FADE_SECONDS)
+    }
+
+    pub fn paint(&self, ui: &mut egui::Ui, label: &str, color: egui::Color32, now: f64, scale: f32) {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(120.0, 44.0) * scale, egui::Sense::hover());
+        let alpha = match self.broken_at {
+            Some(at) => (1.0 - (now - at) / FADE_SECONDS).max(0.0) as f32,
+            None if self.shown > 0 => 1.0,
+            None => 0.0,
+        };
+        if alpha <= 0.0 {
+            return;
+        }
+
+        let flash = (1.0 - (now - self.bumped_at) / FLASH_SECONDS).max(0.0) as f32;
+        let size = (18.0 + (self.shown.min(12) as f32) * 1.5 + flash * 8.0) * scale;
+        let color = lerp_color(color, egui::Color32::WHITE, flash).gamma_multiply(alpha);
+        let painter = ui.painter();
+        painter.text(
+            rect.left_top(),
+            egui::Align2::LEFT_TOP,
+            label,
+            egui::FontId::proportional(12.0 * scale),
+            egui::Color32::GRAY.gamma_multiply(alpha),
+        );
+        painter.text(
+            rect.left_top() + egui::vec2(0.0, 12.0 * scale),
+            egui::Align2::LEFT_TOP,
+            format!("x{}", self.shown),
+            egui::FontId::proportional(size),
+            color,
+        );
+    }
+}
+
+pub fn lerp_color(from: egui::Color32, to: egui::Color32, t: f32) -> egui::Color32 {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+    egui::Color32::from_rgb(mix(from.r(), to.r()), mix(from.g(), to.g()), mix(from.b(), to.b()))
+}
+
+// Where the falling piece is drawn when motion is smoothed. The drawn piece
+// trails the real one by an offset and an angle that shrink away over
+// EASE_SECONDS, so it glides between cells and swings through rotations.
+// Only drawing looks at this; the game itself still moves cell to cell.
+#[derive(Default)]
+pub struct PieceMotion {
+    // The piece as of the last frame, with the piece count and hold flag
+    // that tell a new piece from the same one moving.
+    last: Option<(Tetromino, u32, bool)>,
+    offset: egui::Vec2,
+    angle: f32,
+}
+
+impl PieceMotion {
+    pub fn update(&mut self, game: &TetrisGame, dt: f32) {
+        let piece = game.current_piece;
+        let same = |(last, pieces, hold_used): &(Tetromino, u32, bool)| {
+            (last.kind, *pieces, *hold_used) == (piece.kind, game.stats.pieces, game.hold_used)
+        };
+        match self.last.filter(same) {
+            Some((last, _, _)) => {
+                self.offset += centroid(&last) - centroid(&piece);
+                self.angle += match (piece.rotation as i32 - last.rotation as i32).rem_euclid(4) {
+                    1 => FRAC_PI_2,
+                    2 => PI,
+                    3 => -FRAC_PI_2,
+                    _ => 0.0,
+                };
+            }
+            _ => self.reset(),
+        }
+        self.last = Some((piece, game.stats.pieces, game.hold_used));
+        let keep = (-3.0 * dt / EASE_SECONDS).exp();
+        self.offset *= keep;
+        self.angle *= keep;
+        if self.offset.length() < 0.01 && self.angle.abs() < 0.01 {
+            self.offset = egui::Vec2::ZERO;
+            self.angle = 0.0;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.offset != egui::Vec2::ZERO || self.angle != 0.0
+    }
+
+    // The piece's cells where they are drawn, in cells from the top left of
+    // the hidden rows. A rigid turn about the centre keeps the shape, so the
+    // drawn piece starts out exactly as it was before the move.
+    pub fn cells(&self, piece: &Tetromino) -> [egui::Pos2; 4] {
+        let center = centroid(piece);
+        let (sin, cos) = (-self.angle).sin_cos();
+        piece.cells().map(|(x, y)| {
+            let from_center = egui::vec2(x as f32, y as f32) - center;
+            let turned = egui::vec2(
+                from_center.x * cos - from_center.y * sin,
+                from_center.x * sin + from_center.y * cos,
+            );
+            (center + turned + self.offset).to_pos2()
+        })
+    }
+}
+
+fn centroid(piece: &Tetromino) -> egui::Vec2 {
+    piece.cells().iter().fold(egui::Vec2::ZERO, |sum, &(x, y)| sum + egui::vec2(x as f32, y as f32)) / 4.0
+}
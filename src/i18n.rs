@@ -0,0 +1,153 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+use crate::history;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Language {
+    English,
+    Japanese,
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::English as u8);
+static CJK_FONT_LOADED: AtomicBool = AtomicBool::new(false);
+
+// Fonts that can show Japanese, tried in order after anything the player has
+// put in the fonts folder. The built-in egui fonts have no kana or kanji.
+const CJK_FONTS: [&str; 12] = [
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/noto/NotoSansJP-Regular.ttf",
+    "/usr/share/fonts/opentype/ipafont-gothic/ipagp.ttf",
+    "/usr/share/fonts/truetype/takao-gothic/TakaoPGothic.ttf",
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+    r"C:\Windows\Fonts\YuGothM.ttc",
+    r"C:\Windows\Fonts\meiryo.ttc",
+    r"C:\Windows\Fonts\msgothic.ttc",
+];
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Japanese];
+
+    pub fn name(self) -> String {
+        tr(match self {
+            Language::English => "language-english",
+            Language::Japanese => "language-japanese",
+        })
+    }
+
+    pub fn system() -> Language {
+        static SYSTEM: OnceLock<Language> = OnceLock::new();
+        *SYSTEM.get_or_init(|| match sys_locale::get_locale() {
+            Some(locale) if locale.to_lowercase().starts_with("ja") => Language::Japanese,
+            _ => Language::English,
+        })
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Language::English => include_str!("../locales/en.ftl"),
+            Language::Japanese => include_str!("../locales/ja.ftl"),
+        }
+    }
+
+    fn messages(self) -> &'static HashMap<&'static str, &'static str> {
+        static BUNDLES: OnceLock<Vec<HashMap<&'static str, &'static str>>> = OnceLock::new();
+        let bundles = BUNDLES.get_or_init(|| Language::ALL.iter().map(|language| parse(language.source())).collect());
+        &bundles[self as usize]
+    }
+}
+
+// Messages use a small part of Fluent's syntax: one "id = text" per line,
+// "#" comments, and "{ $name }" for arguments.
+fn parse(source: &'static str) -> HashMap<&'static str, &'static str> {
+    source
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(id, text)| (id.trim(), text.trim()))
+        .collect()
+}
+
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    Language::ALL[LANGUAGE.load(Ordering::Relaxed) as usize]
+}
+
+// Looks a message up in the current language, falling back to English and
+// then to the id itself so a missing translation still shows something.
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+pub fn tr_args(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let text = language()
+        .messages()
+        .get(id)
+        .or_else(|| Language::English.messages().get(id))
+        .copied()
+        .unwrap_or(id);
+    let mut message = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        message.push_str(&rest[..start]);
+        let name = rest[start + 1..start + end].trim().trim_start_matches('$');
+        match args.iter().find(|(arg, _)| *arg == name) {
+            Some((_, value)) => message.push_str(&value.to_string()),
+            None => message.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    message.push_str(rest);
+    message
+}
+
+fn fonts_dir() -> PathBuf {
+    history::data_dir().join("fonts")
+}
+
+// Adds a font with Japanese glyphs behind the default ones the first time
+// Japanese is shown. Fonts dropped into the data folder's fonts directory
+// win over the system ones.
+pub fn load_fonts(ctx: &egui::Context) {
+    if language() != Language::Japanese || CJK_FONT_LOADED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let mut candidates: Vec<PathBuf> = fs::read_dir(fonts_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+            ["ttf", "otf", "ttc"].iter().any(|font| extension.eq_ignore_ascii_case(font))
+        })
+        .collect();
+    candidates.sort();
+    candidates.extend(CJK_FONTS.iter().map(PathBuf::from));
+    let Some(data) = candidates.iter().find_map(|path| fs::read(path).ok()) else {
+        eprintln!("No Japanese font found; put a .ttf or .otf file in {}", fonts_dir().display());
+        return;
+    };
+    let mut fonts = egui::FontDefinitions::default();
+    fonts.font_data.insert("cjk".to_string(), egui::FontData::from_owned(data));
+    for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+        fonts.families.entry(family).or_default().push("cjk".to_string());
+    }
+    ctx.set_fonts(fonts);
+}
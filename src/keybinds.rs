@@ -0,0 +1,341 @@
+use eframe::egui::{self, Key};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::i18n::tr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Hold,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateCw,
+        Action::RotateCcw,
+        Action::Rotate180,
+        Action::Hold,
+    ];
+
+    pub fn name(self) -> String {
+        tr(match self {
+            Action::MoveLeft => "action-move-left",
+            Action::MoveRight => "action-move-right",
+            Action::SoftDrop => "action-soft-drop",
+            Action::HardDrop => "action-hard-drop",
+            Action::RotateCw => "action-rotate-cw",
+            Action::RotateCcw => "action-rotate-ccw",
+            Action::Rotate180 => "action-rotate-180",
+            Action::Hold => "hold",
+        })
+    }
+}
+
+// Keys are stored in the config by their variant name, e.g. "ArrowLeft".
+mod key_name {
+    use eframe::egui::Key;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub const KEYS: [Key; 65] = [
+        Key::ArrowDown, Key::ArrowLeft, Key::ArrowRight, Key::ArrowUp,
+        Key::Escape, Key::Tab, Key::Backspace, Key::Enter, Key::Space,
+        Key::Insert, Key::Delete, Key::Home, Key::End, Key::PageUp, Key::PageDown,
+        Key::Minus, Key::PlusEquals,
+        Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4,
+        Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9,
+        Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+        Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R,
+        Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+        Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6,
+        Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+    ];
+
+    pub fn serialize<S: Serializer>(key: &Key, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:?}", key))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Key, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        KEYS.iter()
+            .copied()
+            .find(|key| format!("{:?}", key) == name)
+            .ok_or_else(|| de::Error::custom(format!("unknown key \"{}\"", name)))
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keybinds {
+    #[serde(with = "key_name")]
+    pub move_left: Key,
+    #[serde(with = "key_name")]
+    pub move_right: Key,
+    #[serde(with = "key_name")]
+    pub soft_drop: Key,
+    #[serde(with = "key_name")]
+    pub hard_drop: Key,
+    #[serde(with = "key_name")]
+    pub rotate_cw: Key,
+    #[serde(with = "key_name")]
+    pub rotate_ccw: Key,
+    #[serde(with = "key_name")]
+    pub rotate_180: Key,
+    #[serde(with = "key_name")]
+    pub hold: Key,
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Preset::Guideline.keybinds()
+    }
+}
+
+// Ready-made layouts. Picking one overwrites every binding; rebinding a key
+// afterwards turns the set into a custom one.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    // Arrows under the right hand, rotation and hold under the left.
+    Guideline,
+    // Movement on WASD, rotation on J K L.
+    Wasd,
+    // Guideline mirrored: movement on E S D F, rotation and hold on the
+    // right of the bottom row.
+    LeftHanded,
+    // Everything within reach of the left hand.
+    OneHanded,
+    // H J K L in place of the arrows.
+    Vi,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [Preset::Guideline, Preset::Wasd, Preset::LeftHanded, Preset::OneHanded, Preset::Vi];
+
+    pub fn name(self) -> String {
+        tr(match self {
+            Preset::Guideline => "preset-guideline",
+            Preset::Wasd => "preset-wasd",
+            Preset::LeftHanded => "preset-left-handed",
+            Preset::OneHanded => "preset-one-handed",
+            Preset::Vi => "preset-vi",
+        })
+    }
+
+    pub fn keybinds(self) -> Keybinds {
+        // Left, right, soft drop, hard drop, rotate right, rotate left, 180, hold.
+        let [move_left, move_right, soft_drop, hard_drop, rotate_cw, rotate_ccw, rotate_180, hold] = match self {
+            Preset::Guideline => [
+                Key::ArrowLeft, Key::ArrowRight, Key::ArrowDown, Key::Space,
+                Key::ArrowUp, Key::Z, Key::A, Key::C,
+            ],
+            Preset::Wasd => [Key::A, Key::D, Key::S, Key::W, Key::K, Key::J, Key::L, Key::Space],
+            Preset::LeftHanded => [Key::S, Key::F, Key::D, Key::Space, Key::E, Key::M, Key::L, Key::N],
+            Preset::OneHanded => [Key::A, Key::D, Key::S, Key::Space, Key::E, Key::Q, Key::W, Key::F],
+            Preset::Vi => [Key::H, Key::L, Key::J, Key::Space, Key::K, Key::Z, Key::A, Key::C],
+        };
+        Keybinds {
+            move_left,
+            move_right,
+            soft_drop,
+            hard_drop,
+            rotate_cw,
+            rotate_ccw,
+            rotate_180,
+            hold,
+        }
+    }
+}
+
+impl Keybinds {
+    fn slot(&mut self, action: Action) -> &mut Key {
+        match action {
+            Action::MoveLeft => &mut self.move_left,
+            Action::MoveRight => &mut self.move_right,
+            Action::SoftDrop => &mut self.soft_drop,
+            Action::HardDrop => &mut self.hard_drop,
+            Action::RotateCw => &mut self.rotate_cw,
+            Action::RotateCcw => &mut self.rotate_ccw,
+            Action::Rotate180 => &mut self.rotate_180,
+            Action::Hold => &mut self.hold,
+        }
+    }
+
+    pub fn key(&self, action: Action) -> Key {
+        match action {
+            Action::MoveLeft => self.move_left,
+            Action::MoveRight => self.move_right,
+            Action::SoftDrop => self.soft_drop,
+            Action::HardDrop => self.hard_drop,
+            Action::RotateCw => self.rotate_cw,
+            Action::RotateCcw => self.rotate_ccw,
+            Action::Rotate180 => self.rotate_180,
+            Action::Hold => self.hold,
+        }
+    }
+
+    // A key can only drive one action, so whatever held it before gets the
+    // old key of the action being rebound.
+    pub fn set(&mut self, action: Action, key: Key) {
+        let old = self.key(action);
+        for other in Action::ALL {
+            if self.key(other) == key {
+                *self.slot(other) = old;
+            }
+        }
+        *self.slot(action) = key;
+    }
+
+    pub fn preset(&self) -> Option<Preset> {
+        Preset::ALL.into_iter().find(|preset| preset.keybinds() == *self)
+    }
+
+    // Shows "Custom" once the bindings match none of the presets.
+    pub fn preset_picker(&mut self, ui: &mut egui::Ui) {
+        let current = self.preset();
+        egui::ComboBox::from_id_source("keybind preset")
+            .selected_text(current.map_or_else(|| tr("preset-custom"), Preset::name))
+            .show_ui(ui, |ui| {
+                for preset in Preset::ALL {
+                    if ui.selectable_label(current == Some(preset), preset.name()).clicked() {
+                        *self = preset.keybinds();
+                    }
+                }
+            });
+    }
+
+    // Lists every action with a button; clicking one waits for the next key
+    // press. Escape cancels.
+    pub fn ui(&mut self, ui: &mut egui::Ui, rebinding: &mut Option<Action>) {
+        if let Some(action) = *rebinding {
+            let key = ui.input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        repeat: false,
+                        ..
+                    } => Some(*key),
+                    _ => None,
+                })
+            });
+            // Only keys the config file can name are accepted.
+            if let Some(key) = key.filter(|key| key_name::KEYS.contains(key)) {
+                // Swallow the press so it doesn't also reach the game.
+                ui.input_mut(|i| i.events.retain(|event| !matches!(event, egui::Event::Key { key: k, .. } if *k == key)));
+                if key != Key::Escape {
+                    self.set(action, key);
+                }
+                *rebinding = None;
+            }
+        }
+
+        egui::Grid::new("keybinds").num_columns(2).show(ui, |ui| {
+            ui.label(tr("preset"));
+            self.preset_picker(ui);
+            ui.end_row();
+            for action in Action::ALL {
+                ui.label(action.name());
+                let text = if *rebinding == Some(action) {
+                    tr("keybinds-press-key")
+                } else {
+                    format!("{:?}", self.key(action))
+                };
+                if ui.button(text).clicked() {
+                    *rebinding = Some(action);
+                }
+                ui.end_row();
+            }
+        });
+    }
+}
+
+// Delayed auto shift: a held direction waits out the DAS, then repeats every
+// ARR. An ARR of zero goes straight to the wall.
+#[derive(Default)]
+pub struct Autoshift {
+    direction: i32,
+    held: Duration,
+    repeats: u32,
+}
+
+impl Autoshift {
+    pub fn press(&mut self, direction: i32) {
+        *self = Self {
+            direction,
+            ..Self::default()
+        };
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.direction != 0
+    }
+
+    // Letting go hands over to the other direction if that is still held.
+    pub fn release(&mut self, direction: i32, other_held: bool) {
+        if self.direction != direction {
+            return;
+        }
+        if other_held {
+            self.press(-direction);
+        } else {
+            self.direction = 0;
+        }
+    }
+
+    // Returns the direction and how many cells to shift this frame.
+    pub fn update(&mut self, dt: Duration, das: Duration, arr: Duration) -> (i32, u32) {
+        if self.direction == 0 {
+            return (0, 0);
+        }
+        self.held += dt;
+        if self.held < das {
+            return (self.direction, 0);
+        }
+        if arr.is_zero() {
+            return (self.direction, u32::MAX);
+        }
+        let due = ((self.held - das).as_nanos() / arr.as_nanos()) as u32 + 1;
+        // A longer ARR mid-hold can make fewer shifts due than already made.
+        let shift = due.saturating_sub(self.repeats);
+        self.repeats = self.repeats.max(due);
+        (self.direction, shift)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAS: Duration = Duration::from_millis(100);
+    const ARR: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn autoshift_waits_then_repeats() {
+        let mut shift = Autoshift::default();
+        shift.press(1);
+        assert_eq!(shift.update(Duration::from_millis(50), DAS, ARR), (1, 0));
+        assert_eq!(shift.update(Duration::from_millis(50), DAS, ARR), (1, 1));
+        assert_eq!(shift.update(Duration::from_millis(45), DAS, ARR), (1, 2));
+        assert_eq!(shift.update(Duration::ZERO, DAS, Duration::ZERO), (1, u32::MAX));
+    }
+
+    #[test]
+    fn longer_arr_mid_hold_does_not_underflow() {
+        let mut shift = Autoshift::default();
+        shift.press(-1);
+        assert_eq!(shift.update(Duration::from_millis(200), DAS, ARR), (-1, 6));
+        let slower = Duration::from_millis(100);
+        assert_eq!(shift.update(Duration::from_millis(10), DAS, slower), (-1, 0));
+        assert_eq!(shift.update(Duration::from_millis(500), DAS, slower), (-1, 1));
+    }
+}
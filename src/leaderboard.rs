@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::history::GameRecord;
+use crate::modes::{BTYPE_LINES, SPRINT_LINES};
+
+pub const TOP: usize = 100;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub score: u32,
+    pub lines: u32,
+    pub time_ms: u64,
+    #[serde(default)]
+    pub replay_hash: String,
+}
+
+#[derive(Serialize)]
+struct Submission<'a> {
+    mode: &'a str,
+    #[serde(flatten)]
+    entry: &'a Entry,
+}
+
+// Sprint ranks by time, everything else by score.
+pub fn rank(mode: &str, entries: &mut Vec<Entry>) {
+    if mode == "Sprint" {
+        entries.sort_by_key(|entry| entry.time_ms);
+    } else {
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+    }
+    entries.truncate(TOP);
+}
+
+// The offline table, built from this machine's history. Sprint and B-Type
+// games only count once their lines are done.
+pub fn local(mode: &str, name: &str, records: &[GameRecord]) -> Vec<Entry> {
+    let goal = match mode {
+        "Sprint" => SPRINT_LINES,
+        "B-Type" => BTYPE_LINES,
+        _ => 0,
+    };
+    let mut entries: Vec<Entry> = records
+        .iter()
+        .filter(|record| record.mode == mode && record.lines >= goal)
+        .map(|record| Entry {
+            name: name.to_string(),
+            score: record.score,
+            lines: record.lines,
+            time_ms: (record.duration * 1000.0) as u64,
+            replay_hash: record.replay_hash.clone().unwrap_or_default(),
+        })
+        .collect();
+    rank(mode, &mut entries);
+    entries
+}
+
+// Both calls run on a background thread and report back through the
+// returned channel, so a slow server never stalls a frame.
+pub fn submit(url: &str, mode: &str, entry: Entry) -> Receiver<Result<(), String>> {
+    let url = format!("{}/scores", url.trim_end_matches('/'));
+    let mode = mode.to_string();
+    spawn(move || {
+        agent()
+            .post(&url)
+            .send_json(Submission { mode: &mode, entry: &entry })
+            .map(|_| ())
+            .map_err(describe)
+    })
+}
+
+pub fn fetch(url: &str, mode: &str) -> Receiver<Result<Vec<Entry>, String>> {
+    let url = format!("{}/scores", url.trim_end_matches('/'));
+    let mode = mode.to_string();
+    spawn(move || {
+        let response = agent().get(&url).query("mode", &mode).call().map_err(describe)?;
+        let mut entries: Vec<Entry> = response.into_json().map_err(|error| error.to_string())?;
+        rank(&mode, &mut entries);
+        Ok(entries)
+    })
+}
+
+fn spawn<T: Send + 'static>(job: impl FnOnce() -> T + Send + 'static) -> Receiver<T> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(job());
+    });
+    receiver
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(TIMEOUT).build()
+}
+
+fn describe(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(code, _) => format!("Server answered {}", code),
+        ureq::Error::Transport(transport) => transport.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(mode: &str, score: u32, lines: u32, duration: f32, hash: Option<&str>) -> GameRecord {
+        GameRecord {
+            mode: mode.to_string(),
+            score,
+            lines,
+            duration,
+            pps: 0.0,
+            finesse_faults: 0,
+            date: String::new(),
+            curve: None,
+            replay_hash: hash.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn local_sprints_rank_by_time_with_replay_hashes() {
+        let records = [
+            record("Sprint", 0, SPRINT_LINES, 90.0, Some("00000000000000aa")),
+            record("Sprint", 0, SPRINT_LINES - 1, 10.0, Some("00000000000000bb")),
+            record("Sprint", 0, SPRINT_LINES, 60.0, None),
+            record("Ultra", 5000, 20, 120.0, Some("00000000000000cc")),
+        ];
+        let entries = local("Sprint", "me", &records);
+        let times: Vec<u64> = entries.iter().map(|entry| entry.time_ms).collect();
+        assert_eq!(times, [60_000, 90_000]);
+        assert_eq!(entries[0].replay_hash, "");
+        assert_eq!(entries[1].replay_hash, "00000000000000aa");
+    }
+}
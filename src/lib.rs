@@ -0,0 +1,2 @@
+// Shared between the game and the dedicated server.
+pub mod net;
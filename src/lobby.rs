@@ -0,0 +1,517 @@
+use eframe::egui;
+use std::collections::VecDeque;
+use std::io::BufReader;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use tetorisu::net::{
+    self, ClientMessage, Handicap, MatchResult, RoomInfo, RoomSettings, RoomState, ServerMessage, MAX_BEST_OF,
+    MAX_CHAT_CHARS,
+};
+
+use crate::handicap;
+use crate::i18n::{tr, tr_args};
+use crate::modes::{self, Mode};
+use crate::rollback::{self, Session, Spectator};
+use crate::tas::Input;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+// Older chat lines scroll away for good past this many.
+const CHAT_LINES: usize = 200;
+
+// Reading happens on a thread of its own, so messages wait in the channel
+// until the next frame picks them up.
+struct Connection {
+    writer: TcpStream,
+    messages: Receiver<ServerMessage>,
+}
+
+fn connect(address: String, name: String) -> Receiver<Result<Connection, String>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let connection = (|| {
+            let socket = address
+                .to_socket_addrs()
+                .map_err(|error| format!("{}: {}", address, error))?
+                .next()
+                .ok_or_else(|| format!("{}: no address", address))?;
+            let stream = TcpStream::connect_timeout(&socket, TIMEOUT).map_err(|error| format!("{}: {}", address, error))?;
+            let mut writer = stream.try_clone().map_err(|error| error.to_string())?;
+            net::send(&mut writer, &ClientMessage::Hello { name }).map_err(|error| error.to_string())?;
+            let (sender, messages) = mpsc::channel();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream);
+                while let Ok(Some(message)) = net::receive(&mut reader) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(Connection { writer, messages })
+        })();
+        let _ = sender.send(connection);
+    });
+    receiver
+}
+
+// What the game has to act on, handed over as the messages come in.
+pub enum LobbyEvent {
+    // The match's session is ready to play.
+    Start { mode: Mode },
+    // The server has called the game, whether or not this board is done.
+    Finished,
+}
+
+// Finding an opponent for online versus: rooms to browse or open, a seat in
+// one with its settings, handicaps and ready checks, and a chat. The room
+// shown is always the server's latest word on it.
+#[derive(Default)]
+pub struct Lobby {
+    connecting: Option<Receiver<Result<Connection, String>>>,
+    connection: Option<Connection>,
+    // Our id on the server, from Welcome.
+    id: u32,
+    rooms: Vec<RoomInfo>,
+    room: Option<RoomState>,
+    // Lines from players, and notices from the game with no name.
+    chat: VecDeque<(Option<String>, String)>,
+    draft: String,
+    room_name: String,
+    settings: RoomSettings,
+    handicap: Handicap,
+    status: Option<String>,
+    // Between the server starting a game and this board's result going in.
+    playing: bool,
+    // Both boards of the game on, from the server's start until it calls
+    // the game, and the lines of our attack reported to it so far.
+    session: Option<Session>,
+    opponent: String,
+    reported: u32,
+    // Set once the session finds the boards have gone apart, so it's only
+    // said once a game.
+    desynced: bool,
+    // The game on in a room we only watch.
+    spectator: Option<Spectator>,
+}
+
+impl Lobby {
+    pub fn connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    // Connected or about to be, so there may be messages to pick up.
+    pub fn active(&self) -> bool {
+        self.connecting.is_some() || self.connected()
+    }
+
+    // Until this board's result has gone in.
+    pub fn playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    pub fn session_mut(&mut self) -> Option<&mut Session> {
+        self.session.as_mut()
+    }
+
+    pub fn spectator(&self) -> Option<&Spectator> {
+        self.spectator.as_ref()
+    }
+
+    // In a room without a seat in it.
+    fn spectating(&self) -> bool {
+        self.room.as_ref().is_some_and(|room| room.members.iter().all(|member| member.id != self.id))
+    }
+
+    pub fn opponent(&self) -> &str {
+        &self.opponent
+    }
+
+    // Plays the next frame with our inputs for it, unless the opponent is
+    // too far behind. Returns whether it played.
+    pub fn advance(&mut self, inputs: &[Input]) -> bool {
+        let Some(session) = &mut self.session else {
+            return false;
+        };
+        let played = session.advance(inputs);
+        let mut messages = session.outgoing();
+        // The server only counts garbage toward checking results; the
+        // session passes it between the boards itself.
+        let sent = session.sent();
+        if sent > self.reported {
+            messages.push(ClientMessage::Garbage { lines: sent - self.reported });
+            self.reported = sent;
+        }
+        for message in messages {
+            self.send(message);
+        }
+        self.check_desync();
+        played
+    }
+
+    fn check_desync(&mut self) {
+        if let Some(frame) = self.session.as_ref().and_then(Session::desync).filter(|_| !self.desynced) {
+            self.desynced = true;
+            self.notice(tr_args("lobby-desync", &[("frame", &frame)]));
+        }
+    }
+
+    fn send(&mut self, message: ClientMessage) {
+        let Some(connection) = &mut self.connection else {
+            return;
+        };
+        if let Err(error) = net::send(&mut connection.writer, &message) {
+            self.disconnect();
+            self.status = Some(tr_args("lobby-lost", &[("error", &error)]));
+        }
+    }
+
+    fn disconnect(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            let _ = connection.writer.shutdown(std::net::Shutdown::Both);
+        }
+        self.rooms.clear();
+        self.room = None;
+        self.playing = false;
+        self.session = None;
+        self.spectator = None;
+    }
+
+    fn notice(&mut self, text: String) {
+        self.say(None, text);
+    }
+
+    fn say(&mut self, player: Option<String>, text: String) {
+        self.chat.push_back((player, text));
+        if self.chat.len() > CHAT_LINES {
+            self.chat.pop_front();
+        }
+    }
+
+    // Reports how this board's game ended, once.
+    pub fn finish(&mut self, result: MatchResult) {
+        if self.playing {
+            self.playing = false;
+            self.send(ClientMessage::Result(result));
+        }
+    }
+
+    pub fn poll(&mut self) -> Vec<LobbyEvent> {
+        if let Some(result) = self.connecting.as_ref().and_then(|receiver| receiver.try_recv().ok()) {
+            self.connecting = None;
+            match result {
+                Ok(connection) => {
+                    self.connection = Some(connection);
+                    self.status = None;
+                    self.send(ClientMessage::ListRooms);
+                }
+                Err(error) => self.status = Some(error),
+            }
+        }
+        let mut events = Vec::new();
+        while let Some(connection) = &self.connection {
+            let message = match connection.messages.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.playing {
+                        events.push(LobbyEvent::Finished);
+                    }
+                    self.disconnect();
+                    self.status = Some(tr("lobby-disconnected"));
+                    break;
+                }
+            };
+            self.handle(message, &mut events);
+        }
+        events
+    }
+
+    fn handle(&mut self, message: ServerMessage, events: &mut Vec<LobbyEvent>) {
+        match message {
+            ServerMessage::Welcome { id } => self.id = id,
+            ServerMessage::Rooms { rooms } => self.rooms = rooms,
+            ServerMessage::Room(room) => {
+                if self.room.as_ref().is_none_or(|old| old.id != room.id) {
+                    self.chat.clear();
+                    self.spectator = None;
+                }
+                if let Some(member) = room.members.iter().find(|member| member.id == self.id) {
+                    self.handicap = member.handicap;
+                }
+                self.room = Some(room);
+            }
+            ServerMessage::Chat { player, text } => self.say(Some(player), text),
+            ServerMessage::Start { seed, seats } => {
+                let name = self.room.as_ref().map_or_else(String::new, |room| room.settings.mode.clone());
+                // Script modes only exist where the script is installed.
+                let mode = Mode::parse(&name).unwrap_or_else(|_| {
+                    self.notice(tr_args("lobby-unknown-mode", &[("mode", &name)]));
+                    Mode::Endless
+                });
+                if self.spectating() {
+                    self.spectator = Some(Spectator::new(seed, &mode, &seats));
+                    return;
+                }
+                // Seats come in the same order as the room's members.
+                let ours = self
+                    .room
+                    .as_ref()
+                    .and_then(|room| room.members.iter().position(|member| member.id == self.id))
+                    .unwrap_or(0);
+                let theirs = 1 - ours.min(1);
+                let handicap = |seat: usize| seats.get(seat).map_or_else(Handicap::default, |seat| seat.handicap);
+                self.opponent = seats.get(theirs).map_or_else(String::new, |seat| seat.name.clone());
+                self.session = Some(Session::new(
+                    rollback::versus_game(seed, &mode, handicap(ours)),
+                    rollback::versus_game(seed, &mode, handicap(theirs)),
+                ));
+                self.reported = 0;
+                self.desynced = false;
+                self.playing = true;
+                events.push(LobbyEvent::Start { mode });
+            }
+            ServerMessage::Input { frame, actions, .. } => {
+                let result = self.session.as_mut().map(|session| session.receive_input(frame, &actions));
+                if let Some(Err(error)) = result {
+                    self.notice(tr_args("lobby-bad-input", &[("error", &error)]));
+                }
+                self.check_desync();
+            }
+            ServerMessage::SeatInput { seat, frame, actions } => {
+                let result = self.spectator.as_mut().map(|spectator| spectator.receive_input(seat, frame, &actions));
+                if let Some(Err(error)) = result {
+                    self.notice(tr_args("lobby-bad-input", &[("error", &error)]));
+                    self.spectator = None;
+                }
+            }
+            ServerMessage::StateHash { frame, hash, .. } => {
+                if let Some(session) = &mut self.session {
+                    session.receive_hash(frame, hash);
+                }
+                self.check_desync();
+            }
+            ServerMessage::Garbage { .. } => {}
+            ServerMessage::Finished { winner } => {
+                self.playing = false;
+                self.session = None;
+                self.notice(match winner {
+                    Some(player) => tr_args("lobby-game-won", &[("player", &player)]),
+                    None => tr("lobby-game-drawn"),
+                });
+                events.push(LobbyEvent::Finished);
+            }
+            ServerMessage::MatchOver { winner } => {
+                // A single game's winner has been told already.
+                if self.room.as_ref().is_some_and(|room| room.settings.best_of > 1) {
+                    self.notice(tr_args("lobby-match-won", &[("player", &winner)]));
+                }
+            }
+            ServerMessage::OpponentLeft => {
+                self.playing = false;
+                self.session = None;
+                self.notice(tr(if self.spectating() { "lobby-player-left" } else { "lobby-opponent-left" }));
+                events.push(LobbyEvent::Finished);
+            }
+            ServerMessage::Rejected { reason } => self.notice(tr_args("lobby-rejected", &[("reason", &reason)])),
+            ServerMessage::Error { message } => self.notice(message),
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, address: &mut String, player: &str) {
+        if !self.connected() {
+            self.connect_ui(ui, address, player);
+        } else if self.room.is_some() {
+            self.room_ui(ui);
+        } else {
+            self.rooms_ui(ui, player);
+        }
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+
+    fn connect_ui(&mut self, ui: &mut egui::Ui, address: &mut String, player: &str) {
+        ui.horizontal(|ui| {
+            ui.label(tr("lobby-server"));
+            ui.text_edit_singleline(address);
+            let connecting = self.connecting.is_some();
+            if ui.add_enabled(!connecting, egui::Button::new(tr("lobby-connect"))).clicked() {
+                self.connecting = Some(connect(address.trim().to_string(), player.to_string()));
+                self.status = Some(tr("lobby-connecting"));
+            }
+        });
+    }
+
+    fn rooms_ui(&mut self, ui: &mut egui::Ui, player: &str) {
+        ui.horizontal(|ui| {
+            if ui.button(tr("lobby-refresh")).clicked() {
+                self.send(ClientMessage::ListRooms);
+            }
+            if ui.button(tr("lobby-quick-match")).clicked() {
+                self.send(ClientMessage::QuickMatch);
+            }
+            if ui.button(tr("lobby-disconnect")).clicked() {
+                self.disconnect();
+            }
+        });
+        ui.separator();
+        if self.rooms.is_empty() {
+            ui.label(tr("lobby-no-rooms"));
+        }
+        let (mut join, mut watch) = (None, None);
+        egui::Grid::new("rooms").num_columns(5).striped(true).show(ui, |ui| {
+            for room in &self.rooms {
+                ui.label(&room.name);
+                ui.label(room.players.join(", "));
+                ui.label(format!(
+                    "{} · {}",
+                    modes::label(&room.settings.mode),
+                    tr_args("lobby-best-of", &[("games", &room.settings.best_of)])
+                ));
+                let open = !room.started && room.players.len() < 2;
+                if ui.add_enabled(open, egui::Button::new(tr("lobby-join"))).clicked() {
+                    join = Some(room.id);
+                }
+                if ui.button(tr("lobby-watch")).clicked() {
+                    watch = Some(room.id);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(room) = join {
+            self.send(ClientMessage::JoinRoom { room });
+        }
+        if let Some(room) = watch {
+            self.send(ClientMessage::Spectate { room });
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(tr("lobby-room-name"));
+            ui.add(
+                egui::TextEdit::singleline(&mut self.room_name)
+                    .hint_text(tr_args("lobby-default-room", &[("player", &player)])),
+            );
+        });
+        settings_ui(ui, &mut self.settings);
+        if ui.button(tr("lobby-create")).clicked() {
+            let name = match self.room_name.trim() {
+                "" => tr_args("lobby-default-room", &[("player", &player)]),
+                name => name.to_string(),
+            };
+            let settings = self.settings.clone();
+            self.send(ClientMessage::CreateRoom { name, settings });
+        }
+    }
+
+    fn room_ui(&mut self, ui: &mut egui::Ui) {
+        let room = self.room.clone().unwrap();
+        let host = room.members.first().is_some_and(|member| member.id == self.id);
+        let spectating = self.spectating();
+        ui.horizontal(|ui| {
+            ui.heading(&room.name);
+            if ui.button(tr("lobby-leave")).clicked() {
+                self.send(ClientMessage::Leave);
+                self.room = None;
+                self.playing = false;
+                self.session = None;
+                self.spectator = None;
+                self.send(ClientMessage::ListRooms);
+            }
+        });
+        if host && !room.started {
+            let mut settings = room.settings.clone();
+            settings_ui(ui, &mut settings);
+            if settings != room.settings {
+                self.send(ClientMessage::ChangeSettings(settings));
+            }
+        } else {
+            ui.label(format!(
+                "{} · {}",
+                modes::label(&room.settings.mode),
+                tr_args("lobby-best-of", &[("games", &room.settings.best_of)])
+            ));
+        }
+        ui.separator();
+        egui::Grid::new("members").num_columns(4).show(ui, |ui| {
+            for (seat, member) in room.members.iter().enumerate() {
+                let name = if seat == 0 { format!("{} {}", member.name, tr("lobby-host")) } else { member.name.clone() };
+                ui.label(name);
+                ui.label(tr(if member.ready { "lobby-ready" } else { "lobby-not-ready" }));
+                ui.label(tr_args("lobby-wins", &[("wins", &member.wins)]));
+                ui.label(handicap::summary(&member.handicap));
+                ui.end_row();
+            }
+        });
+        if !room.spectators.is_empty() {
+            ui.label(tr_args("lobby-spectators", &[("names", &room.spectators.join(", "))]));
+        }
+        if spectating {
+            ui.label(tr("lobby-watching"));
+        } else if room.started {
+            ui.label(tr("lobby-in-game"));
+        } else {
+            ui.collapsing(tr("lobby-handicap"), |ui| {
+                if handicap::ui(ui, &mut self.handicap) {
+                    self.send(ClientMessage::SetHandicap(self.handicap));
+                }
+            });
+            let mut ready = room.members.iter().any(|member| member.id == self.id && member.ready);
+            if ui.checkbox(&mut ready, tr("lobby-ready")).changed() {
+                self.send(ClientMessage::Ready { ready });
+            }
+        }
+        ui.separator();
+        self.chat_ui(ui);
+    }
+
+    fn chat_ui(&mut self, ui: &mut egui::Ui) {
+        let height = (ui.available_height() - 40.0).max(80.0);
+        egui::ScrollArea::vertical()
+            .max_height(height)
+            .stick_to_bottom(true)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                for (player, text) in &self.chat {
+                    match player {
+                        Some(player) => ui.label(format!("{}: {}", player, text)),
+                        None => ui.weak(text),
+                    };
+                }
+            });
+        ui.horizontal(|ui| {
+            let edit = ui.add(egui::TextEdit::singleline(&mut self.draft).char_limit(MAX_CHAT_CHARS));
+            let entered = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if (entered || ui.button(tr("lobby-send")).clicked()) && !self.draft.trim().is_empty() {
+                let text = std::mem::take(&mut self.draft);
+                self.send(ClientMessage::Chat { text });
+                edit.request_focus();
+            }
+        });
+    }
+}
+
+fn settings_ui(ui: &mut egui::Ui, settings: &mut RoomSettings) {
+    ui.horizontal(|ui| {
+        ui.label(tr("lobby-mode"));
+        egui::ComboBox::from_id_source("room-mode")
+            .selected_text(modes::label(&settings.mode))
+            .show_ui(ui, |ui| {
+                for mode in Mode::all() {
+                    ui.selectable_value(&mut settings.mode, mode.name(), mode.label());
+                }
+            });
+        egui::ComboBox::from_id_source("room-best-of")
+            .selected_text(tr_args("lobby-best-of", &[("games", &settings.best_of)]))
+            .show_ui(ui, |ui| {
+                for games in (1..=MAX_BEST_OF).step_by(2) {
+                    ui.selectable_value(&mut settings.best_of, games, tr_args("lobby-best-of", &[("games", &games)]));
+                }
+            });
+    });
+}
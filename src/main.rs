@@ -1,353 +1,2934 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
 use eframe::egui;
-use rand::Rng;
-use std::time::{Duration, Instant};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tetorisu::net::Handicap;
+
+use achievements::{Achievement, Achievements};
+use assist::Assist;
+use board::Board;
+use event_log::EventLog;
+use finesse::Finesse;
+use garbage::GarbageQueue;
+use history::GameRecord;
+use hud::{PieceMotion, StreakCounter};
+use i18n::{tr, tr_args};
+use keybinds::{Action, Autoshift};
+use leaderboard::Entry;
+use lobby::{Lobby, LobbyEvent};
+use modes::{Goal, Mode, SPRINT_LINES};
+use particles::Particles;
+use popups::Popups;
+use quests::{Quest, Quests};
+use randomizer::{PieceGenerator, Randomizer};
+use rollback::{Session, Spectator};
+use rotation::{KickTable, Rotation, Turn};
+use scripting::Script;
+use sequence::{PieceSequence, SequenceSetup};
+use settings::Settings;
+use skins::{BlockStyle, Skin};
+use sound::{Pack, Sound};
+use speed::SpeedCurve;
+use stats::Stats;
+use tas::Input;
+
+mod achievements;
+mod assist;
+mod board;
+mod cli;
+mod env;
+mod event_log;
+mod export;
+mod finesse;
+mod fumen;
+mod garbage;
+mod handicap;
+mod history;
+mod hud;
+mod i18n;
+mod keybinds;
+mod leaderboard;
+mod lobby;
+mod modes;
+mod particles;
+mod popups;
+mod presence;
+mod quests;
+mod randomizer;
+mod rollback;
+mod rotation;
+mod scripting;
+mod sequence;
+mod settings;
+mod skins;
+mod sound;
+mod speed;
+mod stats;
+mod tas;
+mod touch;
 
 const BOARD_WIDTH: usize = 10;
 const BOARD_HEIGHT: usize = 20;
+// Buffer rows above the visible field that pieces spawn into.
+const HIDDEN_ROWS: usize = 2;
+const TOTAL_HEIGHT: usize = BOARD_HEIGHT + HIDDEN_ROWS;
 const BLOCK_SIZE: f32 = 30.0;
-const TICK_DURATION: Duration = Duration::from_millis(500);
+const PREVIEW_COUNT: usize = 5;
+const PREVIEW_BLOCK_SIZE: f32 = 15.0;
+const MIN_BLOCK_SIZE: f32 = 10.0;
+const SIDE_PANEL_WIDTH: f32 = 150.0;
+const CONTROL_BUTTON_SIZE: f32 = 40.0;
+const REWIND_STEP: usize = 5;
+const MAX_LOCK_RESETS: u32 = 15;
+const COUNTDOWN: Duration = Duration::from_secs(3);
+const GO_SECONDS: f64 = 0.6;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+// How often a game with nothing animating redraws, to keep the clock moving.
+const CLOCK_INTERVAL: Duration = Duration::from_millis(100);
+// How often to check for replies from the leaderboard or match server.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Online frames that may pile up while waiting on the opponent.
+const ONLINE_BACKLOG: u32 = 4;
+// The opponent's board next to ours, at this share of our cell size.
+const OPPONENT_SCALE: f32 = 0.5;
+const GARBAGE_COLOR: egui::Color32 = egui::Color32::from_gray(120);
+// Share of each starting garbage row a handicap leaves open, about one cell.
+const HANDICAP_HOLES: f32 = 0.1;
 
 #[derive(Clone, Copy, PartialEq)]
 enum BlockType {
     Empty,
-    Filled,
+    Filled(PieceKind),
+    Garbage,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum PieceKind {
+    I,
+    O,
+    T,
+    L,
+    J,
+    S,
+    Z,
+}
+
+impl PieceKind {
+    const ALL: [PieceKind; 7] = [
+        PieceKind::I,
+        PieceKind::O,
+        PieceKind::T,
+        PieceKind::L,
+        PieceKind::J,
+        PieceKind::S,
+        PieceKind::Z,
+    ];
+
+    // Row bitmasks in the spawn state, bit j for column j, and the size of
+    // the square the piece turns in.
+    const fn spawn_shape(self) -> ([u16; 4], usize) {
+        match self {
+            PieceKind::I => ([0b0000, 0b1111, 0b0000, 0b0000], 4),
+            PieceKind::O => ([0b11, 0b11, 0, 0], 2),
+            PieceKind::T => ([0b010, 0b111, 0b000, 0], 3),
+            PieceKind::L => ([0b100, 0b111, 0b000, 0], 3),
+            PieceKind::J => ([0b001, 0b111, 0b000, 0], 3),
+            PieceKind::S => ([0b110, 0b011, 0b000, 0], 3),
+            PieceKind::Z => ([0b011, 0b110, 0b000, 0], 3),
+        }
+    }
+
+    // L and J, and S and Z, are mirror images of each other.
+    fn mirrored(self) -> PieceKind {
+        match self {
+            PieceKind::L => PieceKind::J,
+            PieceKind::J => PieceKind::L,
+            PieceKind::S => PieceKind::Z,
+            PieceKind::Z => PieceKind::S,
+            kind => kind,
+        }
+    }
+
+    fn size(self) -> i32 {
+        self.spawn_shape().1 as i32
+    }
+
+    fn masks(self, rotation: Rotation) -> [u16; 4] {
+        SHAPES[self as usize][rotation as usize]
+    }
+
+    fn color(self) -> egui::Color32 {
+        match self {
+            PieceKind::I => egui::Color32::from_rgb(0, 200, 220),
+            PieceKind::O => egui::Color32::from_rgb(230, 210, 0),
+            PieceKind::T => egui::Color32::from_rgb(170, 60, 200),
+            PieceKind::L => egui::Color32::from_rgb(240, 150, 0),
+            PieceKind::J => egui::Color32::from_rgb(40, 90, 230),
+            PieceKind::S => egui::Color32::from_rgb(60, 200, 60),
+            PieceKind::Z => egui::Color32::from_rgb(220, 40, 40),
+        }
+    }
+}
+
+// Every piece in all four rotation states, worked out at compile time so
+// spawning and turning never allocate.
+static SHAPES: [[[u16; 4]; 4]; 7] = {
+    let mut shapes = [[[0; 4]; 4]; 7];
+    let mut kind = 0;
+    while kind < 7 {
+        let (mut masks, size) = PieceKind::ALL[kind].spawn_shape();
+        let mut rotation = 0;
+        while rotation < 4 {
+            shapes[kind][rotation] = masks;
+            masks = turn_clockwise(masks, size);
+            rotation += 1;
+        }
+        kind += 1;
+    }
+    shapes
+};
+
+const fn turn_clockwise(masks: [u16; 4], size: usize) -> [u16; 4] {
+    let mut turned = [0; 4];
+    let mut i = 0;
+    while i < size {
+        let mut j = 0;
+        while j < size {
+            if masks[i] & 1 << j != 0 {
+                turned[j] |= 1 << (size - 1 - i);
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    turned
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LastAction {
+    Spawn,
+    Move,
+    Rotate { turn: Turn, kick: usize },
+}
+
+#[derive(Clone, Copy)]
 struct Tetromino {
-    blocks: Vec<Vec<bool>>,
+    kind: PieceKind,
     x: i32,
     y: i32,
+    rotation: Rotation,
+    last_action: LastAction,
 }
 
 impl Tetromino {
-    fn new() -> Self {
-        let shapes = vec![
-            // I
-            vec![
-                vec![true, true, true, true],
-                vec![false, false, false, false],
-            ],
-            // O
-            vec![
-                vec![true, true],
-                vec![true, true],
-            ],
-            // T
-            vec![
-                vec![false, true, false],
-                vec![true, true, true],
-            ],
-            // L
-            vec![
-                vec![true, false, false],
-                vec![true, true, true],
-            ],
-            // J
-            vec![
-                vec![false, false, true],
-                vec![true, true, true],
-            ],
-            // S
-            vec![
-                vec![false, true, true],
-                vec![true, true, false],
-            ],
-            // Z
-            vec![
-                vec![true, true, false],
-                vec![false, true, true],
-            ],
-        ];
-
-        let mut rng = rand::thread_rng();
-        let shape = shapes[rng.gen_range(0..shapes.len())].clone();
-        let width = shape[0].len() as i32;
-
+    fn new(kind: PieceKind) -> Self {
         Tetromino {
-            blocks: shape.clone(),
-            x: (BOARD_WIDTH as i32 - width) / 2,
-            y: 0,
+            kind,
+            x: (BOARD_WIDTH as i32 - kind.size()) / 2,
+            y: HIDDEN_ROWS as i32 - 2,
+            rotation: Rotation::Spawn,
+            last_action: LastAction::Spawn,
         }
     }
 
-    fn rotate(&mut self) {
-        let rows = self.blocks.len();
-        let cols = self.blocks[0].len();
-        let mut rotated = vec![vec![false; rows]; cols];
+    fn rotate(&mut self, turn: Turn) {
+        self.rotation = self.rotation.turned(turn);
+    }
+
+    fn masks(&self) -> [u16; 4] {
+        self.kind.masks(self.rotation)
+    }
 
-        for i in 0..rows {
-            for j in 0..cols {
-                rotated[j][rows - 1 - i] = self.blocks[i][j];
+    // Row by row, left to right.
+    fn cells(&self) -> [(i32, i32); 4] {
+        let mut cells = [(0, 0); 4];
+        let mut count = 0;
+        for (i, mask) in self.masks().into_iter().enumerate() {
+            for j in 0..4 {
+                if mask & 1 << j != 0 {
+                    cells[count] = (self.x + j, self.y + i as i32);
+                    count += 1;
+                }
             }
         }
+        cells
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+#[derive(Clone, Copy)]
+struct Clear {
+    lines: u32,
+    spin: Spin,
+}
+
+impl Clear {
+    fn score(&self) -> u32 {
+        match (self.spin, self.lines) {
+            (Spin::None, 1) => 100,
+            (Spin::None, 2) => 300,
+            (Spin::None, 3) => 500,
+            (Spin::None, 4) => 800,
+            (Spin::Mini, 0) => 100,
+            (Spin::Mini, 1) => 200,
+            (Spin::Mini, 2) => 400,
+            (Spin::Full, 0) => 400,
+            (Spin::Full, 1) => 800,
+            (Spin::Full, 2) => 1200,
+            (Spin::Full, 3) => 1600,
+            _ => 0,
+        }
+    }
+
+    fn attack(&self) -> u32 {
+        match (self.spin, self.lines) {
+            (Spin::None, 2) => 1,
+            (Spin::None, 3) => 2,
+            (Spin::None, 4) => 4,
+            (Spin::Mini, 2) => 1,
+            (Spin::Full, 1) => 2,
+            (Spin::Full, 2) => 4,
+            (Spin::Full, 3) => 6,
+            _ => 0,
+        }
+    }
+
+    // Tetrises and T-spins that clear lines keep a back-to-back chain going.
+    fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.lines > 0 && self.spin != Spin::None)
+    }
+
+    fn name(&self) -> Option<String> {
+        let lines = match self.lines {
+            0 => String::new(),
+            1 => tr("clear-single"),
+            2 => tr("clear-double"),
+            3 => tr("clear-triple"),
+            _ => tr("clear-tetris"),
+        };
+        let name = match self.spin {
+            Spin::None if self.lines == 0 => return None,
+            Spin::None => lines,
+            Spin::Mini => tr_args("clear-tspin-mini", &[("lines", &lines)]),
+            Spin::Full => tr_args("clear-tspin", &[("lines", &lines)]),
+        };
+        Some(name.trim_end().to_string())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TopOut {
+    // The next piece overlapped the stack where it spawns.
+    Block,
+    // A piece locked without reaching the visible field.
+    Lock,
+    // Rising garbage pushed blocks off the top of the field.
+    Garbage,
+}
 
-        self.blocks = rotated;
+impl TopOut {
+    fn name(&self) -> String {
+        tr(match self {
+            TopOut::Block => "top-out-block",
+            TopOut::Lock => "top-out-lock",
+            TopOut::Garbage => "top-out-garbage",
+        })
     }
 }
 
+#[derive(Clone)]
 struct TetrisGame {
-    board: Vec<Vec<BlockType>>,
+    board: Board,
     current_piece: Tetromino,
-    last_update: Instant,
+    next_queue: VecDeque<PieceKind>,
+    hold: Option<PieceKind>,
+    hold_used: bool,
+    rng: StdRng,
+    randomizer: Randomizer,
+    generator: Box<dyn PieceGenerator>,
+    sequence: Option<PieceSequence>,
+    gravity_timer: Duration,
     game_over: bool,
     score: u32,
+    combo: i32,
+    b2b: i32,
+    stats: Stats,
+    last_locked: Option<Tetromino>,
+    finesse: Finesse,
+    clearing: Option<LineClear>,
+    clear_delay: Duration,
+    // Entry delay (ARE) between a lock or line clear and the next spawn,
+    // and the time spent waiting in it so far.
+    are: Duration,
+    entry: Option<Duration>,
+    // Rotation and hold keys held while waiting, applied as the piece spawns.
+    initial_turn: Option<Turn>,
+    initial_hold: bool,
+    events: Vec<GameEvent>,
+    soft_drop: bool,
+    // Gravity multiplier while soft dropping; None drops straight to the floor.
+    soft_drop_factor: Option<u32>,
+    gravity: Duration,
+    lock_delay: Duration,
+    lock_timer: Duration,
+    lock_resets: u32,
+    goal: Option<Goal>,
+    // Time left before play starts; the board is frozen until it runs out.
+    countdown: Duration,
+    // Set when the game ended by reaching its goal rather than topping out.
+    finished: bool,
+    top_out: Option<TopOut>,
+    garbage: GarbageQueue,
+    // Fingerprint of every placement so far, sent along with online scores.
+    replay_hash: u64,
+    // Swaps each new piece for its mirror image.
+    mirror: bool,
+    kicks: Arc<KickTable>,
+    // Online only: how much faster or slower this board falls, how hard it
+    // attacks and how much of the queue it sees.
+    handicap: Handicap,
+    // Sets gravity, lock delay and ARE by level; without one they stay as
+    // the settings have them.
+    curve: Option<Arc<SpeedCurve>>,
+}
+
+// Things worth showing off, collected for the UI to pick up each frame.
+#[derive(Clone)]
+enum GameEvent {
+    Spawned(PieceKind),
+    // Where the piece ended up after each successful move or rotation.
+    Moved { dx: i32, to: Tetromino },
+    Rotated { turn: Turn, kick: usize, to: Tetromino },
+    Held(PieceKind),
+    Locked(Tetromino),
+    HardDrop(Vec<(i32, i32)>),
+    LinesCleared { rows: Vec<(usize, Vec<BlockType>)>, perfect: bool, spin: Spin },
+    // Points a placement earned, with the combo and back-to-back that added
+    // to them and the piece that earned them.
+    Scored { clear: Clear, points: u32, combo: u32, back_to_back: bool, piece: Tetromino },
+    GarbageRaised(u32),
+    ToppedOut(TopOut),
+}
+
+// Rows that have been filled but not yet removed, while the clear animation
+// plays. Gravity and input wait until it finishes.
+#[derive(Clone)]
+struct LineClear {
+    rows: Vec<usize>,
+    elapsed: Duration,
 }
 
 impl Default for TetrisGame {
     fn default() -> Self {
-        Self {
-            board: vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT],
-            current_piece: Tetromino::new(),
-            last_update: Instant::now(),
+        Self::new(None, vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT], None, Randomizer::default())
+    }
+}
+
+impl TetrisGame {
+    fn new(
+        sequence: Option<PieceSequence>,
+        visible: Vec<Vec<BlockType>>,
+        seed: Option<u64>,
+        randomizer: Randomizer,
+    ) -> Self {
+        let defaults = Settings::default();
+        let mut game = Self {
+            board: Board::new(visible),
+            current_piece: Tetromino::new(PieceKind::I),
+            next_queue: VecDeque::with_capacity(PREVIEW_COUNT + 1),
+            hold: None,
+            hold_used: false,
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            randomizer,
+            generator: randomizer.generator(),
+            sequence,
+            gravity_timer: Duration::ZERO,
             game_over: false,
             score: 0,
+            combo: -1,
+            b2b: -1,
+            stats: Stats::default(),
+            last_locked: None,
+            finesse: Finesse::default(),
+            clearing: None,
+            are: defaults.are(),
+            entry: None,
+            initial_turn: None,
+            initial_hold: false,
+            clear_delay: defaults.clear_delay(),
+            events: Vec::new(),
+            soft_drop: false,
+            soft_drop_factor: defaults.soft_drop_factor(),
+            gravity: defaults.gravity(),
+            lock_delay: defaults.lock_delay(),
+            lock_timer: Duration::ZERO,
+            lock_resets: 0,
+            goal: None,
+            countdown: Duration::ZERO,
+            finished: false,
+            top_out: None,
+            garbage: GarbageQueue::default(),
+            replay_hash: FNV_OFFSET,
+            mirror: false,
+            kicks: KickTable::srs(),
+            handicap: Handicap::default(),
+            curve: None,
+        };
+        for _ in 0..PREVIEW_COUNT {
+            let kind = game.generate_piece();
+            game.next_queue.push_back(kind);
         }
+        let first = game.next_piece();
+        game.spawn(first);
+        game
     }
-}
 
-impl TetrisGame {
-    fn update(&mut self) {
+    fn start_mode(&mut self, mode: &Mode, settings: &Settings) {
+        self.goal = mode.goal();
+        if *mode == Mode::BType {
+            self.fill_garbage(settings.btype_height, settings.btype_holes);
+        }
+    }
+
+    // Random garbage from the floor up, drawn from the game's own rng so a
+    // seed always gives the same board. Every row keeps at least one hole
+    // and one block.
+    fn fill_garbage(&mut self, rows: usize, holes: f32) {
+        for y in TOTAL_HEIGHT - rows.min(BOARD_HEIGHT)..TOTAL_HEIGHT {
+            let mut row: Vec<bool> = (0..BOARD_WIDTH).map(|_| self.rng.gen::<f32>() >= holes).collect();
+            if row.iter().all(|&filled| filled) {
+                row[self.rng.gen_range(0..BOARD_WIDTH)] = false;
+            }
+            if row.iter().all(|&filled| !filled) {
+                row[self.rng.gen_range(0..BOARD_WIDTH)] = true;
+            }
+            for (x, filled) in row.into_iter().enumerate() {
+                if filled {
+                    self.board.set(x, y, BlockType::Garbage);
+                }
+            }
+        }
+    }
+
+    // Starting garbage comes from an rng of its own, so the pieces still
+    // match an opponent playing from the same seed.
+    fn apply_handicap(&mut self, handicap: Handicap, seed: u64) {
+        self.handicap = handicap;
+        let rng = std::mem::replace(&mut self.rng, StdRng::seed_from_u64(seed));
+        self.fill_garbage(handicap.garbage_rows as usize, HANDICAP_HOLES);
+        self.rng = rng;
+    }
+
+    // A preview handicap hides the far end of the queue.
+    fn preview_count(&self) -> usize {
+        (self.handicap.preview as usize).min(PREVIEW_COUNT)
+    }
+
+    fn tick(&mut self, dt: Duration) {
         if self.game_over {
             return;
         }
+        if !self.countdown.is_zero() {
+            self.countdown = self.countdown.saturating_sub(dt);
+            return;
+        }
 
-        if !self.can_move(0, 1) {
-            self.merge_piece();
-            self.clear_lines();
-            self.current_piece = Tetromino::new();
-            if !self.can_move(0, 0) {
-                self.game_over = true;
+        self.stats.time += dt;
+        self.garbage.tick(dt);
+        self.check_goal();
+        if self.game_over {
+            return;
+        }
+        if let Some(clearing) = &mut self.clearing {
+            clearing.elapsed += dt;
+            if clearing.elapsed >= self.clear_delay {
+                self.finish_clear();
+            }
+            return;
+        }
+        if let Some(entry) = &mut self.entry {
+            *entry += dt;
+            if *entry >= self.are {
+                self.enter();
             }
             return;
         }
+        if self.soft_drop && self.soft_drop_factor.is_none() {
+            while self.can_move(0, 1) {
+                self.update();
+            }
+        }
+        self.gravity_timer += dt;
+        let interval = self.gravity_interval();
+        while self.is_active() && self.gravity_timer >= interval {
+            // A grounded piece can't fall any further this tick, which also
+            // keeps 20G's zero interval from looping forever.
+            if !self.can_move(0, 1) {
+                let left = self.gravity_timer.as_nanos().checked_rem(interval.as_nanos()).unwrap_or(0);
+                self.gravity_timer = Duration::from_nanos(left as u64);
+                break;
+            }
+            self.gravity_timer -= interval;
+            self.update();
+        }
 
-        self.current_piece.y += 1;
+        if self.is_active() && !self.can_move(0, 1) {
+            self.lock_timer += dt;
+            if self.lock_timer >= self.lock_delay {
+                self.lock_piece();
+            }
+        }
     }
 
-    fn can_move(&self, dx: i32, dy: i32) -> bool {
-        let new_x = self.current_piece.x + dx;
-        let new_y = self.current_piece.y + dy;
+    fn check_goal(&mut self) {
+        let reached = match self.goal {
+            Some(Goal::Lines(lines)) => self.stats.lines >= lines,
+            Some(Goal::Time(limit)) => {
+                self.stats.time = self.stats.time.min(limit);
+                self.stats.time >= limit
+            }
+            None => false,
+        };
+        if reached {
+            self.game_over = true;
+            self.finished = true;
+        }
+    }
 
-        for (i, row) in self.current_piece.blocks.iter().enumerate() {
-            for (j, &is_block) in row.iter().enumerate() {
-                if !is_block {
-                    continue;
-                }
+    // Call before each tick, after any fixed timings are set.
+    fn apply_curve(&mut self) {
+        if let Some(curve) = &self.curve {
+            let speed = curve.at(self.level());
+            self.gravity = speed.gravity;
+            self.lock_delay = speed.lock_delay;
+            self.are = speed.are;
+        }
+    }
 
-                let board_x = new_x + j as i32;
-                let board_y = new_y + i as i32;
+    fn gravity_interval(&self) -> Duration {
+        // Dividing by a float rounds, so no handicap leaves gravity exact.
+        let gravity = if self.handicap.gravity == 1.0 {
+            self.gravity
+        } else {
+            self.gravity.div_f32(self.handicap.gravity)
+        };
+        match (self.soft_drop, self.soft_drop_factor) {
+            (true, Some(factor)) => gravity / factor.max(1),
+            _ => gravity,
+        }
+    }
 
-                if board_x < 0 || board_x >= BOARD_WIDTH as i32 ||
-                   board_y >= BOARD_HEIGHT as i32 {
-                    return false;
-                }
+    // Moving or rotating a grounded piece buys it more time, up to a limit so
+    // it can't be stalled forever.
+    fn reset_lock_timer(&mut self) {
+        if self.lock_resets < MAX_LOCK_RESETS && !self.can_move(0, 1) {
+            self.lock_timer = Duration::ZERO;
+            self.lock_resets += 1;
+        }
+    }
 
-                if board_y >= 0 && self.board[board_y as usize][board_x as usize] == BlockType::Filled {
-                    return false;
-                }
-            }
+    fn update(&mut self) {
+        if !self.is_active() {
+            return;
+        }
+
+        // Grounded pieces wait for the lock delay in tick().
+        if !self.can_move(0, 1) {
+            return;
+        }
+
+        self.current_piece.y += 1;
+        self.current_piece.last_action = LastAction::Move;
+        self.lock_timer = Duration::ZERO;
+        if self.soft_drop {
+            self.score += 1;
         }
-        true
     }
 
-    fn merge_piece(&mut self) {
-        for (i, row) in self.current_piece.blocks.iter().enumerate() {
-            for (j, &is_block) in row.iter().enumerate() {
-                if is_block {
-                    let board_x = self.current_piece.x + j as i32;
-                    let board_y = self.current_piece.y + i as i32;
-                    if board_y >= 0 {
-                        self.board[board_y as usize][board_x as usize] = BlockType::Filled;
-                    }
-                }
-            }
+    fn is_active(&self) -> bool {
+        !self.game_over && !self.is_waiting() && self.countdown.is_zero()
+    }
+
+    fn stack_height(&self) -> usize {
+        self.board.stack_height()
+    }
+
+    // Between a lock and the next spawn, with no piece in play.
+    fn is_waiting(&self) -> bool {
+        self.clearing.is_some() || self.entry.is_some()
+    }
+
+    // How long until gravity or the lock delay acts on the piece by itself.
+    fn next_step_in(&self) -> Duration {
+        if !self.can_move(0, 1) {
+            self.lock_delay.saturating_sub(self.lock_timer)
+        } else {
+            self.gravity_interval().saturating_sub(self.gravity_timer)
+        }
+    }
+
+    fn start_countdown(&mut self) {
+        self.countdown = COUNTDOWN;
+    }
+
+    // How far the clear animation has run, from 0 to 1.
+    fn clear_progress(&self) -> Option<(&[usize], f32)> {
+        let clearing = self.clearing.as_ref()?;
+        let progress = clearing.elapsed.as_secs_f32() / self.clear_delay.as_secs_f32();
+        Some((&clearing.rows, progress.min(1.0)))
+    }
+
+    fn level(&self) -> u32 {
+        self.stats.lines / 10 + 1
+    }
+
+    // Three-corner rule: a T that last rotated into place with three of the
+    // four corners around its center blocked. Both front corners make it a
+    // full spin, as does the far kick that TST and fin setups rely on.
+    fn detect_spin(&self) -> Spin {
+        let piece = &self.current_piece;
+        let LastAction::Rotate { turn, kick } = piece.last_action else {
+            return Spin::None;
+        };
+        if piece.kind != PieceKind::T {
+            return Spin::None;
+        }
+
+        let blocked = |dx: i32, dy: i32| self.board.is_filled(piece.x + dx, piece.y + dy);
+        // Corners clockwise from top-left; the front pair follows the T's point.
+        let corners = [blocked(0, 0), blocked(2, 0), blocked(2, 2), blocked(0, 2)];
+        if corners.iter().filter(|&&c| c).count() < 3 {
+            return Spin::None;
+        }
+        let front = piece.rotation as usize;
+        // The kick table says which kick, if any, upgrades a mini.
+        if (corners[front] && corners[(front + 1) % 4]) || self.kicks.is_full_spin_kick(piece.kind, turn, kick) {
+            Spin::Full
+        } else {
+            Spin::Mini
         }
     }
 
-    fn clear_lines(&mut self) {
-        let mut lines_cleared = 0;
-        let mut y = BOARD_HEIGHT - 1;
-        while y > 0 {
-            if self.board[y].iter().all(|&block| block == BlockType::Filled) {
-                self.board.remove(y);
-                self.board.insert(0, vec![BlockType::Empty; BOARD_WIDTH]);
-                lines_cleared += 1;
+    fn lock_piece(&mut self) {
+        let kind = self.current_piece.kind;
+        let optimal = if self.finesse.enabled {
+            finesse::minimal_inputs(self, kind, &self.current_piece.cells())
+        } else {
+            None
+        };
+        if !self.finesse.record(kind, optimal) {
+            self.spawn(kind);
+            return;
+        }
+
+        let spin = self.detect_spin();
+        let level = self.level();
+        let locked_out = self.current_piece.cells().iter().all(|&(_, y)| y < HIDDEN_ROWS as i32);
+        self.last_locked = Some(self.current_piece);
+        self.hash_placement();
+        self.merge_piece();
+        self.events.push(GameEvent::Locked(self.current_piece));
+        let rows = self.full_rows();
+        let clear = Clear {
+            lines: rows.len() as u32,
+            spin,
+        };
+        if !rows.is_empty() {
+            let perfect = self.board.is_clear_except(&rows);
+            self.events.push(GameEvent::LinesCleared {
+                rows: rows.iter().map(|&y| (y, self.board[y].clone())).collect(),
+                perfect,
+                spin,
+            });
+        }
+        let mut score = clear.score();
+        if clear.lines > 0 {
+            self.combo += 1;
+            if clear.is_difficult() {
+                self.b2b += 1;
             } else {
-                y -= 1;
+                self.b2b = -1;
             }
+        } else {
+            self.combo = -1;
+        }
+        let back_to_back = clear.is_difficult() && self.b2b > 0;
+        if back_to_back {
+            score += score / 2;
+        }
+        if self.combo > 0 {
+            score += 50 * self.combo as u32;
+        }
+        let points = score * level;
+        self.score += points;
+        if points > 0 {
+            self.events.push(GameEvent::Scored {
+                clear,
+                points,
+                combo: self.combo.max(0) as u32,
+                back_to_back,
+                piece: self.current_piece,
+            });
+        }
+        let attack = self.stats.attack;
+        self.stats.record_clear(&clear, self.combo, back_to_back);
+        self.garbage.attack(self.handicap.scale_attack(self.stats.attack - attack));
+        if clear.lines == 0 {
+            self.raise_garbage();
+        }
+        self.stats.pieces += 1;
+        self.hold_used = false;
+        self.check_goal();
+        if locked_out && !self.game_over {
+            self.top_out(TopOut::Lock);
+        }
+        if self.game_over {
+            return;
+        }
+        self.clearing = Some(LineClear {
+            rows,
+            elapsed: Duration::ZERO,
+        });
+        if clear.lines == 0 || self.clear_delay.is_zero() {
+            self.finish_clear();
         }
-        self.score += lines_cleared * 100;
     }
 
-    fn move_piece(&mut self, dx: i32) {
-        if self.can_move(dx, 0) {
-            self.current_piece.x += dx;
+    // Full rows are looked up again here in case a mode script shifted the
+    // board while the animation ran.
+    fn finish_clear(&mut self) {
+        self.clearing = None;
+        let rows = self.full_rows();
+        self.remove_rows(&rows);
+        if self.are.is_zero() {
+            let next = self.next_piece();
+            self.spawn(next);
+        } else {
+            self.entry = Some(Duration::ZERO);
         }
     }
 
-    fn rotate_piece(&mut self) {
-        let mut rotated = self.current_piece.clone();
-        rotated.rotate();
-        
-        let mut valid = false;
-        for test_x in -1..=1 {
-            rotated.x = self.current_piece.x + test_x;
-            if self.is_valid_position(&rotated) {
-                valid = true;
-                break;
+    fn hash_placement(&mut self) {
+        let cells = self.current_piece.cells();
+        let values = std::iter::once(self.current_piece.kind as i32).chain(cells.iter().flat_map(|&(x, y)| [x, y]));
+        for value in values {
+            for byte in value.to_le_bytes() {
+                self.replay_hash = (self.replay_hash ^ byte as u64).wrapping_mul(FNV_PRIME);
             }
         }
+    }
 
-        if valid {
-            self.current_piece = rotated;
+    // A fingerprint of everything that decides how the game goes on from
+    // here: the board, the falling piece, hold and preview, the counters,
+    // timers and pending garbage, and the randomizer. The randomizer is
+    // hashed by what it would deal next, drawn from copies so the game is
+    // untouched. Two games fed the same inputs from the same seed hash the
+    // same after every tick, so peers can compare hashes to catch a desync.
+    fn state_hash(&self) -> u64 {
+        let block = |block: BlockType| match block {
+            BlockType::Empty => 0,
+            BlockType::Garbage => 1,
+            BlockType::Filled(kind) => 2 + kind as i64,
+        };
+        let piece = &self.current_piece;
+        let mut values: Vec<i64> = self.board.iter().flatten().map(|&cell| block(cell)).collect();
+        values.extend([piece.kind as i64, piece.x as i64, piece.y as i64, piece.rotation as i64]);
+        values.extend(self.next_queue.iter().map(|&kind| kind as i64));
+        values.extend([
+            self.hold.map_or(-1, |kind| kind as i64),
+            self.hold_used as i64,
+            self.score as i64,
+            self.combo as i64,
+            self.b2b as i64,
+            self.stats.lines as i64,
+            self.stats.pieces as i64,
+            self.lock_resets as i64,
+            self.stats.time.as_nanos() as i64,
+            self.gravity_timer.as_nanos() as i64,
+            self.lock_timer.as_nanos() as i64,
+            self.entry.map_or(-1, |entry| entry.as_nanos() as i64),
+            self.clearing.as_ref().map_or(-1, |clearing| clearing.elapsed.as_nanos() as i64),
+            self.game_over as i64,
+            self.mirror as i64,
+        ]);
+        for queue in [&self.garbage.incoming, &self.garbage.outgoing] {
+            values.push(-1);
+            values.extend(queue.iter().flat_map(|batch| [batch.lines as i64, batch.remaining.as_nanos() as i64]));
         }
+        let mut rng = self.rng.clone();
+        let mut generator = self.generator.clone();
+        let mut sequence = self.sequence.clone();
+        // Two bags' worth, enough to pin down where a bag randomizer is.
+        for _ in 0..14 {
+            let kind = match sequence.as_mut().and_then(|sequence| sequence.next()) {
+                Some(kind) => kind,
+                None => generator.next(&mut rng),
+            };
+            values.push(kind as i64);
+        }
+        values.push(rng.gen::<u64>() as i64);
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .fold(FNV_OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
     }
 
-    fn is_valid_position(&self, piece: &Tetromino) -> bool {
-        for (i, row) in piece.blocks.iter().enumerate() {
-            for (j, &is_block) in row.iter().enumerate() {
-                if !is_block {
-                    continue;
+    // Each batch rises with a single hole, pushing the stack up.
+    fn raise_garbage(&mut self) {
+        for lines in self.garbage.take_due() {
+            self.events.push(GameEvent::GarbageRaised(lines));
+            let hole = self.rng.gen_range(0..BOARD_WIDTH);
+            for _ in 0..lines {
+                let mut row = vec![BlockType::Garbage; BOARD_WIDTH];
+                row[hole] = BlockType::Empty;
+                if self.board.push_row(row) {
+                    self.top_out(TopOut::Garbage);
                 }
+            }
+        }
+    }
 
-                let board_x = piece.x + j as i32;
-                let board_y = piece.y + i as i32;
-
-                if board_x < 0 || board_x >= BOARD_WIDTH as i32 ||
-                   board_y >= BOARD_HEIGHT as i32 {
-                    return false;
-                }
+    // IHS goes first so an initial rotation applies to the held-in piece.
+    fn enter(&mut self) {
+        self.entry = None;
+        let next = self.next_piece();
+        self.spawn(next);
+        if self.game_over {
+            return;
+        }
+        if self.initial_hold {
+            self.hold_piece();
+        }
+        if let Some(turn) = self.initial_turn {
+            self.rotate_piece(turn);
+        }
+    }
 
-                if board_y >= 0 && self.board[board_y as usize][board_x as usize] == BlockType::Filled {
-                    return false;
-                }
-            }
+    fn generate_piece(&mut self) -> PieceKind {
+        let kind = match self.sequence.as_mut().and_then(|sequence| sequence.next()) {
+            Some(kind) => kind,
+            None => self.generator.next(&mut self.rng),
+        };
+        if self.mirror {
+            kind.mirrored()
+        } else {
+            kind
         }
-        true
     }
 
-    fn hard_drop(&mut self) {
-        while self.can_move(0, 1) {
-            self.current_piece.y += 1;
+    // Mirrors every piece from here on, including the ones already queued.
+    fn mirror_pieces(&mut self) {
+        self.mirror = true;
+        for kind in &mut self.next_queue {
+            *kind = kind.mirrored();
         }
-        self.update();
+        self.current_piece = Tetromino::new(self.current_piece.kind.mirrored());
     }
-}
 
-#[derive(Default)]
-pub struct TetrisApp {
-    game: TetrisGame,
-}
+    fn next_piece(&mut self) -> PieceKind {
+        let kind = self.generate_piece();
+        self.next_queue.push_back(kind);
+        self.next_queue.pop_front().unwrap()
+    }
 
-impl eframe::App for TetrisApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if !self.game.game_over && self.game.last_update.elapsed() >= TICK_DURATION {
-            self.game.update();
-            self.game.last_update = Instant::now();
+    fn spawn(&mut self, kind: PieceKind) {
+        self.current_piece = Tetromino::new(kind);
+        self.events.push(GameEvent::Spawned(kind));
+        self.gravity_timer = Duration::ZERO;
+        self.lock_timer = Duration::ZERO;
+        self.lock_resets = 0;
+        self.finesse.reset_inputs();
+        if !self.can_move(0, 0) {
+            self.top_out(TopOut::Block);
         }
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            if self.game.game_over {
-                ui.centered_and_justified(|ui| {
-                    ui.heading("Game Over!");
-                    if ui.button("Restart").clicked() {
-                        self.game = TetrisGame::default();
-                    }
-                });
-                return;
-            }
+    fn top_out(&mut self, reason: TopOut) {
+        // Garbage can push several rows off at once; only the first counts.
+        if self.top_out.is_none() {
+            self.events.push(GameEvent::ToppedOut(reason));
+        }
+        self.game_over = true;
+        self.top_out = Some(reason);
+    }
+
+    fn hold_piece(&mut self) {
+        if self.hold_used || !self.is_active() {
+            return;
+        }
+        let current = self.current_piece.kind;
+        self.events.push(GameEvent::Held(current));
+        let next = match self.hold.replace(current) {
+            Some(kind) => kind,
+            None => self.next_piece(),
+        };
+        self.spawn(next);
+        self.hold_used = true;
+    }
 
-            ui.label(format!("Score: {}", self.game.score));
+    fn can_move(&self, dx: i32, dy: i32) -> bool {
+        let piece = &self.current_piece;
+        self.board.fits(&piece.masks(), piece.x + dx, piece.y + dy)
+    }
 
-            if ui.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
-                self.game.move_piece(-1);
-            }
-            if ui.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
-                self.game.move_piece(1);
-            }
-            if ui.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-                self.game.update();
-            }
-            if ui.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-                self.game.rotate_piece();
-            }
-            if ui.input(|i| i.key_pressed(egui::Key::Space)) {
-                self.game.hard_drop();
+    fn merge_piece(&mut self) {
+        let block = BlockType::Filled(self.current_piece.kind);
+        for (x, y) in self.current_piece.cells() {
+            if y >= 0 {
+                self.board.set(x as usize, y as usize, block);
             }
+        }
+    }
 
-            let (response, painter) = ui.allocate_painter(
-                egui::vec2(BOARD_WIDTH as f32 * BLOCK_SIZE, BOARD_HEIGHT as f32 * BLOCK_SIZE),
-                egui::Sense::hover(),
-            );
+    fn full_rows(&self) -> Vec<usize> {
+        self.board.full_rows()
+    }
 
-            let board_rect = response.rect;
-            painter.rect_filled(board_rect, 0.0, egui::Color32::from_gray(20));
+    fn remove_rows(&mut self, rows: &[usize]) {
+        self.board.remove_rows(rows);
+    }
 
-            for (y, row) in self.game.board.iter().enumerate() {
-                for (x, block) in row.iter().enumerate() {
-                    if *block == BlockType::Filled {
-                        let block_rect = egui::Rect::from_min_size(
-                            board_rect.min + egui::vec2(x as f32 * BLOCK_SIZE, y as f32 * BLOCK_SIZE),
-                            egui::vec2(BLOCK_SIZE, BLOCK_SIZE),
-                        );
-                        painter.rect_filled(block_rect, 0.0, egui::Color32::BLUE);
-                    }
-                }
-            }
+    fn move_piece(&mut self, dx: i32) {
+        if self.is_active() && self.can_move(dx, 0) {
+            self.current_piece.x += dx;
+            self.current_piece.last_action = LastAction::Move;
+            self.events.push(GameEvent::Moved {
+                dx,
+                to: self.current_piece,
+            });
+            self.reset_lock_timer();
+        }
+    }
 
-            for (i, row) in self.game.current_piece.blocks.iter().enumerate() {
-                for (j, &is_block) in row.iter().enumerate() {
-                    if is_block {
-                        let block_rect = egui::Rect::from_min_size(
-                            board_rect.min + egui::vec2(
-                                (self.game.current_piece.x + j as i32) as f32 * BLOCK_SIZE,
-                                (self.game.current_piece.y + i as i32) as f32 * BLOCK_SIZE,
-                            ),
-                            egui::vec2(BLOCK_SIZE, BLOCK_SIZE),
-                        );
-                        painter.rect_filled(block_rect, 0.0, egui::Color32::RED);
-                    }
-                }
+    fn rotate_piece(&mut self, turn: Turn) {
+        if !self.is_active() {
+            return;
+        }
+        if let Some(rotated) = self.try_rotate(&self.current_piece, turn) {
+            self.current_piece = rotated;
+            if let LastAction::Rotate { turn, kick } = rotated.last_action {
+                self.events.push(GameEvent::Rotated { turn, kick, to: rotated });
             }
+            self.reset_lock_timer();
+        }
+    }
 
-            for x in 0..=BOARD_WIDTH {
-                painter.line_segment(
-                    [
-                        board_rect.min + egui::vec2(x as f32 * BLOCK_SIZE, 0.0),
-                        board_rect.min + egui::vec2(x as f32 * BLOCK_SIZE, board_rect.height()),
-                    ],
-                    egui::Stroke::new(1.0, egui::Color32::from_gray(40)),
-                );
-            }
-            for y in 0..=BOARD_HEIGHT {
-                painter.line_segment(
-                    [
-                        board_rect.min + egui::vec2(0.0, y as f32 * BLOCK_SIZE),
-                        board_rect.min + egui::vec2(board_rect.width(), y as f32 * BLOCK_SIZE),
-                    ],
-                    egui::Stroke::new(1.0, egui::Color32::from_gray(40)),
-                );
+    fn try_rotate(&self, piece: &Tetromino, turn: Turn) -> Option<Tetromino> {
+        let mut rotated = *piece;
+        rotated.rotate(turn);
+
+        for (kick, &(dx, dy)) in self.kicks.kicks(piece.kind, piece.rotation, turn).iter().enumerate() {
+            rotated.x = piece.x + dx;
+            rotated.y = piece.y - dy;
+            if self.is_valid_position(&rotated) {
+                rotated.last_action = LastAction::Rotate { turn, kick };
+                return Some(rotated);
             }
-        });
+        }
+        None
+    }
 
-        ctx.request_repaint();
+    fn drop_position(&self, piece: &Tetromino) -> Tetromino {
+        let mut dropped = *piece;
+        dropped.y += 1;
+        while self.is_valid_position(&dropped) {
+            dropped.y += 1;
+        }
+        dropped.y -= 1;
+        dropped
     }
-}
 
-fn main() {
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([
-                BOARD_WIDTH as f32 * BLOCK_SIZE + 40.0,
-                BOARD_HEIGHT as f32 * BLOCK_SIZE + 80.0,
-            ]),
-        ..Default::default()
-    };
+    fn is_valid_position(&self, piece: &Tetromino) -> bool {
+        self.board.fits(&piece.masks(), piece.x, piece.y)
+    }
+
+    fn hard_drop(&mut self) {
+        if !self.is_active() {
+            return;
+        }
+        while self.can_move(0, 1) {
+            self.current_piece.y += 1;
+            self.current_piece.last_action = LastAction::Move;
+        }
+        self.events.push(GameEvent::HardDrop(self.current_piece.cells().to_vec()));
+        self.lock_piece();
+    }
+}
+
+#[derive(Default)]
+struct SequenceEditor {
+    open: bool,
+    sequence: String,
+    board: String,
+    repeat: bool,
+    fumen: String,
+    comment: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct HistoryWindow {
+    open: bool,
+    records: Vec<GameRecord>,
+    message: Option<String>,
+}
+
+// Shows the online table when one is configured and reachable, and this
+// machine's history otherwise.
+struct LeaderboardView {
+    mode: String,
+    entries: Vec<Entry>,
+    online: bool,
+    status: Option<String>,
+    pending: Option<Receiver<Result<Vec<Entry>, String>>>,
+}
+
+impl Default for LeaderboardView {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Sprint.name(),
+            entries: Vec::new(),
+            online: false,
+            status: None,
+            pending: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum Screen {
+    #[default]
+    MainMenu,
+    ModeSelect,
+    Playing,
+    Paused,
+    Results,
+    Settings,
+    Leaderboard,
+    Achievements,
+    Lobby,
+}
+
+#[derive(Default)]
+pub struct TetrisApp {
+    screen: Screen,
+    // Where the settings screen's Back button leads.
+    settings_return: Screen,
+    game: TetrisGame,
+    practice: bool,
+    history: Vec<TetrisGame>,
+    setup: Option<SequenceSetup>,
+    sequence_editor: SequenceEditor,
+    history_window: HistoryWindow,
+    leaderboard: LeaderboardView,
+    submission: Option<Receiver<Result<(), String>>>,
+    submit_message: Option<String>,
+    combo_counter: StreakCounter,
+    motion: PieceMotion,
+    b2b_counter: StreakCounter,
+    recorded: bool,
+    log_events: bool,
+    event_log: Option<EventLog>,
+    // Best score for the mode before the game just recorded.
+    previous_best: Option<u32>,
+    settings: Settings,
+    config_path: PathBuf,
+    config_message: Option<String>,
+    autoshift: Autoshift,
+    // Only a focused -> unfocused transition pauses, so a window that
+    // opens in the background doesn't start paused.
+    focused: bool,
+    go_until: f64,
+    particles: Particles,
+    popups: Popups,
+    style: BlockStyle,
+    skin_error: Option<String>,
+    sound: sound::Player,
+    assist: Assist,
+    sound_error: Option<String>,
+    kick_error: Option<String>,
+    curve_error: Option<String>,
+    mode: Mode,
+    seed: Option<u64>,
+    script: Option<Script>,
+    script_error: Option<String>,
+    last_frame: Option<Instant>,
+    presence: presence::Presence,
+    achievements: Achievements,
+    quests: Quests,
+    // Quests the last recorded game completed, for the results screen.
+    completed_quests: Vec<Quest>,
+    lobby: Lobby,
+    // Inputs waiting for the next online frame, time not yet played and
+    // whether soft drop was last sent held.
+    online_inputs: Vec<Input>,
+    online_clock: Duration,
+    online_soft_drop: bool,
+    gestures: touch::Gestures,
+    buttons: touch::Buttons,
+}
+
+impl TetrisApp {
+    fn record_placement(&mut self) {
+        // Wait for the clear animation and entry delay so snapshots always
+        // start at a spawn.
+        if !self.practice || self.game.is_waiting() {
+            return;
+        }
+        if self.history.last().is_none_or(|last| last.stats.pieces != self.game.stats.pieces) {
+            self.history.push(self.game.clone());
+        }
+    }
+
+    fn rewind(&mut self, steps: usize) {
+        if self.history.len() <= 1 {
+            return;
+        }
+        let keep = self.history.len().saturating_sub(steps).max(1);
+        self.history.truncate(keep);
+        self.game = self.history[keep - 1].clone();
+        self.game.events.clear();
+        self.particles.clear();
+        self.popups.clear();
+        if self.screen == Screen::Results {
+            self.screen = Screen::Playing;
+        }
+    }
+
+    fn restart(&mut self) {
+        self.assist.clear();
+        let (enabled, strict) = (self.game.finesse.enabled, self.game.finesse.strict);
+        let kicks = self.kick_table();
+        let curve = self.speed_curve();
+        let randomizer = self.settings.randomizer(&self.mode);
+        self.game = match &self.setup {
+            Some(setup) => setup.game(self.seed, randomizer),
+            None => TetrisGame::new(
+                None,
+                vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT],
+                self.seed,
+                randomizer,
+            ),
+        };
+        self.game.kicks = kicks;
+        self.game.curve = curve;
+        self.game.start_mode(&self.mode, &self.settings);
+        if self.settings.mirror_pieces {
+            self.game.mirror_pieces();
+        }
+        self.game.finesse.enabled = enabled;
+        self.game.finesse.strict = strict;
+        self.script = None;
+        self.script_error = None;
+        if let Some(name) = self.mode.script() {
+            match Script::load(name) {
+                Ok(mut script) => {
+                    script.on_start(&mut self.game);
+                    self.script = Some(script);
+                }
+                Err(error) => self.script_error = Some(error),
+            }
+        }
+        self.combo_counter.reset();
+        self.b2b_counter.reset();
+        self.particles.clear();
+        self.popups.clear();
+        self.recorded = false;
+        self.completed_quests.clear();
+        self.event_log = None;
+        if self.log_events {
+            let curve = self.game.curve.as_ref().map(|curve| curve.name.as_str());
+            match EventLog::create(&self.mode_name(), self.seed, &self.game.kicks.name, curve) {
+                Ok(log) => self.event_log = Some(log),
+                Err(error) => eprintln!("Could not start event log: {}", error),
+            }
+        }
+        self.screen = Screen::Playing;
+        self.game.start_countdown();
+        self.set_practice(self.practice);
+    }
+
+    // Resuming goes through the countdown again so play doesn't restart
+    // the instant the key is pressed.
+    fn toggle_pause(&mut self) {
+        match self.screen {
+            Screen::Playing => self.screen = Screen::Paused,
+            Screen::Paused => {
+                self.screen = Screen::Playing;
+                self.game.start_countdown();
+            }
+            _ => {}
+        }
+    }
+
+    // A game in progress is paused rather than left running behind the
+    // settings screen.
+    fn open_settings(&mut self) {
+        self.settings_return = match self.screen {
+            Screen::Playing => Screen::Paused,
+            screen => screen,
+        };
+        self.screen = Screen::Settings;
+    }
+
+    // Game keys are ignored while typing into a text field or picking a binding.
+    fn keyboard_free(&self, ctx: &egui::Context) -> bool {
+        !ctx.wants_keyboard_input() && self.settings.rebinding.is_none()
+    }
+
+    // The current game's table unless the settings name another, which is
+    // loaded and checked here. A table that won't load falls back to SRS.
+    fn kick_table(&mut self) -> Arc<KickTable> {
+        if self.game.kicks.name == self.settings.kicks {
+            return self.game.kicks.clone();
+        }
+        match KickTable::load(&self.settings.kicks) {
+            Ok(table) => {
+                self.kick_error = None;
+                Arc::new(table)
+            }
+            Err(error) => {
+                self.settings.kicks = KickTable::srs().name.clone();
+                self.kick_error = Some(error);
+                KickTable::srs()
+            }
+        }
+    }
+
+    // The mode's speed curve, if the settings give it one. A curve that
+    // won't load is dropped, leaving the mode on the fixed timings.
+    fn speed_curve(&mut self) -> Option<Arc<SpeedCurve>> {
+        let name = self.settings.curve(&self.mode)?.to_string();
+        if let Some(curve) = self.game.curve.as_ref().filter(|curve| curve.name == name) {
+            return Some(curve.clone());
+        }
+        match SpeedCurve::load(&name) {
+            Ok(curve) => {
+                self.curve_error = None;
+                Some(Arc::new(curve))
+            }
+            Err(error) => {
+                self.settings.curves.remove(&self.mode.name());
+                self.curve_error = Some(error);
+                None
+            }
+        }
+    }
+
+    fn poll_lobby(&mut self) {
+        for event in self.lobby.poll() {
+            match event {
+                LobbyEvent::Start { mode } => self.start_online(mode),
+                LobbyEvent::Finished => {
+                    if matches!(self.screen, Screen::Playing | Screen::Paused | Screen::Results) {
+                        self.screen = Screen::Lobby;
+                    }
+                }
+            }
+        }
+    }
+
+    // The lobby's session plays the match; the game shown is a copy of its
+    // board for us, so everything else on screen works as offline. Scripts
+    // stay off, since both sides have to play the boards out the same. The
+    // offline sequence and practice setting are put back for afterwards.
+    fn start_online(&mut self, mode: Mode) {
+        let setup = self.setup.take();
+        let practice = std::mem::replace(&mut self.practice, false);
+        self.mode = mode;
+        self.restart();
+        (self.setup, self.practice) = (setup, practice);
+        self.script = None;
+        if let Some(session) = self.lobby.session() {
+            self.game = session.local().clone();
+        }
+        self.online_inputs.clear();
+        self.online_clock = Duration::ZERO;
+        self.online_soft_drop = false;
+    }
+
+    // Plays the session at its fixed frame rate, with whatever inputs came
+    // in since the last frame played. Waiting on the opponent only holds a
+    // few frames back, so play doesn't race to catch up once they're in.
+    fn step_online(&mut self, dt: Duration, time: f64) {
+        self.online_clock = (self.online_clock + dt).min(tas::FRAME * ONLINE_BACKLOG);
+        let counting = !self.game.countdown.is_zero();
+        let mut events = Vec::new();
+        while self.online_clock >= tas::FRAME && self.lobby.advance(&self.online_inputs) {
+            self.online_inputs.clear();
+            self.online_clock -= tas::FRAME;
+            if let Some(session) = self.lobby.session_mut() {
+                events.extend(session.take_events().0);
+            }
+        }
+        let Some(session) = self.lobby.session() else {
+            return;
+        };
+        self.game = session.local().clone();
+        self.game.events = events;
+        if counting && self.game.countdown.is_zero() {
+            self.go_until = time + GO_SECONDS;
+        }
+    }
+
+    // Inputs go to the session while a match is on, to be played on a
+    // later frame, and straight to the game otherwise.
+    fn input(&mut self, input: Input) {
+        if self.lobby.session().is_some() {
+            self.online_inputs.push(input);
+        } else {
+            tas::apply(&mut self.game, input);
+        }
+    }
+
+    fn sync_style(&mut self, ctx: &egui::Context) {
+        self.style.palette = self.settings.palette;
+        self.style.patterns = self.settings.patterns;
+        self.style.colors = self.settings.colors.clone();
+        self.style.high_contrast = self.settings.high_contrast;
+        let loaded = self.style.skin.as_ref().map(|skin| skin.name.as_str());
+        if loaded == self.settings.skin.as_deref() {
+            return;
+        }
+        self.style.skin = None;
+        if let Some(name) = &self.settings.skin {
+            match Skin::load(ctx, name) {
+                Ok(skin) => {
+                    self.style.skin = Some(skin);
+                    self.skin_error = None;
+                }
+                Err(error) => {
+                    self.settings.skin = None;
+                    self.skin_error = Some(error);
+                }
+            }
+        }
+    }
+
+    // Packs are read when picked; one that won't load goes back to the
+    // built-in sounds, with the reason shown in settings.
+    fn sync_sound(&mut self) {
+        if self.sound.pack() == self.settings.sound_pack.as_deref() {
+            return;
+        }
+        self.sound.set_pack(None);
+        if let Some(name) = &self.settings.sound_pack {
+            match Pack::load(name) {
+                Ok(pack) => {
+                    self.sound.set_pack(Some(pack));
+                    self.sound_error = None;
+                }
+                Err(error) => {
+                    self.settings.sound_pack = None;
+                    self.sound_error = Some(error);
+                }
+            }
+        }
+    }
+
+    fn handle_events(&mut self, time: f64) {
+        for event in std::mem::take(&mut self.game.events) {
+            if let Some(log) = &mut self.event_log {
+                if let Err(error) = log.record(self.game.stats.time, &event) {
+                    eprintln!("{}: {}", log.path().display(), error);
+                    self.event_log = None;
+                }
+            }
+            if let Some(sound) = Sound::for_event(&event).filter(|_| self.settings.sound) {
+                self.sound.play(sound, self.settings.volume);
+            }
+            if let GameEvent::LinesCleared { rows, perfect, spin } = &event {
+                self.check_clear(rows.len(), *perfect, *spin, time);
+            }
+            if let GameEvent::Scored { clear, points, combo, back_to_back, piece } = &event {
+                self.score_popup(clear, *points, *combo, *back_to_back, piece);
+            }
+            if let Some(script) = &mut self.script {
+                match &event {
+                    GameEvent::Spawned(kind) => script.on_spawn(&mut self.game, *kind),
+                    GameEvent::Locked(piece) => script.on_lock(&mut self.game, piece.kind),
+                    GameEvent::LinesCleared { rows, .. } => script.on_clear(&mut self.game, rows.len()),
+                    _ => {}
+                }
+            }
+            if self.settings.effects {
+                self.spawn_effects(event);
+            }
+        }
+    }
+
+    fn spawn_effects(&mut self, event: GameEvent) {
+        match event {
+            GameEvent::HardDrop(cells) => self.particles.sparks(&cells),
+            GameEvent::LinesCleared { rows, perfect, .. } => {
+                for (y, row) in &rows {
+                    let colors: Vec<_> = row.iter().filter_map(|&block| self.style.color(block)).collect();
+                    self.particles.burst(*y, &colors);
+                }
+                if perfect || rows.len() == 4 {
+                    self.particles.confetti();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Rises from the top of the piece that scored: what the placement was
+    // worth, then the back-to-back and combo that added to it.
+    fn score_popup(&mut self, clear: &Clear, points: u32, combo: u32, back_to_back: bool, piece: &Tetromino) {
+        let Some(name) = clear.name() else {
+            return;
+        };
+        let color = if clear.spin != Spin::None {
+            self.settings.palette.color(PieceKind::T, &self.settings.colors)
+        } else if clear.lines == 4 {
+            self.settings.palette.color(PieceKind::I, &self.settings.colors)
+        } else {
+            egui::Color32::WHITE
+        };
+        let mut lines = vec![(tr_args("popup-points", &[("name", &name), ("points", &points)]), color)];
+        if back_to_back {
+            lines.push((tr("back-to-back"), egui::Color32::GOLD));
+        }
+        if combo > 0 {
+            lines.push((tr_args("popup-combo", &[("combo", &combo)]), egui::Color32::LIGHT_BLUE));
+        }
+        let cells = piece.cells();
+        let x = cells.iter().map(|&(x, _)| x as f32).sum::<f32>() / cells.len() as f32 + 0.5;
+        let y = cells.iter().map(|&(_, y)| y).min().unwrap_or(0) as f32;
+        self.popups.show(egui::pos2(x, y), lines);
+    }
+
+    // Practice and sequence setups can rewind or stage the board, so they
+    // don't count.
+    fn earns_achievements(&self) -> bool {
+        !self.practice && self.setup.is_none()
+    }
+
+    fn check_clear(&mut self, lines: usize, perfect: bool, spin: Spin, time: f64) {
+        if !self.earns_achievements() {
+            return;
+        }
+        let unlocked = [
+            (Achievement::FirstTetris, lines == 4),
+            (Achievement::TSpinDouble, spin == Spin::Full && lines == 2),
+            (Achievement::TSpinTriple, spin == Spin::Full && lines == 3),
+            (Achievement::PerfectClear, perfect),
+        ];
+        for (achievement, done) in unlocked {
+            if done {
+                self.achievements.unlock(achievement, time);
+            }
+        }
+    }
+
+    fn check_progress(&mut self, time: f64) {
+        if !self.earns_achievements() {
+            return;
+        }
+        let game = &self.game;
+        let sprinted = self.mode == Mode::Sprint
+            && game.game_over
+            && game.stats.lines >= SPRINT_LINES
+            && game.stats.time < Duration::from_secs(60);
+        let unlocked = [
+            (Achievement::Combo10, game.combo >= 10),
+            (Achievement::BackToBack5, game.b2b >= 5),
+            (Achievement::Score100k, game.score >= 100_000),
+            (Achievement::Lines150, game.stats.lines >= 150),
+            (Achievement::SprintUnder60, sprinted),
+            (Achievement::UltraScore50k, self.mode == Mode::Ultra && game.score >= 50_000),
+        ];
+        for (achievement, done) in unlocked {
+            if done {
+                self.achievements.unlock(achievement, time);
+            }
+        }
+    }
+
+    // Play redraws every frame while something moves on screen and otherwise
+    // just in time for the next gravity step. Other screens only redraw on
+    // input, unless a toast or a server request is waiting.
+    fn repaint_after(&self, time: f64) -> Option<Duration> {
+        let mut delay: Option<Duration> = None;
+        let mut wake = |after: Duration| delay = Some(delay.map_or(after, |delay| delay.min(after)));
+        if self.screen == Screen::Playing {
+            let game = &self.game;
+            let danger = self.settings.danger_intensity > 0.0
+                && !self.settings.reduce_flashing
+                && game.stack_height() > self.settings.danger_height;
+            let animating = !game.countdown.is_zero()
+                || time < self.go_until
+                || game.is_waiting()
+                || game.soft_drop
+                || self.autoshift.is_held()
+                || !game.garbage.incoming.is_empty()
+                || !game.garbage.outgoing.is_empty()
+                || !self.particles.is_empty()
+                || !self.popups.is_empty()
+                || danger
+                || self.motion.is_moving()
+                || self.combo_counter.is_animating(time)
+                || self.b2b_counter.is_animating(time)
+                || self.script.is_some();
+            if animating {
+                wake(Duration::ZERO);
+            } else if !game.game_over {
+                wake(game.next_step_in().min(CLOCK_INTERVAL));
+            }
+        }
+        if self.submission.is_some() || self.leaderboard.pending.is_some() || self.lobby.active() {
+            wake(POLL_INTERVAL);
+        }
+        if let Some(left) = self.achievements.toast_remaining(time) {
+            wake(Duration::from_secs_f64(left));
+        }
+        delay
+    }
+
+    fn mode_name(&self) -> String {
+        if self.mode != Mode::Endless {
+            self.mode.name()
+        } else if self.setup.is_some() {
+            "Sequence".to_string()
+        } else if self.practice {
+            "Practice".to_string()
+        } else {
+            self.mode.name()
+        }
+    }
+
+    fn goal_label(&self, ui: &mut egui::Ui) {
+        match self.game.goal {
+            Some(Goal::Lines(lines)) => {
+                ui.label(tr_args("goal-lines", &[("cleared", &self.game.stats.lines.min(lines)), ("goal", &lines)]));
+            }
+            Some(Goal::Time(limit)) => {
+                let left = stats::format_time(limit.saturating_sub(self.game.stats.time));
+                ui.label(tr_args("goal-time", &[("left", &left)]));
+            }
+            None => {}
+        }
+    }
+
+    fn update_presence(&mut self) {
+        let app_id = self.settings.discord_app_id.trim();
+        if !self.settings.discord_presence || app_id.is_empty() {
+            self.presence.update(None);
+            return;
+        }
+        let playing = matches!(self.screen, Screen::Playing | Screen::Paused | Screen::Results);
+        let activity = if playing {
+            let state = tr_args("presence-state", &[("score", &self.game.score), ("level", &self.game.level())]);
+            let started = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .and_then(|now| now.checked_sub(self.game.stats.time));
+            presence::Activity {
+                app_id: app_id.to_string(),
+                details: modes::label(&self.mode_name()),
+                state: if self.screen == Screen::Paused {
+                    tr_args("presence-paused", &[("state", &state)])
+                } else {
+                    state
+                },
+                start: started.filter(|_| !self.game.game_over).map(|time| time.as_secs()),
+            }
+        } else {
+            presence::Activity {
+                app_id: app_id.to_string(),
+                details: tr("presence-menus"),
+                state: String::new(),
+                start: None,
+            }
+        };
+        self.presence.update(Some(activity));
+    }
+
+    fn record_game(&mut self) {
+        self.recorded = true;
+        if let Some(log) = self.event_log.take() {
+            let path = log.path().clone();
+            if let Err(error) = log.finish(&self.game) {
+                eprintln!("{}: {}", path.display(), error);
+            }
+        }
+        let mode = self.mode_name();
+        self.previous_best = history::load()
+            .iter()
+            .filter(|record| record.mode == mode)
+            .map(|record| record.score)
+            .max();
+        if self.earns_achievements() {
+            self.completed_quests = self.quests.record(&self.mode, &self.game);
+        }
+        let stats = &self.game.stats;
+        let record = GameRecord {
+            mode,
+            score: self.game.score,
+            lines: stats.lines,
+            duration: stats.time.as_secs_f32(),
+            pps: stats.pps(),
+            finesse_faults: self.game.finesse.total_faults(),
+            date: history::timestamp(),
+            curve: self.game.curve.as_ref().map(|curve| curve.name.clone()),
+            replay_hash: Some(format!("{:016x}", self.game.replay_hash)),
+        };
+        let url = self.settings.leaderboard_url.trim();
+        self.submit_message = None;
+        if self.game.finished && matches!(self.mode, Mode::Sprint | Mode::Ultra | Mode::BType) && !url.is_empty() {
+            let entry = Entry {
+                name: self.settings.player_name.clone(),
+                score: record.score,
+                lines: record.lines,
+                time_ms: self.game.stats.time.as_millis() as u64,
+                replay_hash: record.replay_hash.clone().unwrap_or_default(),
+            };
+            self.submission = Some(leaderboard::submit(url, &record.mode, entry));
+            self.submit_message = Some(tr("score-submitting"));
+        }
+        match history::append(&record) {
+            Ok(()) => self.history_window.records.push(record),
+            Err(error) => self.history_window.message = Some(tr_args("history-save-failed", &[("error", &error)])),
+        }
+    }
+
+    fn poll_leaderboard(&mut self) {
+        if let Some(result) = self.submission.as_ref().and_then(|receiver| receiver.try_recv().ok()) {
+            self.submission = None;
+            self.submit_message = Some(match result {
+                Ok(()) => tr("score-submitted"),
+                Err(error) => tr_args("score-submit-failed", &[("error", &error)]),
+            });
+        }
+        let view = &mut self.leaderboard;
+        if let Some(result) = view.pending.as_ref().and_then(|receiver| receiver.try_recv().ok()) {
+            view.pending = None;
+            match result {
+                Ok(entries) => {
+                    view.entries = entries;
+                    view.online = true;
+                    view.status = None;
+                }
+                Err(error) => view.status = Some(tr_args("leaderboard-offline", &[("error", &error)])),
+            }
+        }
+    }
+
+    // The local table shows straight away and is replaced if the fetch
+    // comes back.
+    fn load_leaderboard(&mut self, mode: String) {
+        let view = &mut self.leaderboard;
+        view.entries = leaderboard::local(&mode, &self.settings.player_name, &history::load());
+        view.online = false;
+        view.status = None;
+        view.pending = None;
+        let url = self.settings.leaderboard_url.trim();
+        if url.is_empty() {
+            view.status = Some(tr("leaderboard-no-url"));
+        } else {
+            view.pending = Some(leaderboard::fetch(url, &mode));
+            view.status = Some(tr("leaderboard-loading"));
+        }
+        view.mode = mode;
+    }
+
+    fn leaderboard_screen(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button(tr("back")).clicked() {
+                self.screen = Screen::MainMenu;
+            }
+            ui.heading(tr("leaderboard"));
+            for mode in [Mode::Sprint, Mode::Ultra, Mode::BType] {
+                let name = mode.name();
+                if ui.selectable_label(self.leaderboard.mode == name, mode.label()).clicked() {
+                    self.load_leaderboard(name);
+                }
+            }
+        });
+        let view = &self.leaderboard;
+        ui.label(tr(if view.online { "leaderboard-global" } else { "leaderboard-local" }));
+        if let Some(status) = &view.status {
+            ui.label(status);
+        }
+        ui.separator();
+        let sprint = view.mode == Mode::Sprint.name();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("leaderboard").num_columns(4).striped(true).show(ui, |ui| {
+                for (rank, entry) in view.entries.iter().enumerate() {
+                    ui.label(format!("{}.", rank + 1));
+                    ui.label(&entry.name);
+                    if sprint {
+                        ui.label(stats::format_time(Duration::from_millis(entry.time_ms)));
+                    } else {
+                        ui.label(entry.score.to_string());
+                    }
+                    ui.label(tr_args("leaderboard-lines", &[("lines", &entry.lines)]));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    fn history_window(&mut self, ctx: &egui::Context) {
+        let window = &mut self.history_window;
+        egui::Window::new(tr("history"))
+            .open(&mut window.open)
+            .default_height(300.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let dir = history::data_dir();
+                    if ui.button(tr("history-export-csv")).clicked() {
+                        let path = dir.join("history.csv");
+                        window.message = Some(match history::export_csv(&window.records, &path) {
+                            Ok(()) => tr_args("saved", &[("path", &path.display())]),
+                            Err(error) => tr_args("history-export-failed", &[("error", &error)]),
+                        });
+                    }
+                    if ui.button(tr("history-export-json")).clicked() {
+                        let path = dir.join("history.json");
+                        window.message = Some(match history::export_json(&window.records, &path) {
+                            Ok(()) => tr_args("saved", &[("path", &path.display())]),
+                            Err(error) => tr_args("history-export-failed", &[("error", &error)]),
+                        });
+                    }
+                });
+                if let Some(message) = &window.message {
+                    ui.label(message);
+                }
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("history").striped(true).show(ui, |ui| {
+                        for heading in ["date", "mode", "score", "lines", "time", "pps", "faults"] {
+                            ui.strong(tr(heading));
+                        }
+                        ui.end_row();
+                        for record in window.records.iter().rev() {
+                            ui.label(&record.date);
+                            ui.label(modes::label(&record.mode));
+                            ui.label(record.score.to_string());
+                            ui.label(record.lines.to_string());
+                            ui.label(stats::format_time(Duration::from_secs_f32(record.duration)));
+                            ui.label(format!("{:.2}", record.pps));
+                            ui.label(record.finesse_faults.to_string());
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+    }
+
+    fn results_screen(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.heading(tr(if self.game.finished { "results-finished" } else { "results-game-over" }));
+            if let Some(reason) = self.game.top_out {
+                ui.label(reason.name());
+            }
+            ui.label(modes::label(&self.mode_name()));
+            ui.label(tr_args("results-randomizer", &[("randomizer", &self.game.randomizer.name())]));
+            if let Some(curve) = &self.game.curve {
+                ui.label(tr_args("results-curve", &[("curve", &curve.name)]));
+            }
+            for quest in &self.completed_quests {
+                ui.strong(tr_args("quest-complete", &[("quest", &quest.description())]));
+            }
+            let score = self.game.score;
+            match self.previous_best {
+                Some(best) if score <= best => {
+                    ui.label(tr_args("results-best", &[("score", &best)]));
+                }
+                _ if score > 0 => {
+                    ui.colored_label(egui::Color32::GOLD, tr("results-high-score"));
+                }
+                _ => {}
+            }
+            if let Some(message) = &self.submit_message {
+                ui.label(message);
+            }
+            ui.add_space(8.0);
+            egui::Grid::new("results").num_columns(2).show(ui, |ui| {
+                ui.label(tr("score"));
+                ui.label(score.to_string());
+                ui.end_row();
+                ui.label(tr("level"));
+                ui.label(self.game.level().to_string());
+                ui.end_row();
+            });
+            self.game.stats.ui(ui);
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                if self.lobby.connected() {
+                    if ui.button(tr("online")).clicked() {
+                        self.screen = Screen::Lobby;
+                    }
+                } else if ui.button(tr("restart")).clicked() {
+                    self.restart();
+                }
+                if ui.button(tr("main-menu")).clicked() {
+                    self.screen = Screen::MainMenu;
+                }
+                if self.practice && ui.button(tr("undo")).clicked() {
+                    self.rewind(1);
+                }
+                if ui.button(tr("history")).clicked() {
+                    self.toggle_history();
+                }
+            });
+        });
+    }
 
+    // Hold and stats sit on the left, the queue and score on the right, and
+    // the board fills the middle at whatever size the window allows.
+    fn game_screen(&mut self, ctx: &egui::Context, dt: Duration, time: f64) {
+        let game = &self.game;
+        let hint = (self.settings.assist && !game.game_over && !game.is_waiting())
+            .then(|| self.assist.hint(game))
+            .flatten();
+        let shown = game.preview_count();
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(modes::label(&self.mode_name()));
+                self.goal_label(ui);
+                self.practice_controls(ui);
+                self.finesse_controls(ui);
+                ui.checkbox(&mut self.settings.assist, tr("assist"));
+                if ui.button(tr("sequence")).clicked() {
+                    self.sequence_editor.open = !self.sequence_editor.open;
+                }
+                if ui.button(tr("history")).clicked() {
+                    self.toggle_history();
+                }
+                if ui.button(tr("settings")).clicked() {
+                    self.open_settings();
+                }
+                let paused = self.screen == Screen::Paused;
+                if ui.button(tr(if paused { "resume" } else { "pause" })).clicked() {
+                    self.toggle_pause();
+                }
+                if paused && ui.button(tr("main-menu")).clicked() {
+                    self.screen = Screen::MainMenu;
+                }
+                if paused {
+                    ui.label(tr("preset"));
+                    self.settings.keybinds.preset_picker(ui);
+                }
+            });
+
+            if let Some(hint) = &hint {
+                ui.label(assist::keys(&hint.steps, &self.settings.keybinds));
+            }
+            let script_error = self.script.as_ref().and_then(|script| script.error.as_ref());
+            if let Some(error) = self.script_error.as_ref().or(script_error) {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            } else if let Some(message) = self.script.as_ref().and_then(|script| script.message.as_ref()) {
+                ui.label(message);
+            }
+        });
+
+        if self.settings.control_buttons {
+            egui::TopBottomPanel::bottom("controls").show(ctx, |ui| {
+                for action in self.buttons.ui(ui, CONTROL_BUTTON_SIZE) {
+                    if self.screen == Screen::Playing && self.game.is_active() {
+                        self.perform(action);
+                    }
+                }
+            });
+        }
+
+        // The large board drops the side panels for a strip with just hold,
+        // score and the queue, and gives the board the rest of the window.
+        let large = self.settings.large_board;
+        if large {
+            egui::TopBottomPanel::top("queue").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("hold"));
+                    draw_mini_piece(ui, self.game.hold, PREVIEW_BLOCK_SIZE, &self.style);
+                    ui.separator();
+                    ui.heading(self.game.score.to_string());
+                    ui.label(tr_args("level-number", &[("level", &self.game.level())]));
+                    ui.separator();
+                    ui.label(tr("next"));
+                    for &kind in self.game.next_queue.iter().take(shown) {
+                        draw_mini_piece(ui, Some(kind), PREVIEW_BLOCK_SIZE, &self.style);
+                    }
+                });
+            });
+        }
+
+        let available = ctx.available_rect().size();
+        let side_panels = if large { 0.0 } else { 2.0 * SIDE_PANEL_WIDTH };
+        // Online, the opponent's board sits at the right at a smaller scale.
+        let versus = self.lobby.session().is_some();
+        let columns = BOARD_WIDTH as f32 * if versus { 1.0 + OPPONENT_SCALE } else { 1.0 };
+        let cell = ((available.x - side_panels) / columns)
+            .min(available.y / TOTAL_HEIGHT as f32)
+            .max(MIN_BLOCK_SIZE);
+        let scale = cell / BLOCK_SIZE;
+        let preview = PREVIEW_BLOCK_SIZE * scale;
+
+        if !large {
+            egui::SidePanel::left("hold")
+                .resizable(false)
+                .exact_width(SIDE_PANEL_WIDTH)
+                .show(ctx, |ui| {
+                    ui.label(tr("hold"));
+                    draw_mini_piece(ui, self.game.hold, preview, &self.style);
+                    ui.add_space(10.0);
+                    let (combo, b2b) = (egui::Color32::from_rgb(255, 200, 60), egui::Color32::from_rgb(120, 200, 255));
+                    self.combo_counter.paint(ui, &tr("combo"), combo, time, scale);
+                    self.b2b_counter.paint(ui, &tr("back-to-back"), b2b, time, scale);
+                    self.game.stats.ui(ui);
+                    if self.game.finesse.enabled {
+                        ui.add_space(10.0);
+                        self.finesse_panel(ui);
+                    }
+                });
+            egui::SidePanel::right("next")
+                .resizable(false)
+                .exact_width(SIDE_PANEL_WIDTH)
+                .show(ctx, |ui| {
+                    ui.heading(self.game.score.to_string());
+                    ui.label(tr_args("level-number", &[("level", &self.game.level())]));
+                    ui.add_space(10.0);
+                    ui.label(tr("next"));
+                    for &kind in self.game.next_queue.iter().take(shown) {
+                        draw_mini_piece(ui, Some(kind), preview, &self.style);
+                    }
+                });
+        }
+
+        if let Some(session) = self.lobby.session() {
+            let opponent = session.remote();
+            let opponent_cell = cell * OPPONENT_SCALE;
+            egui::SidePanel::right("opponent")
+                .resizable(false)
+                .exact_width(BOARD_WIDTH as f32 * opponent_cell + 20.0)
+                .show(ctx, |ui| {
+                    ui.strong(self.lobby.opponent());
+                    ui.heading(opponent.score.to_string());
+                    draw_board(ui, opponent, opponent_cell, &self.style);
+                    let incoming: u32 = opponent.garbage.incoming.iter().map(|batch| batch.lines).sum();
+                    if incoming > 0 {
+                        ui.label(tr_args("lobby-opponent-incoming", &[("lines", &incoming)]));
+                    }
+                    opponent.stats.summary_ui(ui, "opponent-stats");
+                    if session.waiting() {
+                        ui.label(tr_args("lobby-waiting", &[("player", &self.lobby.opponent())]));
+                    }
+                });
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.keyboard_free(ctx) && self.screen == Screen::Playing && self.game.is_active() {
+                self.handle_keys(ui, dt);
+            }
+            // The panel margins leave a little less room than estimated above.
+            // Half a cell either side is kept for the garbage meters.
+            let available = ui.available_size();
+            let cell = cell
+                .min(available.x / (BOARD_WIDTH + 1) as f32)
+                .min(available.y / TOTAL_HEIGHT as f32)
+                .max(MIN_BLOCK_SIZE);
+            let scale = cell / BLOCK_SIZE;
+            ui.vertical_centered(|ui| {
+                // Cells are positioned from the top of the hidden rows, which
+                // stay undrawn apart from the active piece peeking into them.
+                let meter = cell * 0.5;
+                let (response, painter) = ui.allocate_painter(
+                    egui::vec2(BOARD_WIDTH as f32 * cell + 2.0 * meter, TOTAL_HEIGHT as f32 * cell),
+                    egui::Sense::click_and_drag(),
+                );
+                if self.settings.touch_gestures {
+                    for action in self.gestures.update(&response, cell, time) {
+                        if self.screen == Screen::Playing && self.game.is_active() {
+                            self.perform(action);
+                        }
+                    }
+                }
+                let origin = response.rect.min + egui::vec2(meter, 0.0);
+                let board_rect = egui::Rect::from_min_max(
+                    origin + egui::vec2(0.0, HIDDEN_ROWS as f32 * cell),
+                    response.rect.max - egui::vec2(meter, 0.0),
+                );
+                self.paint_garbage_meters(&painter, board_rect, meter, cell, scale);
+                // A high stack tints the board red and pulses its border.
+                let danger = if self.game.stack_height() > self.settings.danger_height {
+                    self.settings.danger_intensity
+                } else {
+                    0.0
+                };
+                let high_contrast = self.settings.high_contrast;
+                let background = egui::Color32::from_gray(if high_contrast { 0 } else { 20 });
+                painter.rect_filled(
+                    board_rect,
+                    0.0,
+                    hud::lerp_color(background, egui::Color32::from_rgb(90, 0, 0), danger),
+                );
+
+                let flip = self.settings.mirror_board;
+                let column = |x: f32| if flip { BOARD_WIDTH as f32 - 1.0 - x } else { x } * cell;
+                let clearing = self.game.clear_progress();
+                for (y, row) in self.game.board.iter().enumerate().skip(HIDDEN_ROWS) {
+                    // Cleared rows flash white for the first half of the
+                    // animation, unless flashing is turned off, then shrink
+                    // towards their centre line.
+                    let progress = clearing.filter(|(rows, _)| rows.contains(&y)).map(|(_, t)| t);
+                    for (x, &block) in row.iter().enumerate() {
+                        let Some(color) = self.style.color(block) else {
+                            continue;
+                        };
+                        let block_rect = egui::Rect::from_min_size(
+                            origin + egui::vec2(column(x as f32), y as f32 * cell),
+                            egui::vec2(cell, cell),
+                        );
+                        if let Some(t) = progress {
+                            let flash = if self.settings.reduce_flashing { 0.0 } else { (t * 2.0).min(1.0) };
+                            let color = hud::lerp_color(color, egui::Color32::WHITE, flash);
+                            let height = cell * (1.0 - (t * 2.0 - 1.0).max(0.0));
+                            let block_rect = egui::Rect::from_center_size(
+                                block_rect.center(),
+                                egui::vec2(cell, height),
+                            );
+                            painter.rect_filled(block_rect, 0.0, color);
+                        } else {
+                            self.style.paint(&painter, block_rect, block);
+                        }
+                    }
+                }
+
+                // The suggested placement is only outlined, faintly enough
+                // that the falling piece drawn over it stays clear.
+                if let Some(hint) = &hint {
+                    let color = self.style.color(BlockType::Filled(hint.piece.kind)).unwrap_or(egui::Color32::WHITE);
+                    for (x, y) in hint.piece.cells() {
+                        let block_rect = egui::Rect::from_min_size(
+                            origin + egui::vec2(column(x as f32), y as f32 * cell),
+                            egui::vec2(cell, cell),
+                        );
+                        painter.rect_filled(block_rect, 0.0, color.gamma_multiply(0.15));
+                        painter.rect_stroke(
+                            block_rect.shrink(cell * 0.1),
+                            0.0,
+                            egui::Stroke::new(2.0 * scale, color.gamma_multiply(0.5)),
+                        );
+                    }
+                }
+
+                // Updated after input is handled, so a move shows from the
+                // frame it happens in.
+                if self.settings.smooth_motion {
+                    self.motion.update(&self.game, dt.as_secs_f32());
+                } else {
+                    self.motion.reset();
+                }
+                // The locked piece is already on the board while rows clear.
+                if !self.game.is_waiting() {
+                    let piece = &self.game.current_piece;
+                    for at in self.motion.cells(piece) {
+                        let block_rect = egui::Rect::from_min_size(
+                            origin + egui::vec2(column(at.x), at.y * cell),
+                            egui::vec2(cell, cell),
+                        );
+                        self.style.paint(&painter, block_rect, BlockType::Filled(piece.kind));
+                    }
+                }
+
+                let grid = egui::Stroke::new(1.0, egui::Color32::from_gray(if high_contrast { 80 } else { 40 }));
+                for x in 0..=BOARD_WIDTH {
+                    painter.line_segment(
+                        [
+                            board_rect.min + egui::vec2(x as f32 * cell, 0.0),
+                            board_rect.min + egui::vec2(x as f32 * cell, board_rect.height()),
+                        ],
+                        grid,
+                    );
+                }
+                for y in 0..=BOARD_HEIGHT {
+                    painter.line_segment(
+                        [
+                            board_rect.min + egui::vec2(0.0, y as f32 * cell),
+                            board_rect.min + egui::vec2(board_rect.width(), y as f32 * cell),
+                        ],
+                        grid,
+                    );
+                }
+
+                if danger > 0.0 {
+                    let pulse = if self.settings.reduce_flashing {
+                        1.0
+                    } else {
+                        (time * std::f64::consts::TAU).sin() as f32 * 0.5 + 0.5
+                    };
+                    painter.rect_stroke(
+                        board_rect,
+                        0.0,
+                        egui::Stroke::new(
+                            2.0 + 2.0 * scale * pulse,
+                            egui::Color32::RED.gamma_multiply(danger * (0.4 + 0.6 * pulse)),
+                        ),
+                    );
+                }
+
+                self.particles.paint(&painter, origin, cell, flip);
+                self.popups.paint(&painter, origin, cell, flip);
+
+                let paused = self.screen == Screen::Paused;
+                let overlay = if paused {
+                    Some(tr("paused"))
+                } else if !self.game.countdown.is_zero() {
+                    Some((self.game.countdown.as_secs_f32().ceil() as u32).to_string())
+                } else if time < self.go_until {
+                    Some(tr("go"))
+                } else {
+                    None
+                };
+                if let Some(text) = overlay {
+                    painter.text(
+                        board_rect.center(),
+                        egui::Align2::CENTER_CENTER,
+                        text,
+                        egui::FontId::proportional(56.0 * scale),
+                        egui::Color32::WHITE,
+                    );
+                }
+                if paused {
+                    painter.text(
+                        board_rect.center() + egui::vec2(0.0, 44.0 * scale),
+                        egui::Align2::CENTER_CENTER,
+                        tr("press-to-resume"),
+                        egui::FontId::proportional(16.0 * scale),
+                        egui::Color32::LIGHT_GRAY,
+                    );
+                }
+            });
+        });
+    }
+
+    // Incoming garbage stacks up the left edge of the board, red once it is
+    // ready to rise, with the time left on the next batch above it. Attack
+    // still charging stacks up the right edge.
+    fn paint_garbage_meters(&self, painter: &egui::Painter, board_rect: egui::Rect, width: f32, cell: f32, scale: f32) {
+        let garbage = &self.game.garbage;
+        let mut bottom = board_rect.bottom();
+        for batch in &garbage.incoming {
+            let color = if batch.remaining.is_zero() {
+                egui::Color32::RED
+            } else {
+                egui::Color32::from_rgb(255, 170, 60)
+            };
+            let top = (bottom - batch.lines as f32 * cell).max(board_rect.top());
+            let rect = egui::Rect::from_x_y_ranges(board_rect.left() - width..=board_rect.left() - 2.0, top..=bottom);
+            painter.rect_filled(rect, 0.0, color);
+            bottom = top - 1.0;
+        }
+        if let Some(batch) = garbage.incoming.iter().find(|batch| !batch.remaining.is_zero()) {
+            painter.text(
+                egui::pos2(board_rect.left() - width / 2.0, bottom - 2.0),
+                egui::Align2::CENTER_BOTTOM,
+                format!("{:.1}", batch.remaining.as_secs_f32()),
+                egui::FontId::proportional(10.0 * scale),
+                egui::Color32::WHITE,
+            );
+        }
+
+        let charging = garbage.charging() as f32 * cell;
+        if charging > 0.0 {
+            let top = (board_rect.bottom() - charging).max(board_rect.top());
+            let rect = egui::Rect::from_x_y_ranges(board_rect.right() + 2.0..=board_rect.right() + width, top..=board_rect.bottom());
+            painter.rect_filled(rect, 0.0, egui::Color32::from_rgb(80, 160, 255));
+        }
+    }
+
+    fn main_menu(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 4.0);
+            ui.heading(tr("title"));
+            ui.add_space(8.0);
+            if ui.button(tr("play")).clicked() {
+                self.screen = Screen::ModeSelect;
+            }
+            if ui.button(tr("online")).clicked() {
+                self.screen = Screen::Lobby;
+            }
+            if ui.button(tr("leaderboard")).clicked() {
+                let mode = self.leaderboard.mode.clone();
+                self.load_leaderboard(mode);
+                self.screen = Screen::Leaderboard;
+            }
+            if ui.button(tr("achievements")).clicked() {
+                self.screen = Screen::Achievements;
+            }
+            if ui.button(tr("history")).clicked() {
+                self.toggle_history();
+            }
+            if ui.button(tr("settings")).clicked() {
+                self.open_settings();
+            }
+            if ui.button(tr("quit")).clicked() {
+                ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+            ui.add_space(16.0);
+            self.quests.ui(ui);
+        });
+    }
+
+    fn mode_select(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 4.0);
+            ui.heading(tr("select-mode"));
+            ui.add_space(8.0);
+            egui::Grid::new("modes").num_columns(3).show(ui, |ui| {
+                for mode in Mode::all() {
+                    let name = mode.name();
+                    if ui.button(mode.label()).clicked() {
+                        self.mode = mode.clone();
+                        self.restart();
+                    }
+                    let mut randomizer = self.settings.randomizer(&mode);
+                    egui::ComboBox::from_id_source(("randomizer", &name))
+                        .selected_text(randomizer.name())
+                        .show_ui(ui, |ui| {
+                            for option in Randomizer::ALL {
+                                ui.selectable_value(&mut randomizer, option, option.name());
+                            }
+                        });
+                    if randomizer != self.settings.randomizer(&mode) {
+                        self.settings.randomizers.insert(name.clone(), randomizer);
+                    }
+                    let mut curve = self.settings.curve(&mode).cloned();
+                    egui::ComboBox::from_id_source(("curve", &name))
+                        .selected_text(curve.clone().unwrap_or_else(|| tr("curve-fixed")))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut curve, None, tr("curve-fixed"));
+                            for option in speed::available() {
+                                ui.selectable_value(&mut curve, Some(option.clone()), option);
+                            }
+                        });
+                    if curve.as_ref() != self.settings.curve(&mode) {
+                        match curve {
+                            Some(curve) => self.settings.curves.insert(name, curve),
+                            None => self.settings.curves.remove(&name),
+                        };
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(error) = &self.curve_error {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            }
+            ui.checkbox(&mut self.practice, tr("practice"));
+            ui.add_space(8.0);
+            if ui.button(tr("back")).clicked() {
+                self.screen = Screen::MainMenu;
+            }
+        });
+    }
+
+    fn settings_screen(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button(tr("back")).clicked() {
+                self.screen = self.settings_return;
+            }
+            ui.heading(tr("settings"));
+        });
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            self.settings.ui(ui, self.quests.stars());
+            for error in [&self.skin_error, &self.kick_error, &self.sound_error].into_iter().flatten() {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button(tr("save")).clicked() {
+                    self.config_message = Some(match self.settings.save(&self.config_path) {
+                        Ok(()) => tr_args("saved", &[("path", &self.config_path.display())]),
+                        Err(error) => error,
+                    });
+                }
+                if ui.button(tr("reload")).clicked() {
+                    let (settings, error) = settings::load(&self.config_path, self.quests.stars());
+                    self.settings = settings;
+                    self.config_message =
+                        Some(error.unwrap_or_else(|| tr_args("loaded", &[("path", &self.config_path.display())])));
+                }
+            });
+            if let Some(message) = &self.config_message {
+                ui.label(message);
+            }
+        });
+    }
+
+    fn toggle_history(&mut self) {
+        self.history_window.open = !self.history_window.open;
+        if self.history_window.open {
+            self.history_window.records = history::load();
+        }
+    }
+
+    fn set_practice(&mut self, practice: bool) {
+        self.practice = practice;
+        self.history.clear();
+        self.record_placement();
+    }
+
+    fn practice_controls(&mut self, ui: &mut egui::Ui) {
+        let mut practice = self.practice;
+        if ui.checkbox(&mut practice, tr("practice")).changed() {
+            self.set_practice(practice);
+        }
+        if !self.practice {
+            return;
+        }
+        let can_undo = self.history.len() > 1;
+        if ui.add_enabled(can_undo, egui::Button::new(tr("undo"))).clicked() {
+            self.rewind(1);
+        }
+        if ui.add_enabled(can_undo, egui::Button::new(tr_args("rewind", &[("steps", &REWIND_STEP)]))).clicked() {
+            self.rewind(REWIND_STEP);
+        }
+        ui.label(tr_args("undo-steps", &[("steps", &(self.history.len() - 1))]));
+    }
+
+    fn apply_sequence(&mut self) {
+        let editor = &mut self.sequence_editor;
+        let setup = PieceSequence::parse(&editor.sequence, editor.repeat).and_then(|sequence| {
+            let board = sequence::parse_board(&editor.board)?;
+            Ok(SequenceSetup { sequence, board })
+        });
+        match setup {
+            Ok(setup) => {
+                editor.error = None;
+                self.setup = Some(setup);
+                self.restart();
+            }
+            Err(error) => editor.error = Some(error),
+        }
+    }
+
+    fn import_fumen(&mut self) {
+        let pages = match fumen::decode(&self.sequence_editor.fumen) {
+            Ok(pages) => pages,
+            Err(error) => {
+                self.sequence_editor.error = Some(error);
+                return;
+            }
+        };
+        let pieces: Vec<PieceKind> = pages
+            .iter()
+            .filter_map(|page| page.piece.as_ref().map(|placement| placement.kind))
+            .collect();
+        let board = pages[0].board.clone();
+
+        let editor = &mut self.sequence_editor;
+        editor.sequence = pieces.iter().map(|&kind| sequence::piece_char(kind)).collect();
+        editor.board = sequence::board_to_text(&board);
+        editor.repeat = false;
+        editor.comment = pages[0].comment.clone();
+        editor.error = None;
+        self.setup = Some(SequenceSetup {
+            sequence: PieceSequence::new(pieces, false),
+            board,
+        });
+        self.set_practice(true);
+        self.restart();
+    }
+
+    // With practice history every placement becomes a page; otherwise only
+    // the current board is exported.
+    fn export_fumen(&self) -> String {
+        let states: Vec<&TetrisGame> = if self.practice && !self.history.is_empty() {
+            self.history.iter().collect()
+        } else {
+            vec![&self.game]
+        };
+        let pages: Vec<fumen::Page> = states
+            .iter()
+            .enumerate()
+            .map(|(i, state)| fumen::Page {
+                board: state.board.to_vec(),
+                piece: states
+                    .get(i + 1)
+                    .and_then(|next| next.last_locked.as_ref())
+                    .map(|piece| fumen::Placement {
+                        kind: piece.kind,
+                        cells: piece
+                            .cells()
+                            .into_iter()
+                            .map(|(x, y)| (x, y - HIDDEN_ROWS as i32))
+                            .collect(),
+                    }),
+                comment: None,
+            })
+            .collect();
+        fumen::encode(&pages)
+    }
+
+    fn sequence_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.sequence_editor.open;
+        egui::Window::new(tr("sequence-title"))
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let editor = &mut self.sequence_editor;
+                ui.label(tr("sequence-pieces"));
+                ui.text_edit_singleline(&mut editor.sequence);
+                ui.checkbox(&mut editor.repeat, tr("sequence-repeat"));
+                ui.label(tr("sequence-board"));
+                ui.add(
+                    egui::TextEdit::multiline(&mut editor.board)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_rows(6),
+                );
+                if let Some(error) = &editor.error {
+                    ui.colored_label(egui::Color32::from_rgb(230, 90, 90), error);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button(tr("sequence-start")).clicked() {
+                        self.apply_sequence();
+                    }
+                    if self.setup.is_some() && ui.button(tr("sequence-clear")).clicked() {
+                        self.setup = None;
+                        self.restart();
+                    }
+                });
+                ui.label(tr("sequence-restart-hint"));
+                ui.separator();
+                ui.label(tr("fumen"));
+                ui.text_edit_singleline(&mut self.sequence_editor.fumen);
+                if let Some(comment) = &self.sequence_editor.comment {
+                    ui.label(comment);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button(tr("fumen-import")).clicked() {
+                        self.import_fumen();
+                    }
+                    if ui.button(tr("fumen-export")).clicked() {
+                        let data = self.export_fumen();
+                        ui.output_mut(|o| o.copied_text = data.clone());
+                        self.sequence_editor.fumen = data;
+                    }
+                });
+            });
+        self.sequence_editor.open = open;
+    }
+
+    fn handle_keys(&mut self, ui: &egui::Ui, dt: Duration) {
+        let keys = self.settings.keybinds.clone();
+        let pressed = |action| ui.input(|i| i.key_pressed(keys.key(action)));
+        let held = |action| ui.input(|i| i.key_down(keys.key(action)));
+        // A fresh press taps once and counts as one finesse input; holding
+        // on is left to DAS and ARR.
+        for (action, dx, other) in [
+            (Action::MoveLeft, -1, Action::MoveRight),
+            (Action::MoveRight, 1, Action::MoveLeft),
+        ] {
+            if fresh_press(ui, keys.key(action)) {
+                self.input(if dx < 0 { Input::MoveLeft } else { Input::MoveRight });
+                self.game.finesse.count_input();
+                self.autoshift.press(dx);
+            } else if !held(action) {
+                self.autoshift.release(dx, held(other));
+            }
+        }
+        let (dx, cells) = self.autoshift.update(dt, self.settings.das(), self.settings.arr());
+        // Online, the board shown is a frame or two behind the inputs, so
+        // it can't tell where the wall is; extra moves just don't happen.
+        for _ in 0..cells.min(BOARD_WIDTH as u32) {
+            if !self.game.can_move(dx, 0) {
+                break;
+            }
+            self.input(if dx < 0 { Input::MoveLeft } else { Input::MoveRight });
+        }
+        let actions = [Action::RotateCw, Action::RotateCcw, Action::Rotate180, Action::Hold, Action::HardDrop];
+        for action in actions.into_iter().filter(|&action| pressed(action)).collect::<Vec<_>>() {
+            self.perform(action);
+        }
+    }
+
+    // One press of an action, whatever it came from. Soft drop is held
+    // rather than pressed, so it's handled where the game is ticked.
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::MoveLeft | Action::MoveRight => {
+                self.input(if action == Action::MoveLeft { Input::MoveLeft } else { Input::MoveRight });
+                self.game.finesse.count_input();
+            }
+            Action::RotateCw | Action::RotateCcw | Action::Rotate180 => {
+                let turn = match action {
+                    Action::RotateCw => Turn::Cw,
+                    Action::RotateCcw => Turn::Ccw,
+                    _ => Turn::Half,
+                };
+                self.input(Input::Rotate(turn));
+                self.game.finesse.count_input();
+            }
+            Action::Hold => self.input(Input::Hold),
+            Action::HardDrop => {
+                self.input(Input::HardDrop);
+                self.record_placement();
+            }
+            Action::SoftDrop => {}
+        }
+    }
+
+    fn finesse_controls(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.game.finesse.enabled, tr("finesse"));
+        if self.game.finesse.enabled {
+            ui.checkbox(&mut self.game.finesse.strict, tr("finesse-strict"));
+        }
+    }
+
+    fn finesse_panel(&self, ui: &mut egui::Ui) {
+        let finesse = &self.game.finesse;
+        ui.label(tr_args(
+            "finesse-faults",
+            &[("faults", &finesse.total_faults()), ("pieces", &finesse.total_placements())],
+        ));
+        for kind in PieceKind::ALL {
+            let faults = finesse.faults[kind as usize];
+            if faults > 0 {
+                ui.label(format!("{:?}: {}", kind, faults));
+            }
+        }
+        if let Some(fault) = finesse.last_fault {
+            ui.colored_label(
+                egui::Color32::from_rgb(230, 90, 90),
+                tr_args(
+                    "finesse-fault",
+                    &[("piece", &format!("{:?}", fault.kind)), ("used", &fault.used), ("optimal", &fault.optimal)],
+                ),
+            );
+        }
+    }
+}
+
+fn fresh_press(ui: &egui::Ui, key: egui::Key) -> bool {
+    ui.input(|i| {
+        i.events.iter().any(|event| {
+            matches!(
+                event,
+                egui::Event::Key { key: pressed_key, pressed: true, repeat: false, .. }
+                if *pressed_key == key
+            )
+        })
+    })
+}
+
+// Someone else's board, small and without the effects of your own: the
+// visible rows and the falling piece.
+fn draw_board(ui: &mut egui::Ui, game: &TetrisGame, cell: f32, style: &BlockStyle) {
+    let (response, painter) = ui.allocate_painter(
+        egui::vec2(BOARD_WIDTH as f32 * cell, BOARD_HEIGHT as f32 * cell),
+        egui::Sense::hover(),
+    );
+    painter.rect_filled(response.rect, 0.0, egui::Color32::from_gray(20));
+    let paint = |x: i32, y: i32, block: BlockType| {
+        if y >= HIDDEN_ROWS as i32 {
+            let min = response.rect.min + egui::vec2(x as f32, (y - HIDDEN_ROWS as i32) as f32) * cell;
+            style.paint(&painter, egui::Rect::from_min_size(min, egui::vec2(cell, cell)), block);
+        }
+    };
+    for (y, row) in game.board.iter().enumerate() {
+        for (x, &block) in row.iter().enumerate() {
+            if block != BlockType::Empty {
+                paint(x as i32, y as i32, block);
+            }
+        }
+    }
+    if !game.game_over && !game.is_waiting() {
+        let piece = &game.current_piece;
+        for (x, y) in piece.cells() {
+            paint(x, y, BlockType::Filled(piece.kind));
+        }
+    }
+}
+
+// A match watched from the lobby: both boards side by side, each with its
+// player's name, score and pace.
+fn draw_spectated(ui: &mut egui::Ui, spectator: &Spectator, style: &BlockStyle) {
+    let cell = (ui.available_height() / (BOARD_HEIGHT + 8) as f32).clamp(MIN_BLOCK_SIZE, BLOCK_SIZE);
+    ui.horizontal_top(|ui| {
+        for (seat, (name, game)) in spectator.boards().into_iter().enumerate() {
+            ui.vertical(|ui| {
+                ui.strong(name);
+                ui.heading(game.score.to_string());
+                draw_board(ui, game, cell, style);
+                game.stats.summary_ui(ui, &format!("spectated-{}", seat));
+            });
+        }
+    });
+}
+
+fn draw_mini_piece(ui: &mut egui::Ui, kind: Option<PieceKind>, cell: f32, style: &BlockStyle) {
+    let (response, painter) = ui.allocate_painter(
+        egui::vec2(4.0 * cell, 2.0 * cell),
+        egui::Sense::hover(),
+    );
+    let Some(kind) = kind else {
+        return;
+    };
+    for (i, mask) in kind.masks(Rotation::Spawn).into_iter().filter(|&mask| mask != 0).enumerate() {
+        for j in 0..4 {
+            if mask & 1 << j != 0 {
+                let block_rect = egui::Rect::from_min_size(
+                    response.rect.min + egui::vec2(j as f32 * cell, i as f32 * cell),
+                    egui::vec2(cell, cell),
+                );
+                style.paint(&painter, block_rect.shrink(1.0), BlockType::Filled(kind));
+            }
+        }
+    }
+}
+
+impl eframe::App for TetrisApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        i18n::set_language(self.settings.language());
+        i18n::load_fonts(ctx);
+        let now = Instant::now();
+        let dt = now - self.last_frame.unwrap_or(now);
+        self.last_frame = Some(now);
+        self.game.gravity = self.settings.gravity();
+        self.game.lock_delay = self.settings.lock_delay();
+        self.game.clear_delay = self.settings.clear_delay();
+        self.game.are = self.settings.are();
+        self.game.soft_drop_factor = self.settings.soft_drop_factor();
+        self.game.apply_curve();
+        let soft_drop = self.settings.keybinds.key(Action::SoftDrop);
+        let soft_drop = (self.keyboard_free(ctx) && ctx.input(|i| i.key_down(soft_drop)))
+            || self.gestures.soft_drop()
+            || (self.settings.control_buttons && self.buttons.soft_drop());
+        if self.lobby.session().is_none() {
+            self.game.soft_drop = soft_drop;
+        } else if soft_drop != self.online_soft_drop {
+            self.online_soft_drop = soft_drop;
+            self.online_inputs.push(Input::SoftDrop(soft_drop));
+        }
+        let free = self.settings.initial_actions && self.keyboard_free(ctx);
+        let keybinds = &self.settings.keybinds;
+        let held = |action| free && ctx.input(|i| i.key_down(keybinds.key(action)));
+        let turns = [(Action::RotateCw, Turn::Cw), (Action::RotateCcw, Turn::Ccw), (Action::Rotate180, Turn::Half)];
+        self.game.initial_turn = turns
+            .into_iter()
+            .find(|&(action, _)| held(action))
+            .map(|(_, turn)| turn);
+        self.game.initial_hold = held(Action::Hold);
+        let time = ctx.input(|i| i.time);
+        let focused = ctx.input(|i| i.focused);
+        if self.focused && !focused && self.screen == Screen::Playing {
+            self.screen = Screen::Paused;
+        }
+        self.focused = focused;
+        self.poll_lobby();
+        // A match goes on whatever screen is up; there's no pausing it.
+        let online = self.lobby.session().is_some();
+        if online {
+            self.step_online(dt, time);
+        }
+        if self.screen == Screen::Playing {
+            if !online {
+                let counting = !self.game.countdown.is_zero();
+                self.game.tick(dt);
+                if counting && self.game.countdown.is_zero() {
+                    self.go_until = time + GO_SECONDS;
+                }
+            }
+            self.handle_events(time);
+            if let Some(script) = &mut self.script {
+                if !self.game.game_over && self.game.countdown.is_zero() {
+                    script.on_tick(&mut self.game, dt.as_secs_f64());
+                }
+            }
+            self.particles.update(dt.as_secs_f32());
+            self.popups.update(dt.as_secs_f32());
+        }
+        self.record_placement();
+        self.check_progress(time);
+        if self.game.game_over && !self.recorded {
+            self.record_game();
+        }
+        if let Some(result) = self.lobby.session().and_then(Session::result).filter(|_| self.lobby.playing()) {
+            self.lobby.finish(result);
+        }
+        self.poll_leaderboard();
+        if self.game.game_over && matches!(self.screen, Screen::Playing | Screen::Paused) {
+            self.screen = Screen::Results;
+        }
+        self.update_presence();
+        self.combo_counter.update(self.game.combo, time);
+        self.b2b_counter.update(self.game.b2b, time);
+
+        self.sequence_window(ctx);
+        self.history_window(ctx);
+        self.sync_style(ctx);
+        self.sync_sound();
+
+        let keyboard_free = self.keyboard_free(ctx);
+        let escape = keyboard_free && ctx.input(|i| i.key_pressed(egui::Key::Escape));
+        if keyboard_free
+            && self.practice
+            && matches!(self.screen, Screen::Playing | Screen::Results)
+            && ctx.input(|i| i.key_pressed(egui::Key::Backspace))
+        {
+            let steps = if ctx.input(|i| i.modifiers.shift) { REWIND_STEP } else { 1 };
+            self.rewind(steps);
+        }
+        match self.screen {
+            Screen::Playing | Screen::Paused => {
+                if escape || keyboard_free && ctx.input(|i| i.key_pressed(egui::Key::P)) {
+                    self.toggle_pause();
+                }
+                if keyboard_free && self.setup.is_some() && ctx.input(|i| i.key_pressed(egui::Key::R)) {
+                    self.restart();
+                }
+                self.game_screen(ctx, dt, time);
+            }
+            screen => {
+                if escape {
+                    match screen {
+                        Screen::ModeSelect | Screen::Leaderboard | Screen::Achievements | Screen::Lobby => {
+                            self.screen = Screen::MainMenu
+                        }
+                        Screen::Settings => self.screen = self.settings_return,
+                        _ => {}
+                    }
+                }
+                egui::CentralPanel::default().show(ctx, |ui| match self.screen {
+                    Screen::MainMenu => self.main_menu(ui),
+                    Screen::ModeSelect => self.mode_select(ui),
+                    Screen::Settings => self.settings_screen(ui),
+                    Screen::Leaderboard => self.leaderboard_screen(ui),
+                    Screen::Achievements => {
+                        ui.horizontal(|ui| {
+                            if ui.button(tr("back")).clicked() {
+                                self.screen = Screen::MainMenu;
+                            }
+                            ui.heading(tr("achievements"));
+                        });
+                        self.achievements.gallery(ui);
+                    }
+                    Screen::Results => self.results_screen(ui),
+                    Screen::Lobby => {
+                        ui.horizontal(|ui| {
+                            if ui.button(tr("back")).clicked() {
+                                self.screen = Screen::MainMenu;
+                            }
+                            ui.heading(tr("online"));
+                        });
+                        ui.separator();
+                        if let Some(spectator) = self.lobby.spectator() {
+                            egui::SidePanel::left("spectated")
+                                .resizable(false)
+                                .show_inside(ui, |ui| draw_spectated(ui, spectator, &self.style));
+                        }
+                        let settings = &mut self.settings;
+                        self.lobby.ui(ui, &mut settings.server_address, &settings.player_name);
+                    }
+                    // Escape from settings can land back in the game; it
+                    // gets drawn from the next frame.
+                    Screen::Playing | Screen::Paused => {}
+                });
+            }
+        }
+
+        self.achievements.paint_toasts(ctx, time);
+        if let Some(delay) = self.repaint_after(time) {
+            ctx.request_repaint_after(delay);
+        }
+    }
+}
+
+fn main() {
+    let cli = cli::Cli::parse();
+    if let Some(pieces) = cli.headless {
+        cli::run_headless(pieces, cli.seed);
+        return;
+    }
+    if cli.env {
+        if let Err(error) = cli::run_env(cli.mode.as_deref()) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(latency) = cli.rollback_test {
+        if let Err(error) = cli::run_rollback_test(latency, cli.seed) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(path) = &cli.tas {
+        let export = cli.export.clone().map(|path| export::ExportOptions {
+            path,
+            cell: cli.export_cell,
+            speed: cli.export_speed,
+        });
+        if let Err(error) = cli::run_tas(path, cli.mode.as_deref(), cli.seed, export) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let (mode, practice) = match cli.mode.as_deref() {
+        None => (Mode::Endless, false),
+        Some(name) if name.eq_ignore_ascii_case("practice") => (Mode::Endless, true),
+        Some(name) => match Mode::parse(name) {
+            Ok(mode) => (mode, false),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(2);
+            }
+        },
+    };
+
+    let config_path = cli.config.unwrap_or_else(settings::config_path);
+    let quests = Quests::load();
+    let (settings, config_message) = settings::load(&config_path, quests.stars());
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([
+                BOARD_WIDTH as f32 * settings.block_size + 2.0 * SIDE_PANEL_WIDTH + 40.0,
+                TOTAL_HEIGHT as f32 * settings.block_size + 80.0,
+            ])
+            .with_fullscreen(cli.fullscreen),
+        ..Default::default()
+    };
+
+    let mut app = TetrisApp {
+        settings,
+        config_path,
+        config_message,
+        mode,
+        practice,
+        seed: cli.seed,
+        log_events: cli.log_events,
+        achievements: Achievements::load(),
+        quests,
+        ..Default::default()
+    };
+    app.restart();
+    if cli.mode.is_none() {
+        app.screen = Screen::MainMenu;
+    }
     eframe::run_native(
         "Tetris",
         options,
-        Box::new(|_cc| Box::new(TetrisApp::default())),
+        Box::new(|_cc| Box::new(app)),
     ).unwrap();
-}
\ No newline at end of file
+}
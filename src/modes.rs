@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use crate::i18n::tr;
+use crate::scripting;
+
+pub const SPRINT_LINES: u32 = 40;
+pub const ULTRA_TIME: Duration = Duration::from_secs(120);
+pub const BTYPE_LINES: u32 = 25;
+
+// What ends a game other than topping out.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Goal {
+    Lines(u32),
+    Time(Duration),
+}
+
+#[derive(Clone, PartialEq, Default)]
+pub enum Mode {
+    #[default]
+    Endless,
+    Sprint,
+    Ultra,
+    // Clear 25 lines from a board that starts with garbage.
+    BType,
+    Script(String),
+}
+
+impl Mode {
+    pub fn name(&self) -> String {
+        match self {
+            Mode::Endless => "Endless".to_string(),
+            Mode::Sprint => "Sprint".to_string(),
+            Mode::Ultra => "Ultra".to_string(),
+            Mode::BType => "B-Type".to_string(),
+            Mode::Script(name) => name.clone(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        label(&self.name())
+    }
+
+    pub fn goal(&self) -> Option<Goal> {
+        match self {
+            Mode::Sprint => Some(Goal::Lines(SPRINT_LINES)),
+            Mode::Ultra => Some(Goal::Time(ULTRA_TIME)),
+            Mode::BType => Some(Goal::Lines(BTYPE_LINES)),
+            Mode::Endless | Mode::Script(_) => None,
+        }
+    }
+
+    pub fn script(&self) -> Option<&str> {
+        match self {
+            Mode::Script(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    // Built-in modes followed by whatever scripts are in mods/.
+    pub fn all() -> Vec<Mode> {
+        let mut modes = vec![Mode::Endless, Mode::Sprint, Mode::Ultra, Mode::BType];
+        modes.extend(scripting::available().into_iter().map(Mode::Script));
+        modes
+    }
+
+    pub fn parse(text: &str) -> Result<Mode, String> {
+        Mode::all()
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(text))
+            .ok_or_else(|| {
+                let names: Vec<String> = Mode::all().iter().map(Mode::name).collect();
+                format!("Unknown mode '{}', expected one of: {}", text, names.join(", "))
+            })
+    }
+}
+
+// Names stay in English wherever they're stored, in history, settings and
+// leaderboards; this is only for showing them. Script modes keep their file
+// names.
+pub fn label(name: &str) -> String {
+    match name {
+        "Endless" => tr("mode-endless"),
+        "Sprint" => tr("mode-sprint"),
+        "Ultra" => tr("mode-ultra"),
+        "B-Type" => tr("mode-btype"),
+        "Sequence" => tr("mode-sequence"),
+        "Practice" => tr("practice"),
+        _ => name.to_string(),
+    }
+}
@@ -0,0 +1,288 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Read, Write};
+
+pub const DEFAULT_PORT: u16 = 7878;
+// Longer lines end the connection rather than filling memory.
+pub const MAX_MESSAGE_BYTES: u64 = 64 * 1024;
+
+// Messages are JSON, one per line, in both directions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello { name: String },
+    ListRooms,
+    CreateRoom {
+        name: String,
+        #[serde(default)]
+        settings: RoomSettings,
+    },
+    JoinRoom { room: u32 },
+    // Watches a room's games without a seat. Spectators see both boards and
+    // the chat but have no inputs; Leave stops watching.
+    Spectate { room: u32 },
+    // Joins the first room waiting for an opponent, or opens a new one.
+    QuickMatch,
+    Leave,
+    // Only the host can change these, and only between games. Everyone has
+    // to ready up again afterwards.
+    ChangeSettings(RoomSettings),
+    // Only accepted while no match is running; it sticks across matches.
+    SetHandicap(Handicap),
+    // A game starts once the room is full and everyone in it is ready.
+    Ready { ready: bool },
+    Chat { text: String },
+    // Everything the sender did on a frame, sent for every frame in order,
+    // empty or not, so the opponent knows when it has all of them.
+    Input { frame: u32, actions: Vec<String> },
+    // The sender's game state hash after the frame, for the opponent to
+    // check against its copy of the sender's game.
+    StateHash { frame: u32, hash: u64 },
+    Garbage { lines: u32 },
+    Result(MatchResult),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Welcome { id: u32 },
+    Rooms { rooms: Vec<RoomInfo> },
+    // Sent to everyone in a room whenever anything about it changes.
+    Room(RoomState),
+    Chat { player: String, text: String },
+    // Both players use the seed so they get the same pieces, and each
+    // engine applies every player's handicap.
+    Start { seed: u64, seats: Vec<Seat> },
+    Input { player: String, frame: u32, actions: Vec<String> },
+    // Spectators get every seat's inputs instead, by seat since names can
+    // repeat. Joining mid-game replays the game's Start and inputs so far.
+    SeatInput { seat: usize, frame: u32, actions: Vec<String> },
+    StateHash { player: String, frame: u32, hash: u64 },
+    Garbage { lines: u32 },
+    // One game of the match is over.
+    Finished { winner: Option<String> },
+    // Someone has won most of the games; the tally starts over.
+    MatchOver { winner: String },
+    OpponentLeft,
+    Rejected { reason: String },
+    Error { message: String },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub id: u32,
+    pub name: String,
+    pub players: Vec<String>,
+    pub started: bool,
+    pub settings: RoomSettings,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoomState {
+    pub id: u32,
+    pub name: String,
+    pub settings: RoomSettings,
+    // The host comes first.
+    pub members: Vec<Member>,
+    pub started: bool,
+    #[serde(default)]
+    pub spectators: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Member {
+    // As given in Welcome.
+    pub id: u32,
+    pub name: String,
+    pub ready: bool,
+    // Games won so far this match.
+    pub wins: u32,
+    pub handicap: Handicap,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomSettings {
+    // A mode by its English name, as the game stores it.
+    pub mode: String,
+    // Games in a match; whoever wins most of them takes it.
+    pub best_of: u32,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            mode: "Endless".to_string(),
+            best_of: 1,
+        }
+    }
+}
+
+pub const MAX_BEST_OF: u32 = 9;
+pub const MAX_CHAT_CHARS: usize = 200;
+pub const MAX_NAME_CHARS: usize = 32;
+// More than any one clear can send, even with the attack multiplier.
+pub const MAX_GARBAGE_LINES: u32 = 40;
+
+impl RoomSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.mode.trim().is_empty() {
+            return Err("A room needs a mode".to_string());
+        }
+        if !(1..=MAX_BEST_OF).contains(&self.best_of) || self.best_of.is_multiple_of(2) {
+            return Err(format!("Best of must be an odd number up to {}", MAX_BEST_OF));
+        }
+        Ok(())
+    }
+
+    pub fn wins_needed(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+}
+
+// Evens out matches between players of different skill.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Handicap {
+    // Garbage rows the board starts with.
+    pub garbage_rows: u32,
+    // Scales how fast pieces fall.
+    pub gravity: f32,
+    // Scales the garbage this player sends.
+    pub attack: f32,
+    // Pieces shown in the next queue.
+    pub preview: u32,
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Self {
+            garbage_rows: 0,
+            gravity: 1.0,
+            attack: 1.0,
+            preview: MAX_PREVIEW,
+        }
+    }
+}
+
+pub const MAX_GARBAGE_ROWS: u32 = 15;
+pub const MAX_PREVIEW: u32 = 5;
+
+impl Handicap {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.garbage_rows > MAX_GARBAGE_ROWS {
+            return Err(format!("At most {} starting garbage rows", MAX_GARBAGE_ROWS));
+        }
+        if !(0.25..=4.0).contains(&self.gravity) {
+            return Err("Gravity multiplier must be between 0.25 and 4".to_string());
+        }
+        if !(0.0..=2.0).contains(&self.attack) {
+            return Err("Attack multiplier must be between 0 and 2".to_string());
+        }
+        if self.preview > MAX_PREVIEW {
+            return Err(format!("At most {} preview pieces", MAX_PREVIEW));
+        }
+        Ok(())
+    }
+
+    // Lines an attack sends once the attack multiplier is applied.
+    pub fn scale_attack(&self, lines: u32) -> u32 {
+        (lines as f32 * self.attack).round() as u32
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Seat {
+    pub name: String,
+    pub handicap: Handicap,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MatchResult {
+    pub score: u32,
+    pub lines: u32,
+    pub pieces: u32,
+    pub time_ms: u64,
+    pub topped_out: bool,
+}
+
+pub fn send<T: Serialize>(writer: &mut impl Write, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()
+}
+
+// Ok(None) once the other side has closed the connection.
+pub fn receive<T: DeserializeOwned>(reader: &mut impl BufRead) -> io::Result<Option<T>> {
+    let mut line = String::new();
+    let read = reader.by_ref().take(MAX_MESSAGE_BYTES).read_line(&mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read as u64 == MAX_MESSAGE_BYTES && !line.ends_with('\n') {
+        return Err(io::Error::other(format!("Messages are limited to {} bytes", MAX_MESSAGE_BYTES)));
+    }
+    serde_json::from_str(&line).map(Some).map_err(io::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handicap_limits() {
+        assert!(Handicap::default().validate().is_ok());
+        let extreme = Handicap {
+            garbage_rows: MAX_GARBAGE_ROWS,
+            gravity: 4.0,
+            attack: 0.0,
+            preview: 0,
+        };
+        assert!(extreme.validate().is_ok());
+        let bad = [
+            Handicap { garbage_rows: MAX_GARBAGE_ROWS + 1, ..Handicap::default() },
+            Handicap { gravity: 0.2, ..Handicap::default() },
+            Handicap { gravity: f32::NAN, ..Handicap::default() },
+            Handicap { attack: 2.5, ..Handicap::default() },
+            Handicap { attack: -1.0, ..Handicap::default() },
+            Handicap { preview: MAX_PREVIEW + 1, ..Handicap::default() },
+        ];
+        for handicap in bad {
+            assert!(handicap.validate().is_err(), "{:?}", handicap);
+        }
+    }
+
+    #[test]
+    fn scaled_attack() {
+        let attack = |attack| Handicap { attack, ..Handicap::default() };
+        assert_eq!(Handicap::default().scale_attack(4), 4);
+        assert_eq!(attack(0.5).scale_attack(4), 2);
+        assert_eq!(attack(1.5).scale_attack(3), 5);
+        assert_eq!(attack(0.0).scale_attack(10), 0);
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        let message = ClientMessage::SetHandicap(Handicap { garbage_rows: 3, ..Handicap::default() });
+        let mut buffer = Vec::new();
+        send(&mut buffer, &message).unwrap();
+        let received: Option<ClientMessage> = receive(&mut buffer.as_slice()).unwrap();
+        assert!(matches!(received, Some(ClientMessage::SetHandicap(handicap)) if handicap.garbage_rows == 3));
+        let message = ServerMessage::SeatInput { seat: 1, frame: 7, actions: vec!["hard_drop".to_string()] };
+        let mut buffer = Vec::new();
+        send(&mut buffer, &message).unwrap();
+        let received: Option<ServerMessage> = receive(&mut buffer.as_slice()).unwrap();
+        assert!(matches!(received, Some(ServerMessage::SeatInput { seat: 1, frame: 7, actions }) if actions == ["hard_drop"]));
+        let closed: Option<ClientMessage> = receive(&mut &b""[..]).unwrap();
+        assert!(closed.is_none());
+    }
+
+    #[test]
+    fn oversized_messages_are_refused() {
+        let mut line = format!("{{\"type\":\"hello\",\"name\":\"{}\"}}", "x".repeat(MAX_MESSAGE_BYTES as usize));
+        line.push('\n');
+        let received: io::Result<Option<ClientMessage>> = receive(&mut line.as_bytes());
+        assert!(received.is_err_and(|error| error.kind() != io::ErrorKind::InvalidData));
+    }
+}
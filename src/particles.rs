@@ -0,0 +1,117 @@
+use eframe::egui;
+use rand::rngs::ThreadRng;
+use rand::Rng;
+
+use crate::{PieceKind, BOARD_WIDTH};
+
+const MAX_PARTICLES: usize = 800;
+// Cells per second squared; positions are kept in board cells so the effects
+// don't care how large the board is drawn.
+const GRAVITY: f32 = 30.0;
+
+struct Particle {
+    pos: egui::Pos2,
+    vel: egui::Vec2,
+    color: egui::Color32,
+    size: f32,
+    life: f32,
+    max_life: f32,
+}
+
+#[derive(Default)]
+pub struct Particles {
+    particles: Vec<Particle>,
+    rng: ThreadRng,
+}
+
+impl Particles {
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    // Sparks kicked up from under the piece where it lands.
+    pub fn sparks(&mut self, cells: &[(i32, i32)]) {
+        for &(x, y) in cells {
+            if cells.contains(&(x, y + 1)) {
+                continue;
+            }
+            for _ in 0..4 {
+                let pos = egui::pos2(x as f32 + self.rng.gen_range(0.0..1.0), y as f32 + 1.0);
+                let vel = egui::vec2(self.rng.gen_range(-4.0..4.0), self.rng.gen_range(-8.0..-3.0));
+                let color = egui::Color32::from_rgb(255, self.rng.gen_range(200..=255), 150);
+                self.spawn(pos, vel, color, 0.12, 0.35);
+            }
+        }
+    }
+
+    // Fragments of each cleared cell flying out sideways.
+    pub fn burst(&mut self, y: usize, colors: &[egui::Color32]) {
+        for (x, &color) in colors.iter().enumerate() {
+            for _ in 0..3 {
+                let pos = egui::pos2(x as f32 + 0.5, y as f32 + 0.5);
+                let side = (x as f32 + 0.5 - BOARD_WIDTH as f32 / 2.0).signum();
+                let vel = egui::vec2(
+                    side * self.rng.gen_range(2.0..10.0),
+                    self.rng.gen_range(-10.0..2.0),
+                );
+                self.spawn(pos, vel, color, 0.2, 0.6);
+            }
+        }
+    }
+
+    pub fn confetti(&mut self) {
+        for _ in 0..150 {
+            let pos = egui::pos2(self.rng.gen_range(0.0..BOARD_WIDTH as f32), self.rng.gen_range(-2.0..0.0));
+            let vel = egui::vec2(self.rng.gen_range(-3.0..3.0), self.rng.gen_range(-6.0..4.0));
+            let color = PieceKind::ALL[self.rng.gen_range(0..PieceKind::ALL.len())].color();
+            self.spawn(pos, vel, color, 0.25, 1.6);
+        }
+    }
+
+    fn spawn(&mut self, pos: egui::Pos2, vel: egui::Vec2, color: egui::Color32, size: f32, life: f32) {
+        if self.particles.len() >= MAX_PARTICLES {
+            return;
+        }
+        let life = life * self.rng.gen_range(0.7..1.3);
+        self.particles.push(Particle {
+            pos,
+            vel,
+            color,
+            size,
+            life,
+            max_life: life,
+        });
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for particle in &mut self.particles {
+            particle.vel.y += GRAVITY * dt;
+            particle.pos += particle.vel * dt;
+            particle.life -= dt;
+        }
+        self.particles.retain(|particle| particle.life > 0.0);
+    }
+
+    // A flipped board draws every particle at its mirrored position.
+    pub fn paint(&self, painter: &egui::Painter, origin: egui::Pos2, cell: f32, flip: bool) {
+        for particle in &self.particles {
+            let alpha = particle.life / particle.max_life;
+            let mut pos = particle.pos;
+            if flip {
+                pos.x = BOARD_WIDTH as f32 - pos.x;
+            }
+            painter.rect_filled(
+                egui::Rect::from_center_size(
+                    origin + pos.to_vec2() * cell,
+                    egui::Vec2::splat(particle.size * cell),
+                ),
+                0.0,
+                particle.color.gamma_multiply(alpha),
+            );
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use eframe::egui;
+
+use crate::{BOARD_WIDTH, HIDDEN_ROWS};
+
+const MAX_POPUPS: usize = 24;
+const LIFE: f32 = 1.5;
+// Cells per second, slowing as the text fades.
+const RISE: f32 = 2.0;
+// Text sizes in cells: the first line of a popup, then the rest.
+const TITLE_SIZE: f32 = 0.8;
+const LINE_SIZE: f32 = 0.55;
+// How far from the sides a popup's centre stays, so wide text fits.
+const MARGIN: f32 = 2.5;
+
+struct Popup {
+    text: String,
+    color: egui::Color32,
+    pos: egui::Pos2,
+    size: f32,
+    life: f32,
+}
+
+// Floating text that rises from where points were scored and fades out.
+// Like particles, positions are in board cells.
+#[derive(Default)]
+pub struct Popups {
+    popups: Vec<Popup>,
+}
+
+impl Popups {
+    pub fn clear(&mut self) {
+        self.popups.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.popups.is_empty()
+    }
+
+    // Lines stack downwards from the first, which is drawn larger.
+    pub fn show(&mut self, at: egui::Pos2, lines: Vec<(String, egui::Color32)>) {
+        let mut pos = egui::pos2(
+            at.x.clamp(MARGIN, BOARD_WIDTH as f32 - MARGIN),
+            at.y.max(HIDDEN_ROWS as f32 + 1.0),
+        );
+        for (index, (text, color)) in lines.into_iter().enumerate() {
+            let size = if index == 0 { TITLE_SIZE } else { LINE_SIZE };
+            self.popups.push(Popup {
+                text,
+                color,
+                pos,
+                size,
+                life: LIFE,
+            });
+            pos.y += size + 0.1;
+        }
+        if self.popups.len() > MAX_POPUPS {
+            self.popups.drain(..self.popups.len() - MAX_POPUPS);
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for popup in &mut self.popups {
+            popup.pos.y -= RISE * popup.life / LIFE * dt;
+            popup.life -= dt;
+        }
+        self.popups.retain(|popup| popup.life > 0.0);
+    }
+
+    // Text isn't mirrored on a flipped board, only where it sits.
+    pub fn paint(&self, painter: &egui::Painter, origin: egui::Pos2, cell: f32, flip: bool) {
+        for popup in &self.popups {
+            let alpha = (popup.life / LIFE * 2.0).min(1.0);
+            let mut pos = popup.pos;
+            if flip {
+                pos.x = BOARD_WIDTH as f32 - pos.x;
+            }
+            painter.text(
+                origin + pos.to_vec2() * cell,
+                egui::Align2::CENTER_CENTER,
+                &popup.text,
+                egui::FontId::proportional(popup.size * cell),
+                popup.color.gamma_multiply(alpha),
+            );
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
+use std::error::Error;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Discord rate limits activity updates, and there's no point hammering the
+// socket while Discord isn't running.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(15);
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, PartialEq)]
+pub struct Activity {
+    pub app_id: String,
+    pub details: String,
+    pub state: String,
+    // Unix time the game started, for Discord's elapsed counter.
+    pub start: Option<u64>,
+}
+
+// Rich presence over Discord's local IPC socket. The connection lives on a
+// worker thread, so a Discord that stops answering never stalls a frame;
+// the game just posts what it's doing each frame.
+#[derive(Default)]
+pub struct Presence {
+    worker: Option<Sender<Option<Activity>>>,
+    last: Option<Activity>,
+}
+
+impl Presence {
+    pub fn update(&mut self, activity: Option<Activity>) {
+        if activity == self.last {
+            return;
+        }
+        self.last = activity.clone();
+        if self.worker.is_none() {
+            if activity.is_none() {
+                return;
+            }
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let mut worker = Worker::default();
+                loop {
+                    match receiver.recv_timeout(Duration::from_secs(1)) {
+                        Ok(activity) => worker.wanted = activity,
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                    worker.step();
+                }
+            });
+            self.worker = Some(sender);
+        }
+        if let Some(worker) = &self.worker {
+            let _ = worker.send(activity);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Worker {
+    wanted: Option<Activity>,
+    sent: Option<Activity>,
+    connection: Option<(String, DiscordIpcClient)>,
+    retry_at: Option<Instant>,
+    sent_at: Option<Instant>,
+}
+
+impl Worker {
+    fn step(&mut self) {
+        let app_id = self.wanted.as_ref().map(|activity| activity.app_id.clone());
+        if self.connection.as_ref().map(|(id, _)| id) != app_id.as_ref() {
+            self.disconnect();
+        }
+        let Some(app_id) = app_id else {
+            return;
+        };
+        if self.connection.is_none() {
+            if self.retry_at.is_some_and(|at| Instant::now() < at) {
+                return;
+            }
+            match connect(&app_id) {
+                Ok(client) => self.connection = Some((app_id, client)),
+                Err(_) => {
+                    self.retry_at = Some(Instant::now() + RETRY_INTERVAL);
+                    return;
+                }
+            }
+        }
+        let changed = self.wanted.as_ref().map(summary) != self.sent.as_ref().map(summary);
+        if !changed || self.sent_at.is_some_and(|at| at.elapsed() < UPDATE_INTERVAL) {
+            return;
+        }
+        let activity = self.wanted.clone();
+        if let Some((_, client)) = &mut self.connection {
+            if set_activity(client, activity.as_ref()).is_err() {
+                self.connection = None;
+                self.retry_at = Some(Instant::now() + RETRY_INTERVAL);
+                return;
+            }
+        }
+        self.sent = activity;
+        self.sent_at = Some(Instant::now());
+    }
+
+    // Clears the activity so it doesn't linger after presence is switched off.
+    fn disconnect(&mut self) {
+        if let Some((_, mut client)) = self.connection.take() {
+            let _ = set_activity(&mut client, None);
+            let _ = client.close();
+        }
+        self.sent = None;
+        self.sent_at = None;
+        self.retry_at = None;
+    }
+}
+
+// The start time moves slightly every frame, so it doesn't count as a change.
+fn summary(activity: &Activity) -> (&str, &str) {
+    (&activity.details, &activity.state)
+}
+
+fn connect(app_id: &str) -> Result<DiscordIpcClient, Box<dyn Error>> {
+    let mut client = DiscordIpcClient::new(app_id)?;
+    client.connect()?;
+    Ok(client)
+}
+
+fn set_activity(client: &mut DiscordIpcClient, activity: Option<&Activity>) -> Result<(), Box<dyn Error>> {
+    let Some(activity) = activity else {
+        return client.clear_activity();
+    };
+    let mut payload = activity::Activity::new().details(&activity.details).state(&activity.state);
+    if let Some(start) = activity.start {
+        payload = payload.timestamps(activity::Timestamps::new().start(start as i64));
+    }
+    client.set_activity(payload)
+}
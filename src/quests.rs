@@ -0,0 +1,264 @@
+use eframe::egui;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::history;
+use crate::i18n::{tr, tr_args};
+use crate::modes::Mode;
+use crate::skins::Palette;
+use crate::TetrisGame;
+
+const DAILY_QUESTS: usize = 3;
+const WEEKLY_QUESTS: usize = 2;
+// Stars a finished quest earns toward the palettes it unlocks.
+const DAILY_STARS: u32 = 1;
+const WEEKLY_STARS: u32 = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Period {
+    Daily,
+    Weekly,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Quest {
+    Lines(u32),
+    Tetrises(u32),
+    TSpinDoubles(u32),
+    Pieces(u32),
+    Games(u32),
+    // These have to happen within one game: a combo that long, a sprint
+    // under that many seconds and an Ultra score that high.
+    Combo(u32),
+    SprintUnder(u32),
+    UltraScore(u32),
+}
+
+impl Quest {
+    const KINDS: usize = 8;
+
+    // Weekly quests ask for several days' worth of the daily amounts.
+    fn generate(kind: usize, period: Period, rng: &mut StdRng) -> Quest {
+        let mut pick = |daily: &[u32], weekly: &[u32]| {
+            *match period {
+                Period::Daily => daily,
+                Period::Weekly => weekly,
+            }
+            .choose(rng)
+            .unwrap()
+        };
+        match kind {
+            0 => Quest::Lines(pick(&[40, 60, 100], &[300, 500])),
+            1 => Quest::Tetrises(pick(&[3, 5, 8], &[20, 30])),
+            2 => Quest::TSpinDoubles(pick(&[2, 3], &[8, 12])),
+            3 => Quest::Pieces(pick(&[300, 500], &[2000, 3000])),
+            4 => Quest::Games(pick(&[3, 5], &[20, 30])),
+            5 => Quest::Combo(pick(&[4, 5, 6], &[8, 10])),
+            6 => Quest::SprintUnder(pick(&[240, 180], &[120, 90])),
+            _ => Quest::UltraScore(pick(&[10_000, 20_000], &[40_000, 60_000])),
+        }
+    }
+
+    pub fn target(self) -> u32 {
+        match self {
+            Quest::Lines(count)
+            | Quest::Tetrises(count)
+            | Quest::TSpinDoubles(count)
+            | Quest::Pieces(count)
+            | Quest::Games(count) => count,
+            Quest::Combo(_) | Quest::SprintUnder(_) | Quest::UltraScore(_) => 1,
+        }
+    }
+
+    // How far one game goes toward the target.
+    fn progress(self, mode: &Mode, game: &TetrisGame) -> u32 {
+        let stats = &game.stats;
+        match self {
+            Quest::Lines(_) => stats.lines,
+            Quest::Tetrises(_) => stats.clears[3],
+            Quest::TSpinDoubles(_) => stats.tspin_doubles,
+            Quest::Pieces(_) => stats.pieces,
+            Quest::Games(_) => 1,
+            Quest::Combo(combo) => (stats.max_combo >= combo) as u32,
+            Quest::SprintUnder(seconds) => {
+                (*mode == Mode::Sprint && game.finished && stats.time < Duration::from_secs(seconds as u64)) as u32
+            }
+            Quest::UltraScore(score) => (*mode == Mode::Ultra && game.score >= score) as u32,
+        }
+    }
+
+    pub fn description(self) -> String {
+        match self {
+            Quest::Lines(count) => tr_args("quest-lines", &[("count", &count)]),
+            Quest::Tetrises(count) => tr_args("quest-tetrises", &[("count", &count)]),
+            Quest::TSpinDoubles(count) => tr_args("quest-tspin-doubles", &[("count", &count)]),
+            Quest::Pieces(count) => tr_args("quest-pieces", &[("count", &count)]),
+            Quest::Games(count) => tr_args("quest-games", &[("count", &count)]),
+            Quest::Combo(combo) => tr_args("quest-combo", &[("combo", &combo)]),
+            Quest::SprintUnder(seconds) => {
+                tr_args("quest-sprint", &[("time", &format!("{}:{:02}", seconds / 60, seconds % 60))])
+            }
+            Quest::UltraScore(score) => tr_args("quest-ultra", &[("score", &score)]),
+        }
+    }
+}
+
+impl Period {
+    fn count(self) -> usize {
+        match self {
+            Period::Daily => DAILY_QUESTS,
+            Period::Weekly => WEEKLY_QUESTS,
+        }
+    }
+
+    fn stars(self) -> u32 {
+        match self {
+            Period::Daily => DAILY_STARS,
+            Period::Weekly => WEEKLY_STARS,
+        }
+    }
+
+    // Days and weeks count from the Unix epoch in UTC, with weeks starting
+    // on Monday.
+    fn current(self) -> i64 {
+        let days = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs() / 86400) as i64;
+        match self {
+            Period::Daily => days,
+            Period::Weekly => (days + 3).div_euclid(7),
+        }
+    }
+
+    // The same day or week always gives the same quests, without any two
+    // of a kind.
+    fn quests(self, number: i64) -> Vec<Quest> {
+        let mut rng = StdRng::seed_from_u64((number as u64).wrapping_mul(2) + (self == Period::Weekly) as u64);
+        let mut kinds: Vec<usize> = (0..Quest::KINDS).collect();
+        kinds.shuffle(&mut rng);
+        kinds.truncate(self.count());
+        kinds.into_iter().map(|kind| Quest::generate(kind, self, &mut rng)).collect()
+    }
+}
+
+// Progress on the current day's and week's quests, and the stars earned
+// from every quest so far. Progress starts over when the period changes.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Saved {
+    day: i64,
+    daily: Vec<u32>,
+    week: i64,
+    weekly: Vec<u32>,
+    stars: u32,
+}
+
+#[derive(Default)]
+pub struct Quests {
+    saved: Saved,
+}
+
+fn path() -> PathBuf {
+    history::data_dir().join("quests.json")
+}
+
+impl Quests {
+    // A missing or unreadable file just means no progress yet.
+    pub fn load() -> Self {
+        let saved = fs::read_to_string(path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self { saved }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&self.saved)?)
+    }
+
+    pub fn stars(&self) -> u32 {
+        self.saved.stars
+    }
+
+    // The period's quests with progress on each, starting the progress over
+    // if the day or week has turned.
+    fn period(&mut self, period: Period) -> (Vec<Quest>, &mut Vec<u32>) {
+        let current = period.current();
+        let (number, progress) = match period {
+            Period::Daily => (&mut self.saved.day, &mut self.saved.daily),
+            Period::Weekly => (&mut self.saved.week, &mut self.saved.weekly),
+        };
+        if *number != current {
+            *number = current;
+            progress.clear();
+        }
+        progress.resize(period.count(), 0);
+        (period.quests(current), progress)
+    }
+
+    // Counts a finished game toward every quest, returning the ones it
+    // completed.
+    pub fn record(&mut self, mode: &Mode, game: &TetrisGame) -> Vec<Quest> {
+        let mut completed = Vec::new();
+        for period in [Period::Daily, Period::Weekly] {
+            let (quests, progress) = self.period(period);
+            let mut stars = 0;
+            for (quest, done) in quests.into_iter().zip(progress.iter_mut()) {
+                if *done >= quest.target() {
+                    continue;
+                }
+                *done = (*done + quest.progress(mode, game)).min(quest.target());
+                if *done >= quest.target() {
+                    stars += period.stars();
+                    completed.push(quest);
+                }
+            }
+            self.saved.stars += stars;
+        }
+        if let Err(error) = self.save() {
+            eprintln!("Could not save quests: {}", error);
+        }
+        completed
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        for (period, heading) in [(Period::Daily, "quests-daily"), (Period::Weekly, "quests-weekly")] {
+            ui.strong(tr(heading));
+            let (quests, progress) = self.period(period);
+            egui::Grid::new(heading).num_columns(2).show(ui, |ui| {
+                for (quest, &done) in quests.iter().zip(progress.iter()) {
+                    let color = if done >= quest.target() { egui::Color32::GRAY } else { ui.visuals().text_color() };
+                    ui.colored_label(color, quest.description());
+                    ui.add(
+                        egui::ProgressBar::new(done as f32 / quest.target() as f32)
+                            .desired_width(100.0)
+                            .text(format!("{}/{}", done, quest.target())),
+                    );
+                    ui.end_row();
+                }
+            });
+        }
+        let stars = self.stars();
+        ui.label(tr_args("quests-stars", &[("stars", &stars)]));
+        let next = Palette::ALL
+            .into_iter()
+            .filter(|palette| palette.stars_needed() > stars)
+            .min_by_key(|palette| palette.stars_needed());
+        if let Some(palette) = next {
+            ui.label(tr_args(
+                "quests-next-palette",
+                &[("palette", &palette.name()), ("stars", &palette.stars_needed())],
+            ));
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::tr;
+use crate::PieceKind;
+
+const TGM_ROLLS: usize = 4;
+// Memoryless drought protection hands out a piece once it has been missing
+// for this many draws.
+const DROUGHT_LIMIT: u32 = 12;
+
+// Generators draw from the game's rng so seeded games stay repeatable.
+pub(crate) trait PieceGenerator {
+    fn next(&mut self, rng: &mut StdRng) -> PieceKind;
+    fn clone_box(&self) -> Box<dyn PieceGenerator>;
+}
+
+impl Clone for Box<dyn PieceGenerator> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Randomizer {
+    #[default]
+    Bag7,
+    Bag14,
+    Classic,
+    Tgm,
+    Memoryless,
+}
+
+impl Randomizer {
+    pub const ALL: [Randomizer; 5] = [
+        Randomizer::Bag7,
+        Randomizer::Bag14,
+        Randomizer::Classic,
+        Randomizer::Tgm,
+        Randomizer::Memoryless,
+    ];
+
+    pub fn name(self) -> String {
+        tr(match self {
+            Randomizer::Bag7 => "randomizer-bag7",
+            Randomizer::Bag14 => "randomizer-bag14",
+            Randomizer::Classic => "randomizer-classic",
+            Randomizer::Tgm => "randomizer-tgm",
+            Randomizer::Memoryless => "randomizer-memoryless",
+        })
+    }
+
+    pub fn generator(self) -> Box<dyn PieceGenerator> {
+        match self {
+            Randomizer::Bag7 => Box::new(Bag::new(1)),
+            Randomizer::Bag14 => Box::new(Bag::new(2)),
+            Randomizer::Classic => Box::new(Classic),
+            Randomizer::Tgm => Box::new(Tgm::default()),
+            Randomizer::Memoryless => Box::new(Memoryless::default()),
+        }
+    }
+}
+
+// Shuffled bags holding every piece `copies` times.
+#[derive(Clone)]
+struct Bag {
+    copies: usize,
+    pieces: Vec<PieceKind>,
+}
+
+impl Bag {
+    fn new(copies: usize) -> Self {
+        Self {
+            copies,
+            pieces: Vec::new(),
+        }
+    }
+}
+
+impl PieceGenerator for Bag {
+    fn next(&mut self, rng: &mut StdRng) -> PieceKind {
+        if self.pieces.is_empty() {
+            for _ in 0..self.copies {
+                self.pieces.extend(PieceKind::ALL);
+            }
+            self.pieces.shuffle(rng);
+        }
+        self.pieces.pop().unwrap()
+    }
+
+    fn clone_box(&self) -> Box<dyn PieceGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Clone)]
+struct Classic;
+
+impl PieceGenerator for Classic {
+    fn next(&mut self, rng: &mut StdRng) -> PieceKind {
+        PieceKind::ALL[rng.gen_range(0..PieceKind::ALL.len())]
+    }
+
+    fn clone_box(&self) -> Box<dyn PieceGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+// TGM1 style: reroll up to four times to avoid the last four pieces. The
+// history starts full of Z and the first piece is never S, Z or O.
+#[derive(Clone)]
+struct Tgm {
+    history: [PieceKind; 4],
+    first: bool,
+}
+
+impl Default for Tgm {
+    fn default() -> Self {
+        Self {
+            history: [PieceKind::Z; 4],
+            first: true,
+        }
+    }
+}
+
+impl PieceGenerator for Tgm {
+    fn next(&mut self, rng: &mut StdRng) -> PieceKind {
+        let kind = if self.first {
+            self.first = false;
+            *[PieceKind::I, PieceKind::J, PieceKind::L, PieceKind::T].choose(rng).unwrap()
+        } else {
+            let mut kind = PieceKind::ALL[rng.gen_range(0..PieceKind::ALL.len())];
+            for _ in 1..TGM_ROLLS {
+                if !self.history.contains(&kind) {
+                    break;
+                }
+                kind = PieceKind::ALL[rng.gen_range(0..PieceKind::ALL.len())];
+            }
+            kind
+        };
+        self.history.rotate_right(1);
+        self.history[0] = kind;
+        kind
+    }
+
+    fn clone_box(&self) -> Box<dyn PieceGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Clone, Default)]
+struct Memoryless {
+    // Draws since each piece, in PieceKind::ALL order.
+    droughts: [u32; 7],
+}
+
+impl PieceGenerator for Memoryless {
+    fn next(&mut self, rng: &mut StdRng) -> PieceKind {
+        let longest = (0..self.droughts.len()).max_by_key(|&i| self.droughts[i]).unwrap();
+        let index = if self.droughts[longest] >= DROUGHT_LIMIT {
+            longest
+        } else {
+            rng.gen_range(0..PieceKind::ALL.len())
+        };
+        for drought in &mut self.droughts {
+            *drought += 1;
+        }
+        self.droughts[index] = 0;
+        PieceKind::ALL[index]
+    }
+
+    fn clone_box(&self) -> Box<dyn PieceGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn draw(randomizer: Randomizer, seed: u64, count: usize) -> Vec<PieceKind> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut generator = randomizer.generator();
+        (0..count).map(|_| generator.next(&mut rng)).collect()
+    }
+
+    fn counts(pieces: &[PieceKind]) -> [usize; 7] {
+        let mut counts = [0; 7];
+        for &kind in pieces {
+            counts[kind as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn seven_bag_windows_are_permutations() {
+        for seed in 0..20 {
+            for bag in draw(Randomizer::Bag7, seed, 700).chunks(7) {
+                assert_eq!(counts(bag), [1; 7], "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn fourteen_bag_holds_two_of_each() {
+        for seed in 0..20 {
+            for bag in draw(Randomizer::Bag14, seed, 1400).chunks(14) {
+                assert_eq!(counts(bag), [2; 7], "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_pieces() {
+        for randomizer in Randomizer::ALL {
+            assert_eq!(draw(randomizer, 42, 200), draw(randomizer, 42, 200));
+        }
+    }
+
+    // Rerolls make repeats rare without ruling them out: a piece only comes
+    // back straight away if every roll lands in the history.
+    #[test]
+    fn tgm_history_avoids_repeats() {
+        let mut repeats = 0;
+        let mut classic_repeats = 0;
+        for seed in 0..20 {
+            let pieces = draw(Randomizer::Tgm, seed, 500);
+            assert!(matches!(pieces[0], PieceKind::I | PieceKind::J | PieceKind::L | PieceKind::T));
+            repeats += pieces.windows(2).filter(|pair| pair[0] == pair[1]).count();
+            let classic = draw(Randomizer::Classic, seed, 500);
+            classic_repeats += classic.windows(2).filter(|pair| pair[0] == pair[1]).count();
+        }
+        assert!(repeats * 4 < classic_repeats, "{} repeats against {} without a history", repeats, classic_repeats);
+    }
+
+    #[test]
+    fn memoryless_droughts_end() {
+        let pieces = draw(Randomizer::Memoryless, 7, 5000);
+        for kind in PieceKind::ALL {
+            let mut since = 0;
+            for &piece in &pieces {
+                since = if piece == kind { 0 } else { since + 1 };
+                assert!(since <= DROUGHT_LIMIT as usize + PieceKind::ALL.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use std::collections::HashMap;
+
+use tetorisu::net::{ClientMessage, Handicap, MatchResult, Seat};
+
+use crate::modes::Mode;
+use crate::randomizer::Randomizer;
+use crate::settings::Settings;
+use crate::tas::{self, Input, FRAME};
+use crate::{BlockType, GameEvent, TetrisGame, BOARD_HEIGHT, BOARD_WIDTH};
+
+// Local inputs take effect this many frames after they are made, which hides
+// that much latency without rolling anything back. Both sides use the same
+// delay, so neither has inputs on the first frames.
+pub const INPUT_DELAY: u32 = 2;
+// How far play may run ahead of the opponent's last known frame before it
+// waits for them, and so the most frames a rollback replays.
+pub const MAX_PREDICTION: u32 = 12;
+// Frames between state hashes sent for desync checks.
+const HASH_INTERVAL: u32 = 60;
+
+// Both sides have to build both boards alike, so none of a player's own
+// settings come into it: standard timings and kicks, the 7-bag, the match
+// seed and the seat's handicap.
+pub fn versus_game(seed: u64, mode: &Mode, handicap: Handicap) -> TetrisGame {
+    let board = vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+    let mut game = TetrisGame::new(None, board, Some(seed), Randomizer::default());
+    game.start_mode(mode, &Settings::default());
+    game.apply_handicap(handicap, seed);
+    game.start_countdown();
+    game
+}
+
+// Both boards step together, since garbage passes between them.
+#[derive(Clone)]
+struct Boards {
+    local: TetrisGame,
+    remote: TetrisGame,
+}
+
+impl Boards {
+    fn step(&mut self, local: &[Input], remote: &[Input]) {
+        for &input in local {
+            tas::apply(&mut self.local, input);
+        }
+        for &input in remote {
+            tas::apply(&mut self.remote, input);
+        }
+        self.local.tick(FRAME);
+        self.remote.tick(FRAME);
+        let to_remote = self.local.garbage.take_sent();
+        let to_local = self.remote.garbage.take_sent();
+        self.remote.garbage.receive(to_remote);
+        self.local.garbage.receive(to_local);
+    }
+
+    fn clear_events(&mut self) {
+        self.local.events.clear();
+        self.remote.events.clear();
+    }
+}
+
+// Rollback for versus play. Each side runs both boards: its own on its own
+// inputs, and the opponent's on theirs as they arrive. Frames the opponent
+// hasn't been heard from yet are played as if they did nothing, which is
+// what most frames hold. When their inputs for such a frame turn out to
+// have something in them, both boards go back to the last frame known for
+// sure and play forward again, so the guess is corrected within a frame of
+// the news arriving.
+pub struct Session {
+    // The next frame to play.
+    frame: u32,
+    // The boards after every frame both sides' inputs are known for, up to
+    // confirmed_frame, and the same run on to the present with guesses.
+    confirmed: Boards,
+    confirmed_frame: u32,
+    predicted: Boards,
+    // The first frame played on a guess, if any still stand.
+    guessed_from: Option<u32>,
+    // Frames whose events have been taken.
+    shown: u32,
+    local: Vec<Vec<Input>>,
+    remote: Vec<Vec<Input>>,
+    // Hashes of the opponent's board at checkpoints, ours and theirs, kept
+    // until both are in.
+    checks: HashMap<u32, (Option<u64>, Option<u64>)>,
+    desync: Option<u32>,
+    outgoing: Vec<ClientMessage>,
+    rollbacks: u32,
+    replayed: u64,
+}
+
+impl Session {
+    // Both games must be set up the same way on both sides, from the seed and
+    // handicaps the server sends at the start of the match.
+    pub fn new(local: TetrisGame, remote: TetrisGame) -> Self {
+        let boards = Boards { local, remote };
+        Self {
+            frame: 0,
+            confirmed: boards.clone(),
+            confirmed_frame: 0,
+            predicted: boards,
+            guessed_from: None,
+            shown: 0,
+            local: vec![Vec::new(); INPUT_DELAY as usize],
+            remote: vec![Vec::new(); INPUT_DELAY as usize],
+            checks: HashMap::new(),
+            desync: None,
+            outgoing: Vec::new(),
+            rollbacks: 0,
+            replayed: 0,
+        }
+    }
+
+    pub fn local(&self) -> &TetrisGame {
+        &self.predicted.local
+    }
+
+    pub fn remote(&self) -> &TetrisGame {
+        &self.predicted.remote
+    }
+
+    // Events from the frames played since the last call, on each shown
+    // board. A rollback replays them, but drops those of frames already
+    // taken, so effects already shown don't play twice.
+    pub fn take_events(&mut self) -> (Vec<GameEvent>, Vec<GameEvent>) {
+        self.shown = self.frame;
+        let local = std::mem::take(&mut self.predicted.local.events);
+        (local, std::mem::take(&mut self.predicted.remote.events))
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn confirmed_frame(&self) -> u32 {
+        self.confirmed_frame
+    }
+
+    // Whether play is held up until the opponent's inputs catch up.
+    pub fn waiting(&self) -> bool {
+        self.frame >= self.confirmed_frame + MAX_PREDICTION
+    }
+
+    // The first checkpoint where the two sides' copies of a board differed.
+    pub fn desync(&self) -> Option<u32> {
+        self.desync
+    }
+
+    // Lines our board has sent up to the confirmed frame, which no rollback
+    // can take back.
+    pub fn sent(&self) -> u32 {
+        self.confirmed.local.garbage.total_sent()
+    }
+
+    // Our result, once the end of our game is confirmed, so no rollback can
+    // change it after it's reported.
+    pub fn result(&self) -> Option<MatchResult> {
+        let game = &self.confirmed.local;
+        game.game_over.then(|| MatchResult {
+            score: game.score,
+            lines: game.stats.lines,
+            pieces: game.stats.pieces,
+            time_ms: game.stats.time.as_millis() as u64,
+            topped_out: game.top_out.is_some(),
+        })
+    }
+
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    pub fn replayed(&self) -> u64 {
+        self.replayed
+    }
+
+    // Messages for the server, in the order they must be sent.
+    pub fn outgoing(&mut self) -> Vec<ClientMessage> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    // Plays one frame, taking this frame's inputs to apply INPUT_DELAY frames
+    // from now. Returns false without playing or taking the inputs when too
+    // far ahead of the opponent; call again next frame.
+    pub fn advance(&mut self, inputs: &[Input]) -> bool {
+        if self.waiting() {
+            return false;
+        }
+        self.outgoing.push(ClientMessage::Input {
+            frame: self.frame + INPUT_DELAY,
+            actions: inputs.iter().map(|input| input.name().to_string()).collect(),
+        });
+        self.local.push(inputs.to_vec());
+        let frame = self.frame as usize;
+        let remote = match self.remote.get(frame) {
+            Some(remote) => remote.as_slice(),
+            None => {
+                self.guessed_from.get_or_insert(self.frame);
+                &[]
+            }
+        };
+        self.predicted.clear_events();
+        self.predicted.step(&self.local[frame], remote);
+        self.frame += 1;
+        self.confirm();
+        true
+    }
+
+    // The opponent's inputs for one frame, which must come in order.
+    pub fn receive_input(&mut self, frame: u32, actions: &[String]) -> Result<(), String> {
+        if frame as usize != self.remote.len() {
+            return Err(format!("expected input for frame {}, got frame {}", self.remote.len(), frame));
+        }
+        let inputs = actions.iter().map(|action| Input::parse(action)).collect::<Result<_, _>>()?;
+        self.remote.push(inputs);
+        self.confirm();
+        Ok(())
+    }
+
+    // The opponent's hash of their own board, to check against our copy.
+    pub fn receive_hash(&mut self, frame: u32, hash: u64) {
+        self.checks.entry(frame).or_default().1 = Some(hash);
+        self.check(frame);
+    }
+
+    fn check(&mut self, frame: u32) {
+        if let Some(&(Some(ours), Some(theirs))) = self.checks.get(&frame) {
+            self.checks.remove(&frame);
+            if ours != theirs && self.desync.is_none() {
+                self.desync = Some(frame);
+            }
+        }
+    }
+
+    // Moves the confirmed boards up to the newest frame both sides' inputs
+    // are known for, then replays the rest if a guess was wrong.
+    fn confirm(&mut self) {
+        let target = (self.remote.len() as u32).min(self.frame);
+        let wrong = self
+            .guessed_from
+            .is_some_and(|from| (from..target).any(|frame| !self.remote[frame as usize].is_empty()));
+        while self.confirmed_frame < target {
+            let frame = self.confirmed_frame as usize;
+            self.confirmed.clear_events();
+            self.confirmed.step(&self.local[frame], &self.remote[frame]);
+            self.confirmed_frame += 1;
+            if self.confirmed_frame.is_multiple_of(HASH_INTERVAL) {
+                let frame = self.confirmed_frame;
+                let hash = self.confirmed.local.state_hash();
+                self.outgoing.push(ClientMessage::StateHash { frame, hash });
+                self.checks.entry(frame).or_default().0 = Some(self.confirmed.remote.state_hash());
+                self.check(frame);
+            }
+        }
+        if wrong {
+            self.rollbacks += 1;
+            // The confirmed boards hold the events of the last frame they
+            // played, which may not have been taken yet.
+            self.predicted = self.confirmed.clone();
+            if target <= self.shown {
+                self.predicted.clear_events();
+            }
+            for frame in target..self.frame {
+                self.predicted.step(&self.local[frame as usize], &[]);
+                if frame < self.shown {
+                    self.predicted.clear_events();
+                }
+                self.replayed += 1;
+            }
+        }
+        self.guessed_from = self.guessed_from.map(|from| from.max(target)).filter(|&from| from < self.frame);
+    }
+}
+
+// Watching a match from outside it: both boards, played only as far as
+// both players' inputs have arrived, so nothing is ever guessed or rolled
+// back.
+pub struct Spectator {
+    names: [String; 2],
+    boards: Boards,
+    frame: u32,
+    inputs: [Vec<Vec<Input>>; 2],
+}
+
+impl Spectator {
+    pub fn new(seed: u64, mode: &Mode, seats: &[Seat]) -> Self {
+        let name = |seat: usize| seats.get(seat).map_or_else(String::new, |seat| seat.name.clone());
+        let handicap = |seat: usize| seats.get(seat).map_or_else(Handicap::default, |seat| seat.handicap);
+        Self {
+            names: [name(0), name(1)],
+            boards: Boards {
+                local: versus_game(seed, mode, handicap(0)),
+                remote: versus_game(seed, mode, handicap(1)),
+            },
+            frame: 0,
+            inputs: std::array::from_fn(|_| vec![Vec::new(); INPUT_DELAY as usize]),
+        }
+    }
+
+    // Each seat's inputs must come in order, as players send them.
+    pub fn receive_input(&mut self, seat: usize, frame: u32, actions: &[String]) -> Result<(), String> {
+        let inputs = self.inputs.get_mut(seat).ok_or_else(|| format!("no seat {}", seat))?;
+        if frame != inputs.len() as u32 {
+            return Err(format!("got frame {}, expected {}", frame, inputs.len()));
+        }
+        inputs.push(actions.iter().map(|action| Input::parse(action)).collect::<Result<_, _>>()?);
+        let known = self.inputs.iter().map(|inputs| inputs.len() as u32).min().unwrap_or(0);
+        while self.frame < known {
+            let frame = self.frame as usize;
+            self.boards.step(&self.inputs[0][frame], &self.inputs[1][frame]);
+            self.boards.clear_events();
+            self.frame += 1;
+        }
+        Ok(())
+    }
+
+    // Each seat's player and board.
+    pub fn boards(&self) -> [(&str, &TetrisGame); 2] {
+        [(&self.names[0], &self.boards.local), (&self.names[1], &self.boards.remote)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotation::Turn;
+
+    const SEED: u64 = 11;
+
+    fn session() -> Session {
+        let game = || versus_game(SEED, &Mode::Endless, Handicap::default());
+        Session::new(game(), game())
+    }
+
+    // A little of everything, different for each side.
+    fn inputs(side: u32, frame: u32) -> Vec<Input> {
+        match (frame + side * 7) % 24 {
+            3 => vec![Input::MoveLeft],
+            9 => vec![Input::MoveRight, Input::Rotate(Turn::Cw)],
+            15 if side == 1 => vec![Input::Hold],
+            20 => vec![Input::HardDrop],
+            _ => Vec::new(),
+        }
+    }
+
+    fn deliver(to: &mut Session, messages: Vec<ClientMessage>) {
+        for message in messages {
+            match message {
+                ClientMessage::Input { frame, actions } => to.receive_input(frame, &actions).unwrap(),
+                ClientMessage::StateHash { frame, hash } => to.receive_hash(frame, hash),
+                _ => {}
+            }
+        }
+    }
+
+    // Two sides play each other with their messages held up by a few
+    // frames, then the last of them arrive.
+    fn play(frames: u32, lag: usize) -> (Session, Session) {
+        let mut sides = [session(), session()];
+        let mut in_flight: [Vec<Vec<ClientMessage>>; 2] = Default::default();
+        for _ in 0..frames {
+            for side in 0..2 {
+                let frame = sides[side].frame();
+                sides[side].advance(&inputs(side as u32, frame));
+                in_flight[side].push(sides[side].outgoing());
+                if in_flight[side].len() > lag {
+                    let messages = in_flight[side].remove(0);
+                    deliver(&mut sides[1 - side], messages);
+                }
+            }
+        }
+        for side in 0..2 {
+            for messages in std::mem::take(&mut in_flight[side]) {
+                deliver(&mut sides[1 - side], messages);
+            }
+        }
+        let [first, second] = sides;
+        (first, second)
+    }
+
+    #[test]
+    fn both_sides_agree() {
+        let (first, second) = play(600, 4);
+        assert_eq!(first.desync(), None);
+        assert_eq!(second.desync(), None);
+        assert!(first.rollbacks() > 0);
+        assert_eq!(first.local().state_hash(), second.remote().state_hash());
+        assert_eq!(first.remote().state_hash(), second.local().state_hash());
+    }
+
+    #[test]
+    fn lag_does_not_change_the_game() {
+        let (slow, _) = play(300, 6);
+        let (fast, _) = play(300, 0);
+        assert_eq!(slow.local().state_hash(), fast.local().state_hash());
+        assert_eq!(slow.remote().state_hash(), fast.remote().state_hash());
+    }
+
+    #[test]
+    fn waits_for_the_opponent() {
+        let mut session = session();
+        let played = (0..MAX_PREDICTION * 2).filter(|_| session.advance(&[])).count() as u32;
+        assert_eq!(played, MAX_PREDICTION + INPUT_DELAY);
+        assert!(session.waiting());
+    }
+
+    #[test]
+    fn spectators_see_what_players_see() {
+        const FRAMES: u32 = 300;
+        let mut sides = [session(), session()];
+        let mut spectator = Spectator::new(SEED, &Mode::Endless, &[]);
+        for _ in 0..FRAMES {
+            for side in 0..2 {
+                let frame = sides[side].frame();
+                sides[side].advance(&inputs(side as u32, frame));
+                let messages = sides[side].outgoing();
+                for message in &messages {
+                    // Players send inputs ahead of the frames they play.
+                    if let ClientMessage::Input { frame, actions } = message {
+                        if *frame < FRAMES {
+                            spectator.receive_input(side, *frame, actions).unwrap();
+                        }
+                    }
+                }
+                deliver(&mut sides[1 - side], messages);
+            }
+        }
+        let [(_, first), (_, second)] = spectator.boards();
+        assert_eq!(first.state_hash(), sides[0].local().state_hash());
+        assert_eq!(second.state_hash(), sides[0].remote().state_hash());
+        assert!(spectator.receive_input(0, 0, &[]).is_err());
+        assert!(spectator.receive_input(2, FRAMES, &[]).is_err());
+    }
+
+    #[test]
+    fn mismatched_hash_is_a_desync() {
+        let mut session = session();
+        for frame in 0..HASH_INTERVAL {
+            session.advance(&[]);
+            session.receive_input(frame + INPUT_DELAY, &[]).unwrap();
+        }
+        assert_eq!(session.confirmed_frame(), HASH_INTERVAL);
+        let theirs = session.remote().state_hash();
+        session.receive_hash(HASH_INTERVAL, theirs ^ 1);
+        assert_eq!(session.desync(), Some(HASH_INTERVAL));
+    }
+
+    // Plays in step with an opponent who does nothing, until play starts and
+    // pieces are out, and returns the next frame.
+    fn warm_up(session: &mut Session) -> u32 {
+        let mut frame = 0;
+        while !session.local().countdown.is_zero() || frame % 60 != 0 {
+            session.advance(&[]);
+            session.receive_input(frame + INPUT_DELAY, &[]).unwrap();
+            session.take_events();
+            frame += 1;
+        }
+        frame
+    }
+
+    fn actions(input: Input) -> Vec<String> {
+        vec![input.name().to_string()]
+    }
+
+    #[test]
+    fn rollbacks_keep_effects_not_yet_shown() {
+        let mut session = session();
+        let frame = warm_up(&mut session);
+        // The drop lands on the first frame played without the opponent's
+        // inputs, and the rollback comes before its events are taken.
+        session.advance(&[Input::HardDrop]);
+        session.take_events();
+        session.advance(&[]);
+        session.take_events();
+        session.advance(&[]);
+        session.receive_input(frame + INPUT_DELAY, &actions(Input::MoveLeft)).unwrap();
+        assert_eq!(session.rollbacks(), 1);
+        let (local, _) = session.take_events();
+        assert!(local.iter().any(|event| matches!(event, GameEvent::HardDrop(_))));
+
+        // Frames already taken don't give their events again.
+        session.advance(&[]);
+        session.take_events();
+        session.receive_input(frame + INPUT_DELAY + 1, &actions(Input::MoveRight)).unwrap();
+        assert_eq!(session.rollbacks(), 2);
+        let (local, remote) = session.take_events();
+        assert!(local.is_empty() && remote.is_empty());
+    }
+
+    #[test]
+    fn results_wait_for_confirmation() {
+        const LAG: u32 = 5;
+        let mut session = session();
+        let mut frame = 0;
+        while !session.local().game_over && frame < 5000 {
+            session.advance(&[Input::HardDrop]);
+            if frame >= LAG {
+                session.receive_input(frame - LAG + INPUT_DELAY, &[]).unwrap();
+            }
+            frame += 1;
+        }
+        assert!(session.local().game_over);
+        assert!(session.result().is_none());
+        for remote in frame - LAG + INPUT_DELAY..frame + INPUT_DELAY {
+            session.receive_input(remote, &[]).unwrap();
+        }
+        let result = session.result().unwrap();
+        assert!(result.topped_out);
+        assert_eq!(result.pieces, session.local().stats.pieces);
+    }
+}
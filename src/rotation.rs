@@ -0,0 +1,384 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+use crate::sequence::piece_from_char;
+use crate::{history, PieceKind};
+
+// Custom tables are held to something a piece could plausibly need, so a
+// typo doesn't teleport it across the board.
+const MAX_KICKS: usize = 16;
+const MAX_OFFSET: i32 = 4;
+
+// Wall kicks, tried in order. Offsets are (x, y) with y pointing up, as
+// they're usually written; callers flip y for the board.
+type Kicks = Vec<(i32, i32)>;
+// One list per starting rotation, in Spawn, Right, Reverse, Left order.
+type Turns = [Kicks; 4];
+
+const JLSTZ_CW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+
+const JLSTZ_CCW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+
+const I_CW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+];
+
+const I_CCW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+];
+
+// 180 kicks aren't part of SRS; these are the ones most modern clients use,
+// shared by every piece but O.
+const HALF: [[(i32, i32); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+
+// SRS+ mirrors the I kicks so they push the same way whichever way it turns.
+const I_CW_PLUS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+];
+
+const I_CCW_PLUS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (2, 0), (2, -1), (-1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+];
+
+// ARS only ever tries one cell right, then one left, and the I never kicks.
+// Just the kicks: pieces still sit and turn as they do in SRS.
+const ARS: [(i32, i32); 3] = [(0, 0), (1, 0), (-1, 0)];
+
+const NO_KICKS: [(i32, i32); 1] = [(0, 0)];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Turn {
+    Cw,
+    Ccw,
+    Half,
+}
+
+impl Turn {
+    // Number of clockwise quarter turns.
+    pub fn quarters(self) -> u8 {
+        match self {
+            Turn::Cw => 1,
+            Turn::Half => 2,
+            Turn::Ccw => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Rotation {
+    Spawn,
+    Right,
+    Reverse,
+    Left,
+}
+
+impl Rotation {
+    const ALL: [Rotation; 4] = [Rotation::Spawn, Rotation::Right, Rotation::Reverse, Rotation::Left];
+
+    pub fn turned(self, turn: Turn) -> Rotation {
+        Self::ALL[(self as usize + turn.quarters() as usize) % 4]
+    }
+}
+
+// A full set of kicks, for every piece, turn and starting rotation.
+pub struct KickTable {
+    pub name: String,
+    // By piece in PieceKind::ALL order, then clockwise, counterclockwise and
+    // half turns.
+    pieces: [[Turns; 3]; 7],
+    // For each piece and turn, the kick, by its place in the lists, that
+    // makes a T-spin full even with a back corner open. In SRS it's the
+    // last, long kick that TST and fin setups rely on.
+    full_spin: [[Option<usize>; 3]; 7],
+}
+
+// A custom table in kicks/<name>.toml under the data directory. Pieces it
+// leaves out, and turns it leaves out of a piece, keep the base table's kicks:
+//
+//   base = "SRS"
+//
+//   [T]
+//   half = [
+//       [[0, 0], [0, 1]],    # from spawn
+//       [[0, 0], [1, 0]],    # from right
+//       [[0, 0], [0, -1]],   # from reverse
+//       [[0, 0], [-1, 0]],   # from left
+//   ]
+//   full_spin = { half = 1 }
+//
+// full_spin names the kick, counted from 0, that makes a T-spin full. A
+// turn whose kicks are replaced has none unless it's given again.
+#[derive(Deserialize)]
+struct TableFile {
+    base: Option<String>,
+    #[serde(flatten)]
+    pieces: BTreeMap<String, PieceFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PieceFile {
+    cw: Option<Vec<Kicks>>,
+    ccw: Option<Vec<Kicks>>,
+    half: Option<Vec<Kicks>>,
+    full_spin: Option<FullSpinFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FullSpinFile {
+    cw: Option<usize>,
+    ccw: Option<usize>,
+    half: Option<usize>,
+}
+
+pub fn kicks_dir() -> PathBuf {
+    history::data_dir().join("kicks")
+}
+
+// The built-in tables, then any custom ones.
+pub fn available() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(kicks_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .filter(|name| !KickTable::BUILT_IN.iter().any(|built_in| built_in.eq_ignore_ascii_case(name)))
+        .collect();
+    names.sort();
+    KickTable::BUILT_IN.iter().map(|name| name.to_string()).chain(names).collect()
+}
+
+impl KickTable {
+    pub const BUILT_IN: [&'static str; 3] = ["SRS", "SRS+", "ARS"];
+
+    // Every game starts out with this one, so it's only built once.
+    pub fn srs() -> Arc<KickTable> {
+        static SRS: OnceLock<Arc<KickTable>> = OnceLock::new();
+        SRS.get_or_init(|| Arc::new(Self::built_in("SRS").unwrap())).clone()
+    }
+
+    // A built-in table by name, or else a custom one, checked through.
+    pub fn load(name: &str) -> Result<Self, String> {
+        if let Some(table) = Self::built_in(name) {
+            return Ok(table);
+        }
+        let path = kicks_dir().join(format!("{}.toml", name));
+        let text = fs::read_to_string(&path).map_err(|reason| format!("{}: {}", path.display(), reason))?;
+        Self::parse(name, &text).map_err(|reason| format!("{}: {}", path.display(), reason))
+    }
+
+    fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let file: TableFile = toml::from_str(text).map_err(|reason| reason.to_string())?;
+        let base = file.base.as_deref().unwrap_or("SRS");
+        let mut table = Self::built_in(base).ok_or_else(|| {
+            format!("unknown base table '{}', expected one of {}", base, Self::BUILT_IN.join(", "))
+        })?;
+        table.name = name.to_string();
+        for (key, piece) in file.pieces {
+            let mut chars = key.chars();
+            let kind = match (chars.next().and_then(piece_from_char), chars.next()) {
+                (Some(kind), None) => kind,
+                _ => return Err(format!("unknown piece '{}', expected one of I, O, T, L, J, S, Z", key)),
+            };
+            let full_spin = piece.full_spin.map(|full| [full.cw, full.ccw, full.half]);
+            let turns = [("cw", piece.cw), ("ccw", piece.ccw), ("half", piece.half)];
+            for (turn, (name, kicks)) in turns.into_iter().enumerate() {
+                let error = |reason: String| format!("{}.{}: {}", key, name, reason);
+                if let Some(kicks) = kicks {
+                    table.pieces[kind as usize][turn] = validate(kicks).map_err(error)?;
+                    table.full_spin[kind as usize][turn] = None;
+                }
+                if let Some(index) = full_spin.and_then(|full| full[turn]) {
+                    let shortest = table.pieces[kind as usize][turn].iter().map(Vec::len).min().unwrap_or(0);
+                    if index >= shortest {
+                        return Err(error(format!("full_spin kick {} is past the end of a list", index)));
+                    }
+                    table.full_spin[kind as usize][turn] = Some(index);
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    fn built_in(name: &str) -> Option<Self> {
+        let name = *Self::BUILT_IN.iter().find(|built_in| built_in.eq_ignore_ascii_case(name))?;
+        let turns = |kind, turn| match (name, kind, turn) {
+            (_, PieceKind::O, _) | ("ARS", PieceKind::I, _) => lists([NO_KICKS; 4]),
+            ("ARS", _, _) => lists([ARS; 4]),
+            (_, _, Turn::Half) => lists(HALF),
+            ("SRS+", PieceKind::I, Turn::Cw) => lists(I_CW_PLUS),
+            ("SRS+", PieceKind::I, Turn::Ccw) => lists(I_CCW_PLUS),
+            (_, PieceKind::I, Turn::Cw) => lists(I_CW),
+            (_, PieceKind::I, Turn::Ccw) => lists(I_CCW),
+            (_, _, Turn::Cw) => lists(JLSTZ_CW),
+            (_, _, Turn::Ccw) => lists(JLSTZ_CCW),
+        };
+        // Only SRS's own quarter turns have the long kick; 180s and ARS's
+        // short kicks have nothing like it.
+        let full_spin = |kind, turn| match (name, kind, turn) {
+            ("ARS", _, _) | (_, PieceKind::O | PieceKind::I, _) | (_, _, Turn::Half) => None,
+            _ => Some(JLSTZ_CW[0].len() - 1),
+        };
+        Some(Self {
+            name: name.to_string(),
+            pieces: PieceKind::ALL.map(|kind| TURNS.map(|turn| turns(kind, turn))),
+            full_spin: PieceKind::ALL.map(|kind| TURNS.map(|turn| full_spin(kind, turn))),
+        })
+    }
+
+    // Whether any piece has kicks for a 180, rather than only turning in
+    // place or not at all.
+    pub fn has_half_kicks(&self) -> bool {
+        self.pieces.iter().any(|turns| turns[2].iter().any(|kicks| kicks.iter().any(|&kick| kick != (0, 0))))
+    }
+
+    pub fn kicks(&self, kind: PieceKind, from: Rotation, turn: Turn) -> &[(i32, i32)] {
+        &self.pieces[kind as usize][turn_index(turn)][from as usize]
+    }
+
+    // Whether a turn that needed this kick counts as a full T-spin.
+    pub fn is_full_spin_kick(&self, kind: PieceKind, turn: Turn, kick: usize) -> bool {
+        self.full_spin[kind as usize][turn_index(turn)] == Some(kick)
+    }
+}
+
+fn turn_index(turn: Turn) -> usize {
+    TURNS.iter().position(|&t| t == turn).unwrap()
+}
+
+const TURNS: [Turn; 3] = [Turn::Cw, Turn::Ccw, Turn::Half];
+
+fn lists<const N: usize>(kicks: [[(i32, i32); N]; 4]) -> Turns {
+    kicks.map(|kicks| kicks.to_vec())
+}
+
+fn validate(kicks: Vec<Kicks>) -> Result<Turns, String> {
+    let turns: Turns = kicks
+        .try_into()
+        .map_err(|kicks: Vec<Kicks>| format!("expected 4 lists, one per starting rotation, found {}", kicks.len()))?;
+    for kicks in &turns {
+        if kicks.is_empty() || kicks.len() > MAX_KICKS {
+            return Err(format!("each list needs 1 to {} kicks; [[0, 0]] means no kicks", MAX_KICKS));
+        }
+        if let Some((x, y)) = kicks.iter().find(|(x, y)| x.abs() > MAX_OFFSET || y.abs() > MAX_OFFSET) {
+            return Err(format!("kick ({}, {}) is more than {} cells away", x, y, MAX_OFFSET));
+        }
+    }
+    Ok(turns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockType, LastAction, Spin, TetrisGame, Tetromino, BOARD_WIDTH, TOTAL_HEIGHT};
+
+    #[test]
+    fn srs_marks_its_long_kick() {
+        let srs = KickTable::load("SRS").unwrap();
+        assert_eq!(srs.kicks(PieceKind::T, Rotation::Spawn, Turn::Cw)[4], (-1, -2));
+        assert!(srs.is_full_spin_kick(PieceKind::T, Turn::Cw, 4));
+        assert!(srs.is_full_spin_kick(PieceKind::T, Turn::Ccw, 4));
+        assert!(!srs.is_full_spin_kick(PieceKind::T, Turn::Cw, 3));
+        assert!(!srs.is_full_spin_kick(PieceKind::T, Turn::Half, 4));
+        assert!(KickTable::load("SRS+").unwrap().is_full_spin_kick(PieceKind::T, Turn::Cw, 4));
+        let ars = KickTable::load("ARS").unwrap();
+        assert!((0..5).all(|kick| !ars.is_full_spin_kick(PieceKind::T, Turn::Cw, kick)));
+    }
+
+    #[test]
+    fn custom_tables_build_on_their_base() {
+        let table = KickTable::parse("test", "[T]\nhalf = [[[0, 0], [0, 1]], [[0, 0]], [[0, 0]], [[0, 0]]]").unwrap();
+        assert_eq!(table.kicks(PieceKind::T, Rotation::Spawn, Turn::Half), [(0, 0), (0, 1)]);
+        assert_eq!(table.kicks(PieceKind::J, Rotation::Spawn, Turn::Half), HALF[0]);
+        assert!(table.is_full_spin_kick(PieceKind::T, Turn::Cw, 4));
+
+        // New kicks drop the base's long kick unless it's named again.
+        let cw = "cw = [[[0, 0], [1, 0]], [[0, 0], [1, 0]], [[0, 0], [1, 0]], [[0, 0], [1, 0]]]";
+        let table = KickTable::parse("test", &format!("[T]\n{}", cw)).unwrap();
+        assert!(!table.is_full_spin_kick(PieceKind::T, Turn::Cw, 4));
+        let table = KickTable::parse("test", &format!("[T]\n{}\nfull_spin = {{ cw = 1 }}", cw)).unwrap();
+        assert!(table.is_full_spin_kick(PieceKind::T, Turn::Cw, 1));
+    }
+
+    #[test]
+    fn malformed_tables_are_rejected() {
+        let four = |list: &str| format!("[T]\ncw = [{0}, {0}, {0}, {0}]", list);
+        let bad = [
+            "[T\ncw = 1".to_string(),
+            "base = \"TGM\"".to_string(),
+            "[Q]\ncw = []".to_string(),
+            "[T]\nspin = 1".to_string(),
+            "[T]\ncw = [[[0, 0]], [[0, 0]], [[0, 0]]]".to_string(),
+            four("[]"),
+            four("[[0, 5]]"),
+            four(&format!("[{}]", vec!["[0, 0]"; MAX_KICKS + 1].join(", "))),
+            "[T]\nfull_spin = { cw = 5 }".to_string(),
+            "[T]\nfull_spin = { sideways = 1 }".to_string(),
+        ];
+        for text in bad {
+            assert!(KickTable::parse("test", &text).is_err(), "{}", text);
+        }
+    }
+
+    // A TST slot three rows deep at column 3, with an overhang over it that
+    // leaves only SRS's long kick for a T turning clockwise from spawn.
+    #[test]
+    fn long_kick_into_a_tst_slot() {
+        let mut game = TetrisGame::default();
+        let floor = TOTAL_HEIGHT - 1;
+        let slot = 3;
+        for x in 0..BOARD_WIDTH {
+            for (y, open) in [(floor, x == slot), (floor - 1, x == slot || x == slot + 1), (floor - 2, x == slot)] {
+                if !open {
+                    game.board.set(x, y, BlockType::Garbage);
+                }
+            }
+        }
+        game.board.set(slot, floor - 4, BlockType::Garbage);
+        let mut piece = Tetromino::new(PieceKind::T);
+        piece.x = slot as i32;
+        piece.y = floor as i32 - 4;
+        game.current_piece = piece;
+
+        game.rotate_piece(Turn::Cw);
+        assert!(matches!(game.current_piece.last_action, LastAction::Rotate { turn: Turn::Cw, kick: 4 }));
+        assert_eq!(game.current_piece.rotation, Rotation::Right);
+        assert!(game.detect_spin() == Spin::Full);
+        game.hard_drop();
+        assert_eq!(game.stats.clears[2], 1);
+        assert_eq!(game.stats.tspins, 1);
+    }
+}
@@ -0,0 +1,279 @@
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope, AST};
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::board::Board;
+use crate::history;
+use crate::sequence::{piece_char, piece_from_char};
+use crate::{BlockType, PieceKind, TetrisGame, TopOut, BOARD_HEIGHT, BOARD_WIDTH, HIDDEN_ROWS};
+
+const HOOKS: [&str; 5] = ["on_start", "on_spawn", "on_lock", "on_clear", "on_tick"];
+
+// The slice of the game a script can see and change. It is copied in from
+// the game before each hook runs and written back afterwards.
+#[derive(Default)]
+struct Api {
+    board: Board,
+    score: u32,
+    lines: u32,
+    level: u32,
+    pieces: u32,
+    time: f64,
+    game_over: bool,
+    finished: bool,
+    incoming: u32,
+    // Lines the game has sent in all, and how many of those the script has
+    // already been told about.
+    sent: u32,
+    sent_read: u32,
+    message: Option<String>,
+}
+
+// A custom mode loaded from mods/<name>.rhai under the data directory. Hooks share a `this` map for
+// any state the mode wants to keep between calls.
+pub struct Script {
+    pub name: String,
+    engine: Engine,
+    ast: AST,
+    hooks: Vec<&'static str>,
+    state: Dynamic,
+    api: Rc<RefCell<Api>>,
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+pub fn mods_dir() -> PathBuf {
+    history::data_dir().join("mods")
+}
+
+pub fn available() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(mods_dir()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+impl Script {
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = mods_dir().join(format!("{}.rhai", name));
+        let source = fs::read_to_string(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Self::parse(name, &source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let api = Rc::new(RefCell::new(Api::default()));
+        let engine = engine(&api);
+        let ast = engine.compile(source).map_err(|error| error.to_string())?;
+        let hooks = HOOKS
+            .into_iter()
+            .filter(|hook| ast.iter_functions().any(|f| f.name == *hook))
+            .collect();
+        Ok(Self {
+            name: name.to_string(),
+            engine,
+            ast,
+            hooks,
+            state: Dynamic::from_map(Map::new()),
+            api,
+            message: None,
+            error: None,
+        })
+    }
+
+    pub fn on_start(&mut self, game: &mut TetrisGame) {
+        self.call(game, "on_start", ());
+    }
+
+    pub fn on_spawn(&mut self, game: &mut TetrisGame, kind: PieceKind) {
+        self.call(game, "on_spawn", (piece_char(kind).to_string(),));
+    }
+
+    pub fn on_lock(&mut self, game: &mut TetrisGame, kind: PieceKind) {
+        self.call(game, "on_lock", (piece_char(kind).to_string(),));
+    }
+
+    pub fn on_clear(&mut self, game: &mut TetrisGame, lines: usize) {
+        self.call(game, "on_clear", (lines as i64,));
+    }
+
+    pub fn on_tick(&mut self, game: &mut TetrisGame, dt: f64) {
+        self.call(game, "on_tick", (dt,));
+    }
+
+    // A script that errors once is switched off rather than spamming the
+    // same error every frame.
+    fn call(&mut self, game: &mut TetrisGame, hook: &str, args: impl FuncArgs) {
+        if self.error.is_some() || !self.hooks.contains(&hook) {
+            return;
+        }
+        self.sync_in(game);
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, hook, args);
+        if let Err(error) = result {
+            self.error = Some(format!("{} in {}: {}", self.name, hook, error));
+        }
+        self.sync_out(game);
+    }
+
+    fn sync_in(&self, game: &mut TetrisGame) {
+        let mut api = self.api.borrow_mut();
+        api.board = game.board.clone();
+        api.score = game.score;
+        api.lines = game.stats.lines;
+        api.level = game.level();
+        api.pieces = game.stats.pieces;
+        api.time = game.stats.time.as_secs_f64();
+        api.game_over = game.game_over;
+        api.finished = game.finished;
+        // Taking the sent lines here would keep them from the opponent.
+        api.sent = game.garbage.total_sent();
+    }
+
+    fn sync_out(&mut self, game: &mut TetrisGame) {
+        let mut api = self.api.borrow_mut();
+        game.board = std::mem::take(&mut api.board);
+        game.score = api.score;
+        game.game_over |= api.game_over;
+        game.finished |= api.finished;
+        game.garbage.receive(std::mem::take(&mut api.incoming));
+        if let Some(message) = api.message.take() {
+            self.message = Some(message);
+        }
+        // Cells written over the falling piece end the game, as if it had
+        // spawned into them.
+        if !game.game_over && !game.is_waiting() && !game.can_move(0, 0) {
+            game.top_out(TopOut::Block);
+        }
+    }
+}
+
+fn engine(api: &Rc<RefCell<Api>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.register_fn("board_width", || BOARD_WIDTH as i64);
+    engine.register_fn("board_height", || BOARD_HEIGHT as i64);
+
+    // Cells read as "" when empty, "X" for garbage or the piece letter. Rows
+    // count from the top of the visible field; negative rows are hidden.
+    let shared = api.clone();
+    engine.register_fn("cell", move |x: i64, y: i64| -> String {
+        let api = shared.borrow();
+        match api.board.get((y + HIDDEN_ROWS as i64) as usize).and_then(|row| row.get(x as usize)) {
+            Some(BlockType::Filled(kind)) => piece_char(*kind).to_string(),
+            Some(BlockType::Garbage) => "X".to_string(),
+            _ => String::new(),
+        }
+    });
+    let shared = api.clone();
+    engine.register_fn("set_cell", move |x: i64, y: i64, value: &str| {
+        let block = match value.chars().next() {
+            None | Some('.') => BlockType::Empty,
+            Some(c) => piece_from_char(c).map_or(BlockType::Garbage, BlockType::Filled),
+        };
+        let mut api = shared.borrow_mut();
+        api.board.set(x as usize, (y + HIDDEN_ROWS as i64) as usize, block);
+    });
+    // Pushes the stack up by one garbage row with a hole in the given column.
+    let shared = api.clone();
+    engine.register_fn("add_garbage", move |hole: i64| {
+        let mut api = shared.borrow_mut();
+        let mut row = vec![BlockType::Garbage; BOARD_WIDTH];
+        if let Some(cell) = row.get_mut(hole as usize) {
+            *cell = BlockType::Empty;
+        }
+        if api.board.push_row(row) {
+            api.game_over = true;
+        }
+    });
+    // Queues garbage the way an opponent's attack would, so it can be
+    // cancelled before it rises.
+    let shared = api.clone();
+    engine.register_fn("queue_garbage", move |lines: i64| {
+        shared.borrow_mut().incoming += lines.max(0) as u32;
+    });
+    // Lines the player has sent since the last call.
+    let shared = api.clone();
+    engine.register_fn("sent_garbage", move || {
+        let mut api = shared.borrow_mut();
+        let lines = api.sent.saturating_sub(api.sent_read);
+        api.sent_read = api.sent;
+        lines as i64
+    });
+    let shared = api.clone();
+    engine.register_fn("clear_board", move || {
+        let mut api = shared.borrow_mut();
+        api.board = Board::default();
+    });
+
+    let shared = api.clone();
+    engine.register_fn("score", move || shared.borrow().score as i64);
+    let shared = api.clone();
+    engine.register_fn("add_score", move |points: i64| {
+        let mut api = shared.borrow_mut();
+        api.score = (api.score as i64 + points).max(0) as u32;
+    });
+    let shared = api.clone();
+    engine.register_fn("lines", move || shared.borrow().lines as i64);
+    let shared = api.clone();
+    engine.register_fn("level", move || shared.borrow().level as i64);
+    let shared = api.clone();
+    engine.register_fn("pieces", move || shared.borrow().pieces as i64);
+    let shared = api.clone();
+    engine.register_fn("time", move || shared.borrow().time);
+    let shared = api.clone();
+    // Ends the game as finished, the way reaching a mode's goal does.
+    engine.register_fn("end_game", move || {
+        let mut api = shared.borrow_mut();
+        api.game_over = true;
+        api.finished = true;
+    });
+    let shared = api.clone();
+    engine.register_fn("show", move |text: &str| shared.borrow_mut().message = Some(text.to_string()));
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mods_live_in_the_data_directory() {
+        assert_eq!(mods_dir(), history::data_dir().join("mods"));
+    }
+
+    #[test]
+    fn cells_over_the_piece_top_out() {
+        let mut game = TetrisGame::default();
+        let mut script = Script::parse("test", "fn on_start() { set_cell(0, board_height() - 1, \"X\"); }").unwrap();
+        script.on_start(&mut game);
+        assert!(!game.game_over);
+
+        let (x, y) = game.current_piece.cells()[0];
+        let source = format!("fn on_start() {{ set_cell({}, {}, \"X\"); }}", x, y - HIDDEN_ROWS as i32);
+        let mut script = Script::parse("test", &source).unwrap();
+        script.on_start(&mut game);
+        assert!(script.error.is_none());
+        assert!(game.game_over);
+        assert!(game.top_out == Some(TopOut::Block));
+    }
+
+    #[test]
+    fn garbage_pushed_into_the_piece_tops_out() {
+        let mut game = TetrisGame::default();
+        let source = "fn on_start() { for i in 0..board_height() + 1 { add_garbage(-1); } }";
+        let mut script = Script::parse("test", source).unwrap();
+        script.on_start(&mut game);
+        assert!(game.game_over);
+        assert!(game.top_out.is_some());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::i18n::{tr, tr_args};
+use crate::randomizer::Randomizer;
+use crate::{BlockType, PieceKind, TetrisGame, BOARD_HEIGHT, BOARD_WIDTH};
+
+#[derive(Clone)]
+pub struct PieceSequence {
+    pieces: Vec<PieceKind>,
+    position: usize,
+    repeat: bool,
+}
+
+impl PieceSequence {
+    pub fn new(pieces: Vec<PieceKind>, repeat: bool) -> Self {
+        Self {
+            pieces,
+            position: 0,
+            repeat,
+        }
+    }
+
+    pub fn parse(text: &str, repeat: bool) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        for c in text.chars() {
+            if c.is_whitespace() || c == ',' || c == '-' {
+                continue;
+            }
+            match piece_from_char(c) {
+                Some(kind) => pieces.push(kind),
+                None => return Err(tr_args("sequence-unknown-piece", &[("piece", &c)])),
+            }
+        }
+        if pieces.is_empty() {
+            return Err(tr("sequence-empty"));
+        }
+        Ok(Self::new(pieces, repeat))
+    }
+
+    // Falls back to the random generator once a non-repeating sequence runs out.
+    pub fn next(&mut self) -> Option<PieceKind> {
+        if self.position >= self.pieces.len() {
+            if !self.repeat {
+                return None;
+            }
+            self.position = 0;
+        }
+        let kind = self.pieces[self.position];
+        self.position += 1;
+        Some(kind)
+    }
+}
+
+#[derive(Clone)]
+pub struct SequenceSetup {
+    pub sequence: PieceSequence,
+    pub board: Vec<Vec<BlockType>>,
+}
+
+impl SequenceSetup {
+    pub fn game(&self, seed: Option<u64>, randomizer: Randomizer) -> TetrisGame {
+        TetrisGame::new(Some(self.sequence.clone()), self.board.clone(), seed, randomizer)
+    }
+}
+
+pub fn piece_char(kind: PieceKind) -> char {
+    match kind {
+        PieceKind::I => 'I',
+        PieceKind::O => 'O',
+        PieceKind::T => 'T',
+        PieceKind::L => 'L',
+        PieceKind::J => 'J',
+        PieceKind::S => 'S',
+        PieceKind::Z => 'Z',
+    }
+}
+
+pub fn piece_from_char(c: char) -> Option<PieceKind> {
+    match c.to_ascii_uppercase() {
+        'I' => Some(PieceKind::I),
+        'O' => Some(PieceKind::O),
+        'T' => Some(PieceKind::T),
+        'L' => Some(PieceKind::L),
+        'J' => Some(PieceKind::J),
+        'S' => Some(PieceKind::S),
+        'Z' => Some(PieceKind::Z),
+        _ => None,
+    }
+}
+
+// Rows are written top to bottom and rest on the floor. '.', '_' and spaces
+// are empty, piece letters keep their color and 'X', 'G' or '#' is garbage.
+pub fn parse_board(text: &str) -> Result<Vec<Vec<BlockType>>, String> {
+    let mut rows: Vec<&str> = text
+        .lines()
+        .map(|line| line.trim_end())
+        .skip_while(|line| line.is_empty())
+        .collect();
+    while rows.last().is_some_and(|line| line.is_empty()) {
+        rows.pop();
+    }
+    if rows.len() > BOARD_HEIGHT {
+        return Err(tr_args("board-too-tall", &[("rows", &BOARD_HEIGHT)]));
+    }
+
+    let mut board = vec![vec![BlockType::Empty; BOARD_WIDTH]; BOARD_HEIGHT];
+    let top = BOARD_HEIGHT - rows.len();
+    for (i, line) in rows.iter().enumerate() {
+        if line.chars().count() > BOARD_WIDTH {
+            return Err(tr_args("board-too-wide", &[("row", &(i + 1)), ("cells", &BOARD_WIDTH)]));
+        }
+        for (x, c) in line.chars().enumerate() {
+            board[top + i][x] = match c {
+                '.' | '_' | ' ' => BlockType::Empty,
+                'X' | 'x' | 'G' | 'g' | '#' => BlockType::Garbage,
+                _ => match piece_from_char(c) {
+                    Some(kind) => BlockType::Filled(kind),
+                    None => return Err(tr_args("board-unknown-cell", &[("cell", &c), ("row", &(i + 1))])),
+                },
+            };
+        }
+    }
+    Ok(board)
+}
+
+pub fn board_to_text(board: &[Vec<BlockType>]) -> String {
+    board
+        .iter()
+        .skip_while(|row| row.iter().all(|&cell| cell == BlockType::Empty))
+        .map(|row| {
+            row.iter()
+                .map(|&cell| match cell {
+                    BlockType::Empty => '.',
+                    BlockType::Filled(kind) => piece_char(kind),
+                    BlockType::Garbage => 'X',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(sequence: &mut PieceSequence, count: usize) -> String {
+        (0..count).filter_map(|_| sequence.next()).map(piece_char).collect()
+    }
+
+    #[test]
+    fn parses_letters_and_skips_separators() {
+        let mut sequence = PieceSequence::parse("t, s-z\nio lj", false).unwrap();
+        assert_eq!(drain(&mut sequence, 10), "TSZIOLJ");
+        assert!(PieceSequence::parse("TSQ", false).is_err());
+        assert!(PieceSequence::parse(" ,- ", false).is_err());
+    }
+
+    #[test]
+    fn repeats_or_runs_out() {
+        let mut once = PieceSequence::parse("IO", false).unwrap();
+        assert_eq!(drain(&mut once, 2), "IO");
+        assert!(once.next().is_none());
+        let mut repeating = PieceSequence::parse("IO", true).unwrap();
+        assert_eq!(drain(&mut repeating, 5), "IOIOI");
+    }
+
+    #[test]
+    fn piece_chars_round_trip() {
+        for c in "IOTLJSZ".chars() {
+            assert_eq!(piece_from_char(c).map(piece_char), Some(c));
+            assert_eq!(piece_from_char(c.to_ascii_lowercase()).map(piece_char), Some(c));
+        }
+        assert!(piece_from_char('X').is_none());
+    }
+
+    #[test]
+    fn boards_rest_on_the_floor_and_round_trip() {
+        let text = "\n..T.......\nXXTT.XXXXX\nIIIIXX_LSZ\n\n";
+        let board = parse_board(text).unwrap();
+        assert_eq!(board.len(), BOARD_HEIGHT);
+        assert!(board[BOARD_HEIGHT - 3][2] == BlockType::Filled(PieceKind::T));
+        assert!(board[BOARD_HEIGHT - 2][0] == BlockType::Garbage);
+        assert!(board[BOARD_HEIGHT - 1][6] == BlockType::Empty);
+        assert!(board[BOARD_HEIGHT - 4].iter().all(|&cell| cell == BlockType::Empty));
+        assert_eq!(board_to_text(&board), "..T.......\nXXTT.XXXXX\nIIIIXX.LSZ");
+    }
+
+    #[test]
+    fn rejects_boards_that_do_not_fit() {
+        assert!(parse_board(&"X\n".repeat(BOARD_HEIGHT)).is_ok());
+        assert!(parse_board(&"X\n".repeat(BOARD_HEIGHT + 1)).is_err());
+        assert!(parse_board(&"X".repeat(BOARD_WIDTH + 1)).is_err());
+        assert!(parse_board("XXQ").is_err());
+    }
+}
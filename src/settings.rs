@@ -0,0 +1,415 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tetorisu::net::DEFAULT_PORT;
+
+use crate::i18n::{tr, tr_args, Language};
+use crate::keybinds::{Action, Keybinds};
+use crate::modes::Mode;
+use crate::randomizer::Randomizer;
+use crate::rotation;
+use crate::skins::{self, Palette, PieceColors};
+use crate::sound;
+use crate::BOARD_HEIGHT;
+
+// Everything tunable lives here and round-trips through config.toml. Missing
+// keys fall back to these defaults so old files keep working.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub gravity_ms: u64,
+    pub lock_delay_ms: u64,
+    pub das_ms: u64,
+    pub arr_ms: u64,
+    pub clear_delay_ms: u64,
+    pub are_ms: u64,
+    // IRS/IHS: rotation and hold keys held during ARE act on the spawning piece.
+    pub initial_actions: bool,
+    pub soft_drop_factor: u32,
+    pub instant_soft_drop: bool,
+    // Wall kicks: SRS, SRS+, ARS or a table of your own under kicks/ in the
+    // data directory. Takes effect from the next game.
+    pub kicks: String,
+    // Cell size the window opens at; the board scales with the window after.
+    pub block_size: f32,
+    pub effects: bool,
+    // Eases the falling piece between cells and through rotations on screen.
+    pub smooth_motion: bool,
+    // Stack height in rows past which the board warns of a top out, and how
+    // strongly; zero turns the warning off.
+    pub danger_height: usize,
+    pub danger_intensity: f32,
+    // Modifiers that apply on top of any mode: pieces swapped for their
+    // mirror images, and the board drawn flipped with the controls left as
+    // they are.
+    pub mirror_pieces: bool,
+    pub mirror_board: bool,
+    // Display aids that work with any palette or skin: bright pieces with
+    // thick outlines on a black board, a board that fills the window with
+    // the side panels folded into a strip above it, and no flashing.
+    pub high_contrast: bool,
+    pub large_board: bool,
+    pub reduce_flashing: bool,
+    // Marks the suggested placement for the current piece, with the keys
+    // that get it there.
+    pub assist: bool,
+    pub skin: Option<String>,
+    pub palette: Palette,
+    // Sound effects, from a pack under assets/sfx in the data directory or
+    // the built-in ones. Only played in builds with the sound feature.
+    pub sound: bool,
+    pub volume: f32,
+    pub sound_pack: Option<String>,
+    pub patterns: bool,
+    pub colors: PieceColors,
+    pub keybinds: Keybinds,
+    // Swipes and taps on the board, once a touch screen is used.
+    pub touch_gestures: bool,
+    // Clickable move, rotate, drop and hold buttons under the board.
+    pub control_buttons: bool,
+    // Garbage rows a B-Type game starts with, and the share of each row
+    // left as holes.
+    pub btype_height: usize,
+    pub btype_holes: f32,
+    // Keyed by mode name; modes without an entry use the default bag.
+    pub randomizers: BTreeMap<String, Randomizer>,
+    // Speed curve names, also keyed by mode name; modes without one keep the
+    // fixed gravity, lock delay and ARE above.
+    pub curves: BTreeMap<String, String>,
+    pub player_name: String,
+    // Unset follows the system locale.
+    pub language: Option<Language>,
+    // Base address of an online leaderboard; blank keeps scores local.
+    pub leaderboard_url: String,
+    // host:port of the tetris-server to find online opponents on.
+    pub server_address: String,
+    // Show the current game in Discord; needs the ID of a Discord application.
+    pub discord_presence: bool,
+    pub discord_app_id: String,
+    #[serde(skip)]
+    pub rebinding: Option<Action>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            gravity_ms: 500,
+            lock_delay_ms: 500,
+            das_ms: 167,
+            arr_ms: 33,
+            clear_delay_ms: 300,
+            are_ms: 0,
+            initial_actions: true,
+            soft_drop_factor: 20,
+            instant_soft_drop: false,
+            kicks: "SRS".to_string(),
+            block_size: 30.0,
+            effects: true,
+            smooth_motion: false,
+            danger_height: 15,
+            danger_intensity: 0.6,
+            mirror_pieces: false,
+            mirror_board: false,
+            high_contrast: false,
+            large_board: false,
+            reduce_flashing: false,
+            assist: false,
+            skin: None,
+            palette: Palette::Standard,
+            patterns: false,
+            colors: PieceColors::default(),
+            keybinds: Keybinds::default(),
+            touch_gestures: true,
+            control_buttons: false,
+            btype_height: 6,
+            btype_holes: 0.3,
+            randomizers: BTreeMap::new(),
+            curves: BTreeMap::new(),
+            player_name: "Player".to_string(),
+            language: None,
+            leaderboard_url: String::new(),
+            server_address: format!("localhost:{}", DEFAULT_PORT),
+            discord_presence: false,
+            discord_app_id: String::new(),
+            sound: true,
+            volume: 0.5,
+            sound_pack: None,
+            rebinding: None,
+        }
+    }
+}
+
+pub fn config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("tetris")
+        .join("config.toml")
+}
+
+// Writes the defaults out on first run so there's a file to edit. A broken
+// file is left alone and reported instead of being overwritten.
+pub fn load(path: &Path, stars: u32) -> (Settings, Option<String>) {
+    let (mut settings, error) = match fs::read_to_string(path) {
+        Ok(text) => match toml::from_str(&text) {
+            Ok(settings) => (settings, None),
+            Err(error) => (Settings::default(), Some(format!("{}: {}", path.display(), error))),
+        },
+        Err(_) => {
+            let settings = Settings::default();
+            let error = settings.save(path).err();
+            (settings, error)
+        }
+    };
+    let locked = settings.lock_palette(stars);
+    (settings, error.or(locked))
+}
+
+impl Settings {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|error| error.to_string())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+        }
+        fs::write(path, text).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    // A palette the quest stars haven't unlocked yet goes back to the
+    // default, however it got into the file.
+    fn lock_palette(&mut self, stars: u32) -> Option<String> {
+        let needed = self.palette.stars_needed();
+        if stars >= needed {
+            return None;
+        }
+        let message = tr_args("palette-still-locked", &[("palette", &self.palette.name()), ("stars", &needed)]);
+        self.palette = Palette::default();
+        Some(message)
+    }
+
+    pub fn gravity(&self) -> Duration {
+        Duration::from_millis(self.gravity_ms.max(1))
+    }
+
+    pub fn lock_delay(&self) -> Duration {
+        Duration::from_millis(self.lock_delay_ms)
+    }
+
+    pub fn das(&self) -> Duration {
+        Duration::from_millis(self.das_ms)
+    }
+
+    pub fn arr(&self) -> Duration {
+        Duration::from_millis(self.arr_ms)
+    }
+
+    pub fn clear_delay(&self) -> Duration {
+        Duration::from_millis(self.clear_delay_ms)
+    }
+
+    pub fn are(&self) -> Duration {
+        Duration::from_millis(self.are_ms)
+    }
+
+    pub fn randomizer(&self, mode: &Mode) -> Randomizer {
+        self.randomizers.get(&mode.name()).copied().unwrap_or_default()
+    }
+
+    pub fn curve(&self, mode: &Mode) -> Option<&String> {
+        self.curves.get(&mode.name())
+    }
+
+    pub fn language(&self) -> Language {
+        self.language.unwrap_or_else(Language::system)
+    }
+
+    pub fn soft_drop_factor(&self) -> Option<u32> {
+        (!self.instant_soft_drop).then_some(self.soft_drop_factor)
+    }
+
+    // Palettes need enough quest stars to be picked.
+    pub fn ui(&mut self, ui: &mut egui::Ui, stars: u32) {
+        let rows = format!(" {}", tr("unit-rows"));
+        egui::Grid::new("settings").num_columns(2).show(ui, |ui| {
+            ui.label(tr("settings-language"));
+            egui::ComboBox::from_id_source("language")
+                .selected_text(self.language.map_or_else(|| tr("settings-language-system"), Language::name))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.language, None, tr("settings-language-system"));
+                    for language in Language::ALL {
+                        ui.selectable_value(&mut self.language, Some(language), language.name());
+                    }
+                });
+            ui.end_row();
+            let timings = [
+                ("settings-gravity", &mut self.gravity_ms, 1..=2000),
+                ("settings-lock-delay", &mut self.lock_delay_ms, 0..=2000),
+                ("settings-das", &mut self.das_ms, 0..=500),
+                ("settings-arr", &mut self.arr_ms, 0..=200),
+                ("settings-clear-delay", &mut self.clear_delay_ms, 0..=1000),
+                ("settings-are", &mut self.are_ms, 0..=1000),
+            ];
+            for (label, value, range) in timings {
+                ui.label(tr(label));
+                ui.add(egui::Slider::new(value, range).suffix(" ms"));
+                ui.end_row();
+            }
+            ui.label(tr("settings-initial-actions"));
+            ui.checkbox(&mut self.initial_actions, "");
+            ui.end_row();
+            ui.label(tr("settings-soft-drop"));
+            ui.horizontal(|ui| {
+                ui.add_enabled(
+                    !self.instant_soft_drop,
+                    egui::Slider::new(&mut self.soft_drop_factor, 1..=40).suffix("x"),
+                );
+                ui.checkbox(&mut self.instant_soft_drop, tr("settings-soft-drop-instant"));
+            });
+            ui.end_row();
+            ui.label(tr("settings-kicks"));
+            egui::ComboBox::from_id_source("kicks")
+                .selected_text(self.kicks.clone())
+                .show_ui(ui, |ui| {
+                    for name in rotation::available() {
+                        ui.selectable_value(&mut self.kicks, name.clone(), name);
+                    }
+                });
+            ui.end_row();
+            ui.label(tr("settings-effects"));
+            ui.checkbox(&mut self.effects, "");
+            ui.end_row();
+            ui.label(tr("settings-smooth-motion"));
+            ui.checkbox(&mut self.smooth_motion, "");
+            ui.end_row();
+            ui.label(tr("settings-danger"));
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut self.danger_height, 1..=BOARD_HEIGHT).suffix(&rows));
+                ui.add(egui::Slider::new(&mut self.danger_intensity, 0.0..=1.0).text(tr("settings-danger-strength")));
+            });
+            ui.end_row();
+            ui.label(tr("settings-touch-gestures"));
+            ui.checkbox(&mut self.touch_gestures, "");
+            ui.end_row();
+            ui.label(tr("settings-control-buttons"));
+            ui.checkbox(&mut self.control_buttons, "");
+            ui.end_row();
+            ui.label(tr("settings-mirror"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.mirror_pieces, tr("settings-mirror-pieces"));
+                ui.checkbox(&mut self.mirror_board, tr("settings-mirror-board"));
+            });
+            ui.end_row();
+            ui.label(tr("settings-accessibility"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.high_contrast, tr("settings-high-contrast"));
+                ui.checkbox(&mut self.large_board, tr("settings-large-board"));
+                ui.checkbox(&mut self.reduce_flashing, tr("settings-reduce-flashing"));
+            });
+            ui.end_row();
+            ui.label(tr("settings-btype"));
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut self.btype_height, 0..=BOARD_HEIGHT - 4).suffix(&rows));
+                ui.add(egui::Slider::new(&mut self.btype_holes, 0.1..=0.9).text(tr("settings-btype-holes")));
+            });
+            ui.end_row();
+            ui.label(tr("settings-skin"));
+            egui::ComboBox::from_id_source("skin")
+                .selected_text(self.skin.clone().unwrap_or_else(|| tr("settings-skin-flat")))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.skin, None, tr("settings-skin-flat"));
+                    for name in skins::available() {
+                        ui.selectable_value(&mut self.skin, Some(name.clone()), name);
+                    }
+                });
+            ui.end_row();
+            ui.label(tr("settings-palette"));
+            egui::ComboBox::from_id_source("palette")
+                .selected_text(self.palette.name())
+                .show_ui(ui, |ui| {
+                    for palette in Palette::ALL {
+                        let needed = palette.stars_needed();
+                        if stars >= needed {
+                            ui.selectable_value(&mut self.palette, palette, palette.name());
+                        } else {
+                            let name = tr_args("palette-locked", &[("palette", &palette.name()), ("stars", &needed)]);
+                            ui.add_enabled(false, egui::SelectableLabel::new(false, name));
+                        }
+                    }
+                });
+            ui.end_row();
+            ui.label(tr("settings-patterns"));
+            ui.checkbox(&mut self.patterns, "");
+            ui.end_row();
+            if cfg!(feature = "sound") {
+                ui.label(tr("settings-sound"));
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.sound, "");
+                    ui.add_enabled(self.sound, egui::Slider::new(&mut self.volume, 0.0..=1.0).text(tr("settings-volume")));
+                });
+                ui.end_row();
+                ui.label(tr("settings-sound-pack"));
+                egui::ComboBox::from_id_source("sound-pack")
+                    .selected_text(self.sound_pack.clone().unwrap_or_else(|| tr("settings-sound-pack-built-in")))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.sound_pack, None, tr("settings-sound-pack-built-in"));
+                        for name in sound::available() {
+                            ui.selectable_value(&mut self.sound_pack, Some(name.clone()), name);
+                        }
+                    });
+                ui.end_row();
+            }
+            ui.label(tr("settings-player-name"));
+            ui.text_edit_singleline(&mut self.player_name);
+            ui.end_row();
+            ui.label(tr("settings-leaderboard-url"));
+            ui.add(
+                egui::TextEdit::singleline(&mut self.leaderboard_url).hint_text(tr("settings-leaderboard-url-hint")),
+            );
+            ui.end_row();
+            ui.label(tr("settings-discord"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.discord_presence, "");
+                ui.add_enabled(
+                    self.discord_presence,
+                    egui::TextEdit::singleline(&mut self.discord_app_id).hint_text(tr("settings-discord-app-id")),
+                );
+            });
+            ui.end_row();
+        });
+        ui.collapsing(tr("settings-colors"), |ui| self.colors.ui(ui));
+        ui.collapsing(tr("settings-controls"), |ui| self.keybinds.ui(ui, &mut self.rebinding));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_palettes_fall_back_to_the_default() {
+        let mut settings: Settings = toml::from_str("palette = \"Neon\"").unwrap();
+        assert!(settings.palette == Palette::Neon);
+        assert!(settings.lock_palette(Palette::Neon.stars_needed() - 1).is_some());
+        assert!(settings.palette == Palette::default());
+
+        let mut settings: Settings = toml::from_str("palette = \"Neon\"").unwrap();
+        assert!(settings.lock_palette(Palette::Neon.stars_needed()).is_none());
+        assert!(settings.palette == Palette::Neon);
+        let mut settings: Settings = toml::from_str("palette = \"Colorblind\"").unwrap();
+        assert!(settings.lock_palette(0).is_none());
+        assert!(settings.palette == Palette::Colorblind);
+    }
+
+    #[test]
+    fn loading_applies_the_lock() {
+        let path = std::env::temp_dir().join(format!("tetorisu-settings-{}.toml", std::process::id()));
+        fs::write(&path, "palette = \"Retro\"\nvolume = 0.25").unwrap();
+        let (settings, message) = load(&path, 0);
+        fs::remove_file(&path).unwrap();
+        assert!(settings.palette == Palette::default());
+        assert!(message.is_some());
+        assert_eq!(settings.volume, 0.25);
+    }
+}
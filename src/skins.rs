@@ -0,0 +1,327 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use crate::i18n::tr;
+use crate::{history, BlockType, PieceKind, GARBAGE_COLOR};
+
+// A skin is a PNG strip of square tiles: one per piece in PieceKind::ALL
+// order (I O T L J S Z), then garbage. Extra tiles to the right are ignored.
+const TILES: usize = 8;
+
+pub struct Skin {
+    pub name: String,
+    texture: egui::TextureHandle,
+    // Width of one tile in texture coordinates.
+    tile: f32,
+}
+
+pub fn skins_dir() -> PathBuf {
+    history::data_dir().join("skins")
+}
+
+pub fn available() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(skins_dir()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+impl Skin {
+    pub fn load(ctx: &egui::Context, name: &str) -> Result<Self, String> {
+        let path = skins_dir().join(format!("{}.png", name));
+        let image = decode(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        let [width, height] = image.size;
+        if height == 0 || width < height * TILES {
+            return Err(format!(
+                "{}: expected {} square tiles side by side",
+                path.display(),
+                TILES
+            ));
+        }
+        let tile = height as f32 / width as f32;
+        let texture = ctx.load_texture(format!("skin-{}", name), image, egui::TextureOptions::LINEAR);
+        Ok(Self {
+            name: name.to_string(),
+            texture,
+            tile,
+        })
+    }
+
+    fn uv(&self, block: BlockType) -> Option<egui::Rect> {
+        let index = match block {
+            BlockType::Empty => return None,
+            BlockType::Filled(kind) => kind as usize,
+            BlockType::Garbage => TILES - 1,
+        };
+        let left = index as f32 * self.tile;
+        Some(egui::Rect::from_min_max(
+            egui::pos2(left, 0.0),
+            egui::pos2(left + self.tile, 1.0),
+        ))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Standard,
+    // Okabe-Ito colors, which stay apart under the common forms of color
+    // blindness.
+    Colorblind,
+    // Themes unlocked with stars from quests.
+    Pastel,
+    Neon,
+    Retro,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 5] = [
+        Palette::Standard,
+        Palette::Colorblind,
+        Palette::Pastel,
+        Palette::Neon,
+        Palette::Retro,
+    ];
+
+    pub fn name(self) -> String {
+        tr(match self {
+            Palette::Standard => "palette-standard",
+            Palette::Colorblind => "palette-colorblind",
+            Palette::Pastel => "palette-pastel",
+            Palette::Neon => "palette-neon",
+            Palette::Retro => "palette-retro",
+        })
+    }
+
+    // Quest stars it takes to unlock; the accessibility palette is never
+    // locked.
+    pub fn stars_needed(self) -> u32 {
+        match self {
+            Palette::Standard | Palette::Colorblind => 0,
+            Palette::Pastel => 3,
+            Palette::Neon => 12,
+            Palette::Retro => 30,
+        }
+    }
+
+    pub fn color(self, kind: PieceKind, colors: &PieceColors) -> egui::Color32 {
+        match self {
+            Palette::Standard => colors.piece(kind),
+            Palette::Colorblind => match kind {
+                PieceKind::I => egui::Color32::from_rgb(86, 180, 233),
+                PieceKind::O => egui::Color32::from_rgb(240, 228, 66),
+                PieceKind::T => egui::Color32::from_rgb(204, 121, 167),
+                PieceKind::L => egui::Color32::from_rgb(230, 159, 0),
+                PieceKind::J => egui::Color32::from_rgb(0, 114, 178),
+                PieceKind::S => egui::Color32::from_rgb(0, 158, 115),
+                PieceKind::Z => egui::Color32::from_rgb(213, 94, 0),
+            },
+            Palette::Pastel => match kind {
+                PieceKind::I => egui::Color32::from_rgb(164, 222, 240),
+                PieceKind::O => egui::Color32::from_rgb(250, 236, 160),
+                PieceKind::T => egui::Color32::from_rgb(214, 178, 236),
+                PieceKind::L => egui::Color32::from_rgb(250, 200, 150),
+                PieceKind::J => egui::Color32::from_rgb(160, 184, 240),
+                PieceKind::S => egui::Color32::from_rgb(176, 230, 176),
+                PieceKind::Z => egui::Color32::from_rgb(244, 168, 176),
+            },
+            Palette::Neon => match kind {
+                PieceKind::I => egui::Color32::from_rgb(0, 255, 255),
+                PieceKind::O => egui::Color32::from_rgb(255, 255, 0),
+                PieceKind::T => egui::Color32::from_rgb(255, 0, 255),
+                PieceKind::L => egui::Color32::from_rgb(255, 128, 0),
+                PieceKind::J => egui::Color32::from_rgb(64, 96, 255),
+                PieceKind::S => egui::Color32::from_rgb(0, 255, 64),
+                PieceKind::Z => egui::Color32::from_rgb(255, 0, 64),
+            },
+            // The four greens of an old handheld's screen.
+            Palette::Retro => match kind {
+                PieceKind::I | PieceKind::S => egui::Color32::from_rgb(155, 188, 15),
+                PieceKind::O | PieceKind::J => egui::Color32::from_rgb(139, 172, 15),
+                PieceKind::T | PieceKind::Z => egui::Color32::from_rgb(48, 98, 48),
+                PieceKind::L => egui::Color32::from_rgb(224, 248, 208),
+            },
+        }
+    }
+}
+
+// The standard palette, editable from the config file.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PieceColors {
+    pub i: [u8; 3],
+    pub o: [u8; 3],
+    pub t: [u8; 3],
+    pub l: [u8; 3],
+    pub j: [u8; 3],
+    pub s: [u8; 3],
+    pub z: [u8; 3],
+    pub garbage: [u8; 3],
+}
+
+impl Default for PieceColors {
+    fn default() -> Self {
+        let rgb = |color: egui::Color32| [color.r(), color.g(), color.b()];
+        Self {
+            i: rgb(PieceKind::I.color()),
+            o: rgb(PieceKind::O.color()),
+            t: rgb(PieceKind::T.color()),
+            l: rgb(PieceKind::L.color()),
+            j: rgb(PieceKind::J.color()),
+            s: rgb(PieceKind::S.color()),
+            z: rgb(PieceKind::Z.color()),
+            garbage: rgb(GARBAGE_COLOR),
+        }
+    }
+}
+
+impl PieceColors {
+    fn slot(&mut self, kind: PieceKind) -> &mut [u8; 3] {
+        match kind {
+            PieceKind::I => &mut self.i,
+            PieceKind::O => &mut self.o,
+            PieceKind::T => &mut self.t,
+            PieceKind::L => &mut self.l,
+            PieceKind::J => &mut self.j,
+            PieceKind::S => &mut self.s,
+            PieceKind::Z => &mut self.z,
+        }
+    }
+
+    pub fn piece(&self, kind: PieceKind) -> egui::Color32 {
+        let [r, g, b] = match kind {
+            PieceKind::I => self.i,
+            PieceKind::O => self.o,
+            PieceKind::T => self.t,
+            PieceKind::L => self.l,
+            PieceKind::J => self.j,
+            PieceKind::S => self.s,
+            PieceKind::Z => self.z,
+        };
+        egui::Color32::from_rgb(r, g, b)
+    }
+
+    pub fn garbage(&self) -> egui::Color32 {
+        let [r, g, b] = self.garbage;
+        egui::Color32::from_rgb(r, g, b)
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("colors").num_columns(2).show(ui, |ui| {
+            for kind in PieceKind::ALL {
+                ui.label(format!("{:?}", kind));
+                ui.color_edit_button_srgb(self.slot(kind));
+                ui.end_row();
+            }
+            ui.label(tr("garbage"));
+            ui.color_edit_button_srgb(&mut self.garbage);
+            ui.end_row();
+        });
+        if ui.button(tr("colors-reset")).clicked() {
+            *self = Self::default();
+        }
+    }
+}
+
+// Everything that decides how a single block is drawn.
+#[derive(Default)]
+pub struct BlockStyle {
+    pub skin: Option<Skin>,
+    pub palette: Palette,
+    pub patterns: bool,
+    pub colors: PieceColors,
+    pub high_contrast: bool,
+}
+
+impl BlockStyle {
+    pub fn color(&self, block: BlockType) -> Option<egui::Color32> {
+        let color = match block {
+            BlockType::Empty => return None,
+            BlockType::Filled(kind) => self.palette.color(kind, &self.colors),
+            BlockType::Garbage if self.high_contrast => return Some(egui::Color32::from_gray(200)),
+            BlockType::Garbage => self.colors.garbage(),
+        };
+        Some(if self.high_contrast { brighten(color) } else { color })
+    }
+
+    pub fn paint(&self, painter: &egui::Painter, rect: egui::Rect, block: BlockType) {
+        if let Some((skin, uv)) = self.skin.as_ref().and_then(|skin| Some((skin, skin.uv(block)?))) {
+            painter.image(skin.texture.id(), rect, uv, egui::Color32::WHITE);
+        } else if let Some(color) = self.color(block) {
+            painter.rect_filled(rect, 0.0, color);
+        }
+        if self.patterns {
+            paint_pattern(painter, rect, block);
+        }
+        if self.high_contrast && block != BlockType::Empty {
+            let width = (rect.width() * 0.12).max(2.0);
+            painter.rect_stroke(rect.shrink(width / 2.0), 0.0, egui::Stroke::new(width, egui::Color32::BLACK));
+        }
+    }
+}
+
+// Scales a color up until its brightest channel is full, keeping the hue, so
+// every piece stands out against a black board.
+fn brighten(color: egui::Color32) -> egui::Color32 {
+    let max = color.r().max(color.g()).max(color.b()).max(1) as f32;
+    let scale = |channel: u8| (channel as f32 * 255.0 / max) as u8;
+    egui::Color32::from_rgb(scale(color.r()), scale(color.g()), scale(color.b()))
+}
+
+// A distinct mark per piece so pieces can be told apart without hue.
+fn paint_pattern(painter: &egui::Painter, rect: egui::Rect, block: BlockType) {
+    let r = rect.shrink(rect.width() * 0.25);
+    let stroke = egui::Stroke::new((rect.width() * 0.08).max(1.0), egui::Color32::from_black_alpha(160));
+    let lines: &[[egui::Pos2; 2]] = match block {
+        BlockType::Empty => return,
+        BlockType::Filled(PieceKind::I) => &[[r.center_top(), r.center_bottom()]],
+        BlockType::Filled(PieceKind::O) => {
+            painter.rect_stroke(r, 0.0, stroke);
+            return;
+        }
+        BlockType::Filled(PieceKind::T) => &[
+            [r.left_bottom(), r.center_top()],
+            [r.center_top(), r.right_bottom()],
+            [r.right_bottom(), r.left_bottom()],
+        ],
+        BlockType::Filled(PieceKind::L) => &[[r.left_top(), r.left_bottom()], [r.left_bottom(), r.right_bottom()]],
+        BlockType::Filled(PieceKind::J) => &[[r.right_top(), r.right_bottom()], [r.right_bottom(), r.left_bottom()]],
+        BlockType::Filled(PieceKind::S) => &[[r.left_bottom(), r.right_top()]],
+        BlockType::Filled(PieceKind::Z) => &[[r.left_top(), r.right_bottom()]],
+        BlockType::Garbage => &[[r.left_top(), r.right_bottom()], [r.left_bottom(), r.right_top()]],
+    };
+    for &points in lines {
+        painter.line_segment(points, stroke);
+    }
+}
+
+fn decode(path: &Path) -> Result<egui::ColorImage, String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|error| error.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|error| error.to_string())?;
+    let pixels = &buffer[..info.buffer_size()];
+
+    let rgba: Vec<u8> = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels.chunks(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => pixels.chunks(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return Err("unsupported indexed image".to_string()),
+    };
+    Ok(egui::ColorImage::from_rgba_unmultiplied(
+        [info.width as usize, info.height as usize],
+        &rgba,
+    ))
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{history, GameEvent, Spin};
+
+// The sounds a pack can replace, by the names its manifest uses.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Sound {
+    Move,
+    Rotate,
+    Lock,
+    Clear1,
+    Clear2,
+    Clear3,
+    Clear4,
+    TSpin,
+    PerfectClear,
+    TopOut,
+}
+
+impl Sound {
+    pub const ALL: [Sound; 10] = [
+        Sound::Move,
+        Sound::Rotate,
+        Sound::Lock,
+        Sound::Clear1,
+        Sound::Clear2,
+        Sound::Clear3,
+        Sound::Clear4,
+        Sound::TSpin,
+        Sound::PerfectClear,
+        Sound::TopOut,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sound::Move => "move",
+            Sound::Rotate => "rotate",
+            Sound::Lock => "lock",
+            Sound::Clear1 => "clear1",
+            Sound::Clear2 => "clear2",
+            Sound::Clear3 => "clear3",
+            Sound::Clear4 => "clear4",
+            Sound::TSpin => "tspin",
+            Sound::PerfectClear => "pc",
+            Sound::TopOut => "topout",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Sound> {
+        Self::ALL.into_iter().find(|sound| sound.name() == name)
+    }
+
+    // A spin or perfect clear plays instead of the plain clear it came with.
+    // Spins that clear nothing only show up as a score.
+    pub fn for_event(event: &GameEvent) -> Option<Sound> {
+        Some(match event {
+            GameEvent::Moved { .. } => Sound::Move,
+            GameEvent::Rotated { .. } => Sound::Rotate,
+            GameEvent::Locked(_) => Sound::Lock,
+            GameEvent::LinesCleared { perfect: true, .. } => Sound::PerfectClear,
+            GameEvent::LinesCleared { spin, .. } if *spin != Spin::None => Sound::TSpin,
+            GameEvent::LinesCleared { rows, .. } => match rows.len() {
+                0 => return None,
+                1 => Sound::Clear1,
+                2 => Sound::Clear2,
+                3 => Sound::Clear3,
+                _ => Sound::Clear4,
+            },
+            GameEvent::Scored { clear, .. } if clear.lines == 0 && clear.spin != Spin::None => Sound::TSpin,
+            GameEvent::ToppedOut(_) => Sound::TopOut,
+            _ => return None,
+        })
+    }
+}
+
+pub fn packs_dir() -> PathBuf {
+    history::data_dir().join("assets").join("sfx")
+}
+
+// Packs are the directories under assets/sfx with a manifest in them.
+pub fn available() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(packs_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join(MANIFEST).is_file())
+        .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+const MANIFEST: &str = "manifest.toml";
+
+// A sound pack in assets/sfx/<name>/ under the data directory. Its
+// manifest.toml names a file in the same directory for each sound it
+// replaces, by the names in Sound::name; any it leaves out, or whose file
+// is missing, keep the built-in sound:
+//
+//   move = "move.wav"
+//   clear4 = "tetris.ogg"
+//   pc = "perfect.ogg"
+pub struct Pack {
+    pub name: String,
+    files: HashMap<Sound, PathBuf>,
+}
+
+impl Pack {
+    pub fn load(name: &str) -> Result<Self, String> {
+        let dir = packs_dir().join(name);
+        let path = dir.join(MANIFEST);
+        let text = fs::read_to_string(&path).map_err(|reason| format!("{}: {}", path.display(), reason))?;
+        let mut pack = Self::parse(name, &dir, &text).map_err(|reason| format!("{}: {}", path.display(), reason))?;
+        pack.files.retain(|_, file| file.is_file());
+        Ok(pack)
+    }
+
+    fn parse(name: &str, dir: &Path, text: &str) -> Result<Self, String> {
+        let entries: HashMap<String, String> = toml::from_str(text).map_err(|reason| reason.to_string())?;
+        let mut files = HashMap::new();
+        for (key, file) in entries {
+            let sound = Sound::parse(&key).ok_or_else(|| {
+                let names: Vec<&str> = Sound::ALL.iter().map(|sound| sound.name()).collect();
+                format!("unknown sound '{}', expected one of {}", key, names.join(", "))
+            })?;
+            // Files stay inside the pack, so a pack can't reach elsewhere.
+            let relative = Path::new(&file);
+            if file.is_empty() || !relative.components().all(|part| matches!(part, Component::Normal(_))) {
+                return Err(format!("{}: '{}' has to be a file in the pack's directory", key, file));
+            }
+            files.insert(sound, dir.join(relative));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            files,
+        })
+    }
+
+    #[cfg_attr(not(feature = "sound"), allow(dead_code))]
+    pub fn file(&self, sound: Sound) -> Option<&Path> {
+        self.files.get(&sound).map(PathBuf::as_path)
+    }
+}
+
+// The built-in sounds are short tones, as (frequency in Hz, length in ms)
+// notes played one after another.
+#[cfg_attr(not(feature = "sound"), allow(dead_code))]
+fn tones(sound: Sound) -> &'static [(f32, u64)] {
+    match sound {
+        Sound::Move => &[(660.0, 12)],
+        Sound::Rotate => &[(880.0, 18)],
+        Sound::Lock => &[(196.0, 40)],
+        Sound::Clear1 => &[(523.0, 70)],
+        Sound::Clear2 => &[(523.0, 60), (659.0, 70)],
+        Sound::Clear3 => &[(523.0, 50), (659.0, 50), (784.0, 80)],
+        Sound::Clear4 => &[(523.0, 50), (659.0, 50), (784.0, 50), (1047.0, 140)],
+        Sound::TSpin => &[(740.0, 50), (988.0, 50), (740.0, 90)],
+        Sound::PerfectClear => &[(784.0, 60), (988.0, 60), (1175.0, 60), (1568.0, 200)],
+        Sound::TopOut => &[(392.0, 120), (330.0, 120), (262.0, 120), (196.0, 300)],
+    }
+}
+
+// Plays sounds for game events, from the chosen pack where it has them and
+// the built-in tones otherwise. Without the sound feature it does nothing,
+// and with it the output opens on the first sound; a machine without one
+// just stays quiet.
+#[derive(Default)]
+pub struct Player {
+    pack: Option<Pack>,
+    #[cfg(feature = "sound")]
+    output: Option<output::Output>,
+    #[cfg(feature = "sound")]
+    failed: bool,
+}
+
+impl Player {
+    pub fn pack(&self) -> Option<&str> {
+        self.pack.as_ref().map(|pack| pack.name.as_str())
+    }
+
+    pub fn set_pack(&mut self, pack: Option<Pack>) {
+        self.pack = pack;
+        #[cfg(feature = "sound")]
+        if let Some(output) = &mut self.output {
+            output.clear();
+        }
+    }
+
+    #[cfg(not(feature = "sound"))]
+    pub fn play(&mut self, _sound: Sound, _volume: f32) {}
+
+    #[cfg(feature = "sound")]
+    pub fn play(&mut self, sound: Sound, volume: f32) {
+        if self.output.is_none() && !self.failed {
+            match output::Output::open() {
+                Ok(output) => self.output = Some(output),
+                Err(error) => {
+                    eprintln!("Could not open audio output: {}", error);
+                    self.failed = true;
+                }
+            }
+        }
+        let Some(output) = &mut self.output else {
+            return;
+        };
+        let file = self.pack.as_ref().and_then(|pack| pack.file(sound));
+        if !file.is_some_and(|file| output.play_file(file, volume)) {
+            output.play_tones(tones(sound), volume);
+        }
+    }
+}
+
+#[cfg(feature = "sound")]
+mod output {
+    use rodio::source::{SineWave, Source};
+    use rodio::{Decoder, OutputStream, OutputStreamHandle};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // How loud the built-in tones are next to a pack's sounds at the same
+    // volume setting.
+    const TONE_LEVEL: f32 = 0.15;
+
+    pub struct Output {
+        // Dropping the stream stops all sound, so it's kept with the handle.
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+        // Pack files read so far, or None for ones that couldn't be read or
+        // decoded, which fall back to the built-in tones from then on.
+        files: HashMap<PathBuf, Option<Arc<[u8]>>>,
+    }
+
+    impl Output {
+        pub fn open() -> Result<Self, String> {
+            let (stream, handle) = OutputStream::try_default().map_err(|error| error.to_string())?;
+            Ok(Self {
+                _stream: stream,
+                handle,
+                files: HashMap::new(),
+            })
+        }
+
+        pub fn clear(&mut self) {
+            self.files.clear();
+        }
+
+        // False if the file can't be played, so the built-in sound plays.
+        pub fn play_file(&mut self, path: &Path, volume: f32) -> bool {
+            let bytes = self.files.entry(path.to_path_buf()).or_insert_with(|| {
+                let bytes: Arc<[u8]> = fs::read(path).ok()?.into();
+                Decoder::new(Cursor::new(bytes.clone())).ok().map(|_| bytes)
+            });
+            let Some(bytes) = bytes.clone() else {
+                return false;
+            };
+            match Decoder::new(Cursor::new(bytes)) {
+                Ok(decoder) => self.handle.play_raw(decoder.amplify(volume).convert_samples()).is_ok(),
+                Err(_) => false,
+            }
+        }
+
+        pub fn play_tones(&mut self, notes: &[(f32, u64)], volume: f32) {
+            let mut start = Duration::ZERO;
+            for &(frequency, ms) in notes {
+                let length = Duration::from_millis(ms);
+                let note = SineWave::new(frequency)
+                    .take_duration(length)
+                    .fade_in(Duration::from_millis(3))
+                    .amplify(volume * TONE_LEVEL)
+                    .delay(start);
+                let _ = self.handle.play_raw(note);
+                start += length;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Pack, String> {
+        Pack::parse("test", Path::new("/packs/test"), text)
+    }
+
+    #[test]
+    fn manifest_maps_sounds_to_files() {
+        let pack = parse("move = \"move.wav\"\nclear4 = \"sounds/tetris.ogg\"\npc = \"pc.ogg\"").unwrap();
+        assert_eq!(pack.file(Sound::Move), Some(Path::new("/packs/test/move.wav")));
+        assert_eq!(pack.file(Sound::Clear4), Some(Path::new("/packs/test/sounds/tetris.ogg")));
+        assert_eq!(pack.file(Sound::PerfectClear), Some(Path::new("/packs/test/pc.ogg")));
+        // Left out, so the built-in sound plays.
+        assert_eq!(pack.file(Sound::Lock), None);
+    }
+
+    #[test]
+    fn bad_manifests_are_rejected() {
+        assert!(parse("explode = \"boom.wav\"").err().unwrap().contains("unknown sound 'explode'"));
+        assert!(parse("move = \"../elsewhere.wav\"").is_err());
+        assert!(parse("move = \"/etc/passwd\"").is_err());
+        assert!(parse("move = \"\"").is_err());
+        assert!(parse("move = 3").is_err());
+        assert!(parse("move = ").is_err());
+    }
+
+    #[test]
+    fn events_pick_their_sounds() {
+        let cleared = |lines: usize, perfect: bool, spin: Spin| GameEvent::LinesCleared {
+            rows: (0..lines).map(|y| (y, Vec::new())).collect(),
+            perfect,
+            spin,
+        };
+        assert_eq!(Sound::for_event(&cleared(1, false, Spin::None)), Some(Sound::Clear1));
+        assert_eq!(Sound::for_event(&cleared(4, false, Spin::None)), Some(Sound::Clear4));
+        assert_eq!(Sound::for_event(&cleared(2, false, Spin::Full)), Some(Sound::TSpin));
+        assert_eq!(Sound::for_event(&cleared(4, true, Spin::None)), Some(Sound::PerfectClear));
+        assert_eq!(Sound::for_event(&GameEvent::GarbageRaised(2)), None);
+    }
+
+    #[test]
+    fn every_sound_has_a_name_and_a_tone() {
+        for sound in Sound::ALL {
+            assert_eq!(Sound::parse(sound.name()), Some(sound));
+            assert!(!tones(sound).is_empty());
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::history;
+
+// Limits for custom curves, generous enough for any real game's timings.
+const MAX_LEVEL: u32 = 999;
+const MAX_MS: f64 = 60_000.0;
+
+// One NES frame, and one frame of the arcade games TGM's timings are
+// counted in.
+const NES_FRAME_MS: f64 = 1000.0 / 60.0988;
+const TGM_FRAME_MS: f64 = 1000.0 / 60.0;
+
+// Frames per row on the NES, from level 0; 29 and up drop a row a frame.
+const NES_GRAVITY: [u8; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1,
+];
+
+// TGM2's Death mode: 20G from the start, with lock delay and ARE in frames
+// shrinking each 100-level section, taken here as one level per section.
+const DEATH_LOCK: [u8; 6] = [30, 26, 22, 18, 15, 15];
+const DEATH_ARE: [u8; 6] = [12, 12, 12, 6, 5, 4];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Speed {
+    pub gravity: Duration,
+    pub lock_delay: Duration,
+    pub are: Duration,
+}
+
+#[derive(Clone, Copy)]
+struct Step {
+    level: u32,
+    gravity_ms: f64,
+    lock_delay_ms: f64,
+    are_ms: f64,
+}
+
+// Timings by level. Each step holds from its level until the next one.
+pub struct SpeedCurve {
+    pub name: String,
+    steps: Vec<Step>,
+}
+
+// A custom curve in curves/<name>.toml under the data directory. A gravity
+// of 0 is 20G, where pieces drop straight to the floor. Lock delay and ARE
+// carry over from the step before when left out:
+//
+//   [[step]]
+//   level = 1
+//   gravity_ms = 800
+//   lock_delay_ms = 500
+//   are_ms = 100
+//
+//   [[step]]
+//   level = 10
+//   gravity_ms = 0
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CurveFile {
+    step: Vec<StepFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepFile {
+    level: u32,
+    gravity_ms: f64,
+    lock_delay_ms: Option<f64>,
+    are_ms: Option<f64>,
+}
+
+pub fn curves_dir() -> PathBuf {
+    history::data_dir().join("curves")
+}
+
+// The built-in curves, then any custom ones.
+pub fn available() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(curves_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .filter(|name| !SpeedCurve::BUILT_IN.iter().any(|built_in| built_in.eq_ignore_ascii_case(name)))
+        .collect();
+    names.sort();
+    SpeedCurve::BUILT_IN.iter().map(|name| name.to_string()).chain(names).collect()
+}
+
+impl SpeedCurve {
+    pub const BUILT_IN: [&'static str; 3] = ["Guideline", "NES", "TGM Death"];
+
+    // A built-in curve by name, or else a custom one, checked through.
+    pub fn load(name: &str) -> Result<Self, String> {
+        if let Some(curve) = Self::built_in(name) {
+            return Ok(curve);
+        }
+        let path = curves_dir().join(format!("{}.toml", name));
+        let text = fs::read_to_string(&path).map_err(|reason| format!("{}: {}", path.display(), reason))?;
+        Self::parse(name, &text).map_err(|reason| format!("{}: {}", path.display(), reason))
+    }
+
+    fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let file: CurveFile = toml::from_str(text).map_err(|reason| reason.to_string())?;
+        Ok(Self {
+            name: name.to_string(),
+            steps: validate(file.step)?,
+        })
+    }
+
+    fn built_in(name: &str) -> Option<Self> {
+        let name = *Self::BUILT_IN.iter().find(|built_in| built_in.eq_ignore_ascii_case(name))?;
+        let steps = match name {
+            // Seconds per row are (0.8 - (level - 1) * 0.007) ^ (level - 1),
+            // nearly 20G by level 20.
+            "Guideline" => (1..=20)
+                .map(|level| Step {
+                    level,
+                    gravity_ms: 1000.0 * (0.8 - (level - 1) as f64 * 0.007).powi(level as i32 - 1),
+                    lock_delay_ms: 500.0,
+                    are_ms: 0.0,
+                })
+                .collect(),
+            // The NES has no lock delay: a piece locks when its next drop
+            // fails. ARE is its shortest, 10 frames.
+            "NES" => (1..)
+                .zip(NES_GRAVITY)
+                .map(|(level, frames)| Step {
+                    level,
+                    gravity_ms: frames as f64 * NES_FRAME_MS,
+                    lock_delay_ms: frames as f64 * NES_FRAME_MS,
+                    are_ms: 10.0 * NES_FRAME_MS,
+                })
+                .collect(),
+            _ => (1..)
+                .zip(DEATH_LOCK.into_iter().zip(DEATH_ARE))
+                .map(|(level, (lock, are))| Step {
+                    level,
+                    gravity_ms: 0.0,
+                    lock_delay_ms: lock as f64 * TGM_FRAME_MS,
+                    are_ms: are as f64 * TGM_FRAME_MS,
+                })
+                .collect(),
+        };
+        Some(Self {
+            name: name.to_string(),
+            steps,
+        })
+    }
+
+    pub fn at(&self, level: u32) -> Speed {
+        let step = self.steps.iter().rev().find(|step| step.level <= level).unwrap_or(&self.steps[0]);
+        let duration = |ms: f64| Duration::from_secs_f64(ms / 1000.0);
+        Speed {
+            gravity: duration(step.gravity_ms),
+            lock_delay: duration(step.lock_delay_ms),
+            are: duration(step.are_ms),
+        }
+    }
+}
+
+fn validate(steps: Vec<StepFile>) -> Result<Vec<Step>, String> {
+    if steps.first().map(|step| step.level) != Some(1) {
+        return Err("the first step has to be for level 1".to_string());
+    }
+    let mut checked: Vec<Step> = Vec::with_capacity(steps.len());
+    for step in steps {
+        if let Some(last) = checked.last() {
+            if step.level <= last.level {
+                return Err(format!("level {} comes after level {}; steps go up by level", step.level, last.level));
+            }
+        }
+        if step.level > MAX_LEVEL {
+            return Err(format!("level {} is past {}", step.level, MAX_LEVEL));
+        }
+        let lock_delay_ms = step.lock_delay_ms.or(checked.last().map(|last| last.lock_delay_ms)).unwrap_or(500.0);
+        let are_ms = step.are_ms.or(checked.last().map(|last| last.are_ms)).unwrap_or(0.0);
+        let timings = [("gravity_ms", step.gravity_ms), ("lock_delay_ms", lock_delay_ms), ("are_ms", are_ms)];
+        if let Some((field, ms)) = timings.iter().find(|(_, ms)| !(0.0..=MAX_MS).contains(ms)) {
+            return Err(format!("level {}: {} is {}, expected 0 to {}", step.level, field, ms, MAX_MS));
+        }
+        checked.push(Step {
+            level: step.level,
+            gravity_ms: step.gravity_ms,
+            lock_delay_ms,
+            are_ms,
+        });
+    }
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(duration: Duration) -> f64 {
+        duration.as_secs_f64() * 1000.0
+    }
+
+    #[test]
+    fn built_in_curves() {
+        for name in SpeedCurve::BUILT_IN {
+            assert!(SpeedCurve::load(&name.to_lowercase()).is_ok_and(|curve| curve.name == name));
+        }
+
+        let guideline = SpeedCurve::load("Guideline").unwrap();
+        assert_eq!(guideline.at(1).gravity, Duration::from_secs(1));
+        assert!(guideline.at(2).gravity < guideline.at(1).gravity);
+        assert!(ms(guideline.at(20).gravity) < 1.0);
+        assert_eq!(guideline.at(99), guideline.at(20));
+        assert_eq!(guideline.at(0), guideline.at(1));
+
+        let nes = SpeedCurve::load("NES").unwrap();
+        assert!((ms(nes.at(1).gravity) - 48.0 * NES_FRAME_MS).abs() < 0.01);
+        assert_eq!(nes.at(1).lock_delay, nes.at(1).gravity);
+        assert!((ms(nes.at(30).gravity) - NES_FRAME_MS).abs() < 0.01);
+
+        let death = SpeedCurve::load("TGM Death").unwrap();
+        assert_eq!(death.at(1).gravity, Duration::ZERO);
+        assert!((ms(death.at(1).lock_delay) - 500.0).abs() < 0.01);
+        assert!((ms(death.at(6).are) - 4.0 * TGM_FRAME_MS).abs() < 0.01);
+    }
+
+    #[test]
+    fn custom_steps_carry_timings_over() {
+        let text = "[[step]]\nlevel = 1\ngravity_ms = 800\nare_ms = 100\n\n[[step]]\nlevel = 10\ngravity_ms = 0\n";
+        let curve = SpeedCurve::parse("custom", text).unwrap();
+        let first = curve.at(9);
+        assert_eq!(first.gravity, Duration::from_millis(800));
+        assert_eq!(first.lock_delay, Duration::from_millis(500));
+        assert_eq!(first.are, Duration::from_millis(100));
+        let last = curve.at(10);
+        assert_eq!(last.gravity, Duration::ZERO);
+        assert_eq!(last.are, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn rejects_bad_curves() {
+        let bad = [
+            "[[step]]\nlevel = 2\ngravity_ms = 800\n",
+            "[[step]]\nlevel = 1\ngravity_ms = 800\n[[step]]\nlevel = 1\ngravity_ms = 500\n",
+            "[[step]]\nlevel = 1\ngravity_ms = 800\n[[step]]\nlevel = 1000\ngravity_ms = 500\n",
+            "[[step]]\nlevel = 1\ngravity_ms = -1\n",
+            "[[step]]\nlevel = 1\ngravity_ms = 800\nlock_delay_ms = 100000\n",
+            "[[step]]\nlevel = 1\ngravity_ms = 800\nspeed = 2\n",
+            "step = []\n",
+        ];
+        for text in bad {
+            assert!(SpeedCurve::parse("custom", text).is_err(), "{}", text);
+        }
+    }
+}
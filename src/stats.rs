@@ -0,0 +1,100 @@
+use crate::i18n::tr;
+use crate::{Clear, Spin};
+use eframe::egui;
+use std::time::Duration;
+
+// Lines sent for a given combo count, following the guideline table.
+const COMBO_ATTACK: [u32; 11] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+#[derive(Clone, Default)]
+pub struct Stats {
+    pub time: Duration,
+    pub pieces: u32,
+    pub lines: u32,
+    pub clears: [u32; 4],
+    pub tspins: u32,
+    pub tspin_doubles: u32,
+    pub attack: u32,
+    pub max_combo: u32,
+}
+
+impl Stats {
+    pub fn pps(&self) -> f32 {
+        per_second(self.pieces, self.time)
+    }
+
+    pub fn apm(&self) -> f32 {
+        per_second(self.attack, self.time) * 60.0
+    }
+
+    pub fn record_clear(&mut self, clear: &Clear, combo: i32, back_to_back: bool) {
+        if clear.spin != Spin::None {
+            self.tspins += 1;
+        }
+        self.attack += clear.attack() + back_to_back as u32;
+        if clear.lines == 0 {
+            return;
+        }
+
+        let combo = combo.max(0) as u32;
+        self.lines += clear.lines;
+        self.clears[clear.lines as usize - 1] += 1;
+        self.max_combo = self.max_combo.max(combo);
+        if clear.spin == Spin::Full && clear.lines == 2 {
+            self.tspin_doubles += 1;
+        }
+        self.attack += COMBO_ATTACK[(combo as usize).min(COMBO_ATTACK.len() - 1)];
+    }
+
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        let rows = [
+            ("time", format_time(self.time)),
+            ("pieces", self.pieces.to_string()),
+            ("pps", format!("{:.2}", self.pps())),
+            ("lines", self.lines.to_string()),
+            ("apm", format!("{:.1}", self.apm())),
+            ("stats-singles", self.clears[0].to_string()),
+            ("stats-doubles", self.clears[1].to_string()),
+            ("stats-triples", self.clears[2].to_string()),
+            ("stats-tetrises", self.clears[3].to_string()),
+            ("stats-tspins", self.tspins.to_string()),
+            ("stats-max-combo", self.max_combo.to_string()),
+        ];
+        grid(ui, "stats", rows);
+    }
+
+    // Just the pace of a game, for an opponent's board beside your own.
+    pub fn summary_ui(&self, ui: &mut egui::Ui, id: &str) {
+        let rows = [
+            ("pieces", self.pieces.to_string()),
+            ("pps", format!("{:.2}", self.pps())),
+            ("lines", self.lines.to_string()),
+            ("apm", format!("{:.1}", self.apm())),
+        ];
+        grid(ui, id, rows);
+    }
+}
+
+fn grid(ui: &mut egui::Ui, id: &str, rows: impl IntoIterator<Item = (&'static str, String)>) {
+    egui::Grid::new(id).num_columns(2).show(ui, |ui| {
+        for (label, value) in rows {
+            ui.label(tr(label));
+            ui.label(value);
+            ui.end_row();
+        }
+    });
+}
+
+fn per_second(count: u32, time: Duration) -> f32 {
+    let seconds = time.as_secs_f32();
+    if seconds > 0.0 {
+        count as f32 / seconds
+    } else {
+        0.0
+    }
+}
+
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!("{}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100)
+}
@@ -0,0 +1,118 @@
+use eframe::egui;
+
+use crate::i18n::tr;
+use crate::keybinds::Action;
+
+// Second tap within this many seconds counts as a double tap.
+const DOUBLE_TAP: f64 = 0.3;
+// An upward swipe at least this many cells long, done within the time
+// limit, is a flick.
+const FLICK_CELLS: f32 = 2.0;
+const FLICK_SECONDS: f64 = 0.3;
+
+// Turns swipes and taps on the board into actions: dragging sideways moves
+// a cell at a time, dragging down soft drops, flicking up or double tapping
+// hard drops and a tap rotates. Mice are left alone until a touch is seen,
+// so clicking the board on a desktop does nothing.
+#[derive(Default)]
+pub struct Gestures {
+    touch_seen: bool,
+    // Drag distance in cells not yet turned into moves.
+    pending: egui::Vec2,
+    travel: egui::Vec2,
+    started_at: f64,
+    last_tap: Option<f64>,
+    soft_drop: bool,
+}
+
+impl Gestures {
+    pub fn soft_drop(&self) -> bool {
+        self.soft_drop
+    }
+
+    pub fn update(&mut self, response: &egui::Response, cell: f32, time: f64) -> Vec<Action> {
+        self.touch_seen |= response.ctx.input(|i| i.any_touches());
+        let mut actions = Vec::new();
+        if !self.touch_seen {
+            return actions;
+        }
+
+        if response.drag_started() {
+            self.pending = egui::Vec2::ZERO;
+            self.travel = egui::Vec2::ZERO;
+            self.started_at = time;
+        }
+        if response.dragged() {
+            let delta = response.drag_delta() / cell;
+            self.pending += delta;
+            self.travel += delta;
+            while self.pending.x >= 1.0 {
+                actions.push(Action::MoveRight);
+                self.pending.x -= 1.0;
+            }
+            while self.pending.x <= -1.0 {
+                actions.push(Action::MoveLeft);
+                self.pending.x += 1.0;
+            }
+            // Mostly downward drags soft drop for as long as they're held.
+            self.soft_drop = self.travel.y >= 1.0 && self.travel.y > self.travel.x.abs();
+        }
+        if response.drag_released() {
+            if -self.travel.y >= FLICK_CELLS && time - self.started_at <= FLICK_SECONDS {
+                actions.push(Action::HardDrop);
+            }
+            self.soft_drop = false;
+        }
+        if response.clicked() {
+            if self.last_tap.is_some_and(|at| time - at < DOUBLE_TAP) {
+                actions.push(Action::HardDrop);
+                self.last_tap = None;
+            } else {
+                actions.push(Action::RotateCw);
+                self.last_tap = Some(time);
+            }
+        }
+        actions
+    }
+}
+
+// Clickable controls under the board for playing without a keyboard.
+// Everything but soft drop acts once per click; soft drop lasts as long as
+// its button is held down.
+#[derive(Default)]
+pub struct Buttons {
+    soft_drop: bool,
+}
+
+impl Buttons {
+    pub fn soft_drop(&self) -> bool {
+        self.soft_drop
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, size: f32) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let layout = [
+            (Action::Hold, tr("hold")),
+            (Action::RotateCcw, "⟲".to_string()),
+            (Action::Rotate180, "180".to_string()),
+            (Action::RotateCw, "⟳".to_string()),
+            (Action::MoveLeft, "⬅".to_string()),
+            (Action::SoftDrop, "⬇".to_string()),
+            (Action::HardDrop, "⏬".to_string()),
+            (Action::MoveRight, "➡".to_string()),
+        ];
+        ui.horizontal_wrapped(|ui| {
+            for (action, label) in layout {
+                let button = egui::Button::new(egui::RichText::new(label).size(size * 0.5))
+                    .min_size(egui::vec2(size * 1.5, size));
+                let response = ui.add(button).on_hover_text(action.name());
+                if action == Action::SoftDrop {
+                    self.soft_drop = response.is_pointer_button_down_on();
+                } else if response.clicked() {
+                    actions.push(action);
+                }
+            }
+        });
+        actions
+    }
+}